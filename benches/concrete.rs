@@ -0,0 +1,77 @@
+// Compares decoding through the boxed iterator inside `SampleIterator` against the concrete per-format iterator
+// types, which let the compiler inline the decoder into the summing loop.
+
+use std::{fs, path::PathBuf};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+const SECONDS: u32 = 10;
+
+fn write_test_file() -> PathBuf {
+    let path = std::env::temp_dir().join("creak-bench-concrete.wav");
+    let spec = hound::WavSpec {
+        channels: CHANNELS,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for i in 0..SAMPLE_RATE * SECONDS {
+        let sample = ((i as f32 * 0.01).sin() * i16::MAX as f32) as i16;
+        for _ in 0..CHANNELS {
+            writer.write_sample(sample).unwrap();
+        }
+    }
+    writer.finalize().unwrap();
+    path
+}
+
+fn wav(c: &mut Criterion) {
+    let path = write_test_file();
+    let mut group = c.benchmark_group("concrete wav");
+    group.throughput(Throughput::Elements((SAMPLE_RATE * SECONDS * CHANNELS as u32) as u64));
+    group.sample_size(20);
+
+    group.bench_function("into_samples", |b| b.iter(|| {
+        creak::Decoder::open(&path).unwrap()
+            .into_samples().unwrap()
+            .map(|sample| sample.unwrap())
+            .sum::<f32>()
+    }));
+
+    group.bench_function("into_wav_samples", |b| b.iter(|| {
+        creak::Decoder::open(&path).unwrap()
+            .into_wav_samples().unwrap()
+            .map(|sample| sample.unwrap())
+            .sum::<f32>()
+    }));
+
+    group.finish();
+    fs::remove_file(&path).ok();
+}
+
+fn flac(c: &mut Criterion) {
+    let path = "samples/sine_1k_44100.flac";
+    let mut group = c.benchmark_group("concrete flac");
+
+    group.bench_function("into_samples", |b| b.iter(|| {
+        creak::Decoder::open(path).unwrap()
+            .into_samples().unwrap()
+            .map(|sample| sample.unwrap())
+            .sum::<f32>()
+    }));
+
+    group.bench_function("into_flac_samples", |b| b.iter(|| {
+        creak::Decoder::open(path).unwrap()
+            .into_flac_samples().unwrap()
+            .map(|sample| sample.unwrap())
+            .sum::<f32>()
+    }));
+
+    group.finish();
+}
+
+criterion_group!(benches, wav, flac);
+criterion_main!(benches);
@@ -0,0 +1,82 @@
+// Counts the heap allocations made while decoding a FLAC file, and compares creak against decoding the same file
+// with `claxon` into a fresh buffer for every block, which is what creak would do if it didn't reuse its block buffer.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn decode_creak(path: &Path) -> f32 {
+    creak::Decoder::open(path).unwrap()
+        .into_samples().unwrap()
+        .map(|sample| sample.unwrap())
+        .sum::<f32>()
+}
+
+fn decode_fresh_buffers(path: &Path) -> f32 {
+    let mut reader = claxon::FlacReader::open(path).unwrap();
+    let scale = (i32::MAX >> (32 - reader.streaminfo().bits_per_sample)) as f32;
+    let mut blocks = reader.blocks();
+    let mut sum = 0.0;
+    while let Some(block) = blocks.read_next_or_eof(vec![]).unwrap() {
+        for frame in 0..block.duration() {
+            for channel in 0..block.channels() {
+                sum += block.sample(channel, frame) as f32 / scale;
+            }
+        }
+    }
+    sum
+}
+
+fn count_allocations(f: impl FnOnce() -> f32) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    criterion::black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn flac(c: &mut Criterion) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("samples/sine_1k_44100.flac");
+    let info = creak::Decoder::open(&path).unwrap().info();
+    let samples = info.total_frames().unwrap() * info.channels() as u64;
+
+    let mut reader = claxon::FlacReader::open(&path).unwrap();
+    let mut blocks = reader.blocks();
+    let block_count = std::iter::from_fn(|| blocks.read_next_or_eof(vec![]).unwrap().map(|_| ())).count();
+    println!("flac/creak: {} allocation(s) decoding {} blocks", count_allocations(|| decode_creak(&path)), block_count);
+    println!("flac/claxon fresh buffers: {} allocation(s) decoding {} blocks", count_allocations(|| decode_fresh_buffers(&path)), block_count);
+
+    let mut group = c.benchmark_group("flac");
+    group.throughput(Throughput::Elements(samples));
+    group.bench_function("creak", |b| b.iter(|| decode_creak(&path)));
+    group.bench_function("claxon fresh buffers", |b| b.iter(|| decode_fresh_buffers(&path)));
+    group.finish();
+}
+
+criterion_group!(benches, flac);
+criterion_main!(benches);
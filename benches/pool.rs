@@ -0,0 +1,83 @@
+// Counts the heap allocations made while decoding the same short files over and over, as a server decoding many clips
+// would, and compares decoding with a `DecodeBufferPool` against letting every decoder allocate its own buffers.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use creak::{DecodeBufferPool, Decoder, DecoderOptions};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const SAMPLES: &[&str] = &[
+    "sine_1k_i16_44100.wav",
+    "sine_1k_44100.flac",
+];
+
+/// Number of times each file is decoded when counting allocations.
+const REPEATS: usize = 100;
+
+fn decode(path: &Path, pool: Option<&DecodeBufferPool>) -> f32 {
+    let options = DecoderOptions {
+        buffer_pool: pool.cloned(),
+        ..Default::default()
+    };
+    Decoder::open_with_options(path, options).unwrap()
+        .into_samples().unwrap()
+        .map(|sample| sample.unwrap())
+        .sum::<f32>()
+}
+
+fn count_allocations(f: impl Fn() -> f32) -> (usize, usize) {
+    let (allocations, bytes) = (ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED_BYTES.load(Ordering::Relaxed));
+    for _ in 0..REPEATS {
+        criterion::black_box(f());
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - allocations, ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes)
+}
+
+fn pool(c: &mut Criterion) {
+    for name in SAMPLES {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("samples").join(name);
+        let pool = DecodeBufferPool::new();
+        let (fresh, fresh_bytes) = count_allocations(|| decode(&path, None));
+        let (pooled, pooled_bytes) = count_allocations(|| decode(&path, Some(&pool)));
+        println!("pool/{}: {} decode(s) made {} allocation(s) ({} bytes) without a pool, {} ({} bytes) with one",
+            name, REPEATS, fresh, fresh_bytes, pooled, pooled_bytes);
+
+        let mut group = c.benchmark_group(format!("pool/{}", name));
+        group.bench_function("fresh buffers", |b| b.iter(|| decode(&path, None)));
+        group.bench_function("pooled buffers", |b| b.iter(|| decode(&path, Some(&pool))));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, pool);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+// Compares `Decoder::probe()`, which reads only the headers of the formats that allow it, against opening each file
+// in full and reading its info, which is what a library indexer would otherwise have to do.
+
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const SAMPLES: &[&str] = &[
+    "sine_1k_i16_44100.wav",
+    "sine_1k_44100.mp3",
+    "sine_1k_44100.flac",
+];
+
+fn probe(c: &mut Criterion) {
+    for name in SAMPLES {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("samples").join(name);
+        let mut group = c.benchmark_group(format!("probe/{}", name));
+        group.bench_function("probe", |b| b.iter(|| creak::Decoder::probe(&path).unwrap()));
+        group.bench_function("open", |b| b.iter(|| creak::Decoder::open(&path).unwrap().info()));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, probe);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+// Compares creak's bulk 16-bit WAV path against reading the same file one sample at a time through `hound`,
+// which is how creak decoded 16-bit WAV before the bulk path was added.
+
+use std::{fs, path::PathBuf};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+const SECONDS: u32 = 10;
+
+fn write_test_file() -> PathBuf {
+    let path = std::env::temp_dir().join("creak-bench-wav16.wav");
+    let spec = hound::WavSpec {
+        channels: CHANNELS,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for i in 0..SAMPLE_RATE * SECONDS {
+        let sample = ((i as f32 * 0.01).sin() * i16::MAX as f32) as i16;
+        for _ in 0..CHANNELS {
+            writer.write_sample(sample).unwrap();
+        }
+    }
+    writer.finalize().unwrap();
+    path
+}
+
+fn wav16(c: &mut Criterion) {
+    let path = write_test_file();
+    let mut group = c.benchmark_group("wav16");
+    group.throughput(Throughput::Elements((SAMPLE_RATE * SECONDS * CHANNELS as u32) as u64));
+    group.sample_size(20);
+
+    group.bench_function("creak", |b| b.iter(|| {
+        creak::Decoder::open(&path).unwrap()
+            .into_samples().unwrap()
+            .map(|sample| sample.unwrap())
+            .sum::<f32>()
+    }));
+
+    group.bench_function("hound per-sample", |b| b.iter(|| {
+        let samples: Box<dyn Iterator<Item = Result<f32, hound::Error>>> = Box::new(hound::WavReader::open(&path).unwrap()
+            .into_samples::<i16>()
+            .map(|sample| sample.map(|sample| sample as f32 / i16::MAX as f32)));
+        samples.map(|sample| sample.unwrap()).sum::<f32>()
+    }));
+
+    group.finish();
+    fs::remove_file(&path).ok();
+}
+
+criterion_group!(benches, wav16);
+criterion_main!(benches);
@@ -0,0 +1,384 @@
+use std::{collections::VecDeque, convert::TryFrom};
+
+use crate::{DecoderError, Sample, SampleIterator};
+
+/// Converts interleaved stereo samples to interleaved mid/side samples.
+pub(crate) struct MidSide {
+    samples: SampleIterator,
+    side: Option<Sample>,
+}
+
+impl MidSide {
+    #[inline]
+    pub fn new(samples: SampleIterator) -> Self {
+        Self {
+            samples,
+            side: None,
+        }
+    }
+}
+
+impl Iterator for MidSide {
+    type Item = Result<Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(side) = self.side.take() {
+            return Some(Ok(side))
+        }
+
+        let left = match self.samples.next()? {
+            Ok(left) => left,
+            Err(err) => return Some(Err(err)),
+        };
+        let right = match self.samples.next() {
+            Some(Ok(right)) => right,
+            Some(Err(err)) => return Some(Err(err)),
+            None => return Some(Err(DecoderError::IncompleteData)),
+        };
+
+        self.side = Some((left - right) / 2.0);
+        Some(Ok((left + right) / 2.0))
+    }
+}
+
+/// Applies linear fade-in and fade-out ramps to interleaved samples.
+pub(crate) struct Fades {
+    samples: SampleIterator,
+    channels: usize,
+    channel_cursor: usize,
+    frame: u64,
+    /// The number of frames `samples` yields, which is fewer than the stream holds if it was opened after a seek.
+    total_frames: u64,
+    fade_in_frames: u64,
+    fade_out_frames: u64,
+}
+
+impl Fades {
+    #[inline]
+    pub fn new(samples: SampleIterator, channels: usize, total_frames: u64, fade_in_frames: u64, fade_out_frames: u64) -> Self {
+        Self {
+            samples,
+            channels: channels.max(1),
+            channel_cursor: 0,
+            frame: 0,
+            total_frames,
+            fade_in_frames,
+            fade_out_frames,
+        }
+    }
+
+    #[inline]
+    fn gain(&self) -> f32 {
+        let mut gain = 1.0;
+        if self.frame < self.fade_in_frames {
+            gain *= self.frame as f32 / self.fade_in_frames as f32;
+        }
+        let frames_left = self.total_frames.saturating_sub(self.frame + 1);
+        if frames_left < self.fade_out_frames {
+            gain *= frames_left as f32 / self.fade_out_frames as f32;
+        }
+        gain
+    }
+}
+
+impl Iterator for Fades {
+    type Item = Result<Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let gain = self.gain();
+        let sample = self.samples.next()?;
+
+        // Every channel in a frame gets the same gain
+        self.channel_cursor += 1;
+        if self.channel_cursor == self.channels {
+            self.channel_cursor = 0;
+            self.frame += 1;
+        }
+
+        Some(sample.map(|sample| sample * gain))
+    }
+}
+
+/// Removes leading and trailing frames whose peak level is below a threshold.
+///
+/// Silent frames after the first non-silent one are held back until a non-silent frame proves they aren't trailing.
+pub(crate) struct TrimSilence {
+    samples: SampleIterator,
+    channels: usize,
+    threshold: Sample,
+    started: bool,
+    /// Silent frames that will be output if any non-silent frame follows them.
+    pending: VecDeque<Sample>,
+    /// Samples ready to be returned.
+    output: VecDeque<Sample>,
+    frame: Vec<Sample>,
+}
+
+impl TrimSilence {
+    #[inline]
+    pub fn new(samples: SampleIterator, channels: usize, threshold: Sample) -> Self {
+        Self {
+            samples,
+            channels: channels.max(1),
+            threshold,
+            started: false,
+            pending: VecDeque::new(),
+            output: VecDeque::new(),
+            frame: Vec::with_capacity(channels),
+        }
+    }
+}
+
+impl Iterator for TrimSilence {
+    type Item = Result<Sample, DecoderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(sample) = self.output.pop_front() {
+                return Some(Ok(sample))
+            }
+
+            self.frame.clear();
+            for sample in self.samples.by_ref().take(self.channels) {
+                match sample {
+                    Ok(sample) => self.frame.push(sample),
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+
+            // Whatever silence is still pending at the end of the stream is trailing silence
+            if self.frame.is_empty() {
+                return None
+            }
+
+            let peak = self.frame.iter().fold(0.0, |peak: Sample, sample| peak.max(sample.abs()));
+            if peak < self.threshold {
+                if self.started {
+                    self.pending.extend(self.frame.iter());
+                }
+            } else {
+                self.started = true;
+                self.output.append(&mut self.pending);
+                self.output.extend(self.frame.iter());
+            }
+        }
+    }
+}
+
+/// Truncates or pads interleaved samples to an exact number of samples.
+///
+/// A source that ends partway through a frame is padded from where it stopped, so the output is still whole frames.
+pub(crate) struct FitFrames {
+    samples: SampleIterator,
+    /// Number of samples left to output.
+    remaining: u64,
+    pad_value: Sample,
+    source_ended: bool,
+}
+
+impl FitFrames {
+    #[inline]
+    pub fn new(samples: SampleIterator, total_samples: u64, pad_value: Sample) -> Self {
+        Self {
+            samples,
+            remaining: total_samples,
+            pad_value,
+            source_ended: false,
+        }
+    }
+}
+
+impl Iterator for FitFrames {
+    type Item = Result<Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None
+        }
+        if !self.source_ended {
+            match self.samples.next() {
+                Some(Ok(sample)) => {
+                    self.remaining -= 1;
+                    return Some(Ok(sample))
+                },
+                Some(Err(err)) => {
+                    self.remaining = 0;
+                    return Some(Err(err))
+                },
+                None => self.source_ended = true,
+            }
+        }
+        self.remaining -= 1;
+        Some(Ok(self.pad_value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // An error ends the iterator early, so only the upper bound is certain
+        (0, usize::try_from(self.remaining).ok())
+    }
+}
+
+/// Mixes each frame of interleaved samples into a different number of channels with a fixed gain matrix.
+pub(crate) struct ChannelRemap {
+    samples: SampleIterator,
+    input_channels: usize,
+    /// Gain of each input channel in each output channel, one row per output channel.
+    matrix: Vec<Vec<Sample>>,
+    input: Vec<Sample>,
+    output: Vec<Sample>,
+    output_cursor: usize,
+}
+
+impl ChannelRemap {
+    #[inline]
+    pub fn new(samples: SampleIterator, matrix: Vec<Vec<Sample>>) -> Self {
+        let (input_channels, output_channels) = (samples.channels(), matrix.len());
+        Self {
+            samples,
+            input_channels,
+            matrix,
+            input: Vec::with_capacity(input_channels),
+            output: Vec::with_capacity(output_channels),
+            output_cursor: 0,
+        }
+    }
+}
+
+impl Iterator for ChannelRemap {
+    type Item = Result<Sample, DecoderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(sample) = self.output.get(self.output_cursor) {
+            self.output_cursor += 1;
+            return Some(Ok(*sample))
+        }
+
+        self.input.clear();
+        for sample in self.samples.by_ref().take(self.input_channels) {
+            match sample {
+                Ok(sample) => self.input.push(sample),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        match self.input.len() {
+            0 => return None,
+            n if n < self.input_channels => return Some(Err(DecoderError::IncompleteData)),
+            _ => {},
+        }
+
+        let input = &self.input;
+        self.output.clear();
+        self.output.extend(self.matrix.iter().map(|gains| gains.iter().zip(input).map(|(gain, sample)| gain * sample).sum::<Sample>()));
+        self.output_cursor = 0;
+        self.next()
+    }
+}
+
+/// Sums two streams of interleaved samples with the same channel count, sample by sample.
+pub(crate) struct Mix {
+    first: SampleIterator,
+    second: SampleIterator,
+    /// Whether to continue until both streams end, treating the one that ended first as silence.
+    pad: bool,
+    first_ended: bool,
+    second_ended: bool,
+}
+
+impl Mix {
+    #[inline]
+    pub fn new(first: SampleIterator, second: SampleIterator, pad: bool) -> Self {
+        Self {
+            first,
+            second,
+            pad,
+            first_ended: false,
+            second_ended: false,
+        }
+    }
+}
+
+impl Iterator for Mix {
+    type Item = Result<Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = if self.first_ended { None } else { self.first.next() };
+        let second = if self.second_ended { None } else { self.second.next() };
+        self.first_ended |= first.is_none();
+        self.second_ended |= second.is_none();
+        match (first, second) {
+            (Some(Err(err)), _) | (_, Some(Err(err))) => Some(Err(err)),
+            (Some(Ok(a)), Some(Ok(b))) => Some(Ok(a + b)),
+            (Some(Ok(sample)), None) | (None, Some(Ok(sample))) if self.pad => Some(Ok(sample)),
+            _ => None,
+        }
+    }
+}
+
+/// Iterates over the frames of decoded audio, each paired with its index in the stream.
+///
+/// Each item is one frame, holding one sample per channel. Decoding stops after the first error.
+pub struct EnumeratedFrames {
+    samples: SampleIterator,
+    channels: usize,
+    next_frame: u64,
+    finished: bool,
+}
+
+impl EnumeratedFrames {
+    #[inline]
+    pub(crate) fn new(samples: SampleIterator, start_frame: u64) -> Self {
+        Self {
+            channels: samples.channels().max(1),
+            samples,
+            next_frame: start_frame,
+            finished: false,
+        }
+    }
+
+    /// Gets the number of samples in each frame.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+}
+
+impl Iterator for EnumeratedFrames {
+    type Item = Result<(u64, Box<[Sample]>), DecoderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None
+        }
+
+        let mut frame = Vec::with_capacity(self.channels);
+        for sample in self.samples.by_ref().take(self.channels) {
+            match sample {
+                Ok(sample) => frame.push(sample),
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(err))
+                },
+            }
+        }
+        match frame.len() {
+            0 => {
+                self.finished = true;
+                return None
+            },
+            n if n < self.channels => {
+                self.finished = true;
+                return Some(Err(DecoderError::IncompleteData))
+            },
+            _ => {},
+        }
+
+        let index = self.next_frame;
+        self.next_frame += 1;
+        Some(Ok((index, frame.into_boxed_slice())))
+    }
+}
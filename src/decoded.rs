@@ -0,0 +1,124 @@
+use std::ops::Index;
+use std::time::Duration;
+
+use crate::{AudioInfo, Sample};
+
+/// A fully decoded audio file, held in memory for random access.
+///
+/// Returned by `decode_all()` and `Decoder::decode_all()`. The samples are stored interleaved, and indexing by frame
+/// gives that frame's samples, so `audio[frame][channel]` reads a single sample.
+#[derive(Debug, Clone)]
+pub struct DecodedAudio {
+    info: AudioInfo,
+    samples: Vec<Sample>,
+}
+
+impl DecodedAudio {
+    /// Wraps interleaved samples described by `info`. A partial frame at the end is dropped.
+    #[inline]
+    pub(crate) fn new(info: AudioInfo, mut samples: Vec<Sample>) -> Self {
+        let channels = info.channels().max(1);
+        samples.truncate(samples.len() / channels * channels);
+        Self { info, samples }
+    }
+
+    /// Gets the info of the file the audio was decoded from.
+    #[inline]
+    pub fn info(&self) -> &AudioInfo {
+        &self.info
+    }
+
+    /// Gets the number of channels in the audio.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.info.channels()
+    }
+
+    /// Gets the sample rate of the audio.
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.info.sample_rate()
+    }
+
+    /// Gets the number of frames that were decoded.
+    #[inline]
+    pub fn len_frames(&self) -> usize {
+        self.samples.len() / self.channels().max(1)
+    }
+
+    /// Gets the number of samples that were decoded, across all channels.
+    #[inline]
+    pub fn len_samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns `true` if no samples were decoded.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Gets the duration of the decoded audio, or `None` if the sample rate is zero.
+    #[inline]
+    pub fn duration(&self) -> Option<Duration> {
+        match self.sample_rate() {
+            0 => None,
+            rate => Some(Duration::from_secs_f64(self.len_frames() as f64 / rate as f64)),
+        }
+    }
+
+    /// Gets the samples of the specified frame, or `None` if it's past the end.
+    #[inline]
+    pub fn frame(&self, frame: usize) -> Option<&[Sample]> {
+        let channels = self.channels();
+        self.samples.get(frame * channels..(frame + 1) * channels)
+    }
+
+    /// Iterates over the frames, each a slice with one sample per channel.
+    #[inline]
+    pub fn frames(&self) -> std::slice::ChunksExact<'_, Sample> {
+        self.samples.chunks_exact(self.channels().max(1))
+    }
+
+    /// Iterates over the samples of a single channel. The iterator is empty if `channel` is out of range.
+    #[inline]
+    pub fn channel(&self, channel: usize) -> impl Iterator<Item = Sample> + '_ {
+        let channels = self.channels();
+        let frames = if channel < channels { self.len_frames() } else { 0 };
+        self.samples.iter().skip(channel).step_by(channels.max(1)).take(frames).copied()
+    }
+
+    /// Gets the interleaved samples.
+    #[inline]
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Consumes the audio and returns its interleaved samples.
+    #[inline]
+    pub fn into_interleaved(self) -> Vec<Sample> {
+        self.samples
+    }
+
+    /// Consumes the audio and returns one `Vec` of samples per channel.
+    pub fn into_planar(self) -> Vec<Vec<Sample>> {
+        (0..self.channels()).map(|channel| self.channel(channel).collect()).collect()
+    }
+}
+
+impl Index<usize> for DecodedAudio {
+    type Output = [Sample];
+
+    /// Gets the samples of the specified frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame` is past the end of the audio.
+    #[inline]
+    fn index(&self, frame: usize) -> &Self::Output {
+        match self.frame(frame) {
+            Some(samples) => samples,
+            None => panic!("frame {} is out of range for audio with {} frames", frame, self.len_frames()),
+        }
+    }
+}
@@ -1,27 +1,62 @@
-use std::{fmt::Display, fs::File, io::{self, BufReader}, path::Path};
+use std::{collections::VecDeque, convert::TryFrom, fmt::Display, fs::File, io::{self, BufReader, Cursor, Read, Seek}, ops::ControlFlow, path::Path, str::FromStr, time::Duration};
 
 use std::error::Error;
 
+use self::ogg::OggCodec;
 use self::raw::RawDecoder;
+use crate::{DecodeBufferPool, DecodedAudio, Dither, I16SampleIterator, LoudnessResult, Meters, PcmReader, PlanarBlocks, PlanarSamples, SpectrumFrames};
+use crate::adapters::{ChannelRemap, EnumeratedFrames, Fades, FitFrames, MidSide, Mix, TrimSilence};
 
+mod custom;
+mod events;
 mod raw;
+mod ogg;
+mod sniff;
 #[cfg(feature = "wav")] mod wav;
+#[cfg(feature = "wav")] mod riff;
+#[cfg(feature = "wav")] mod adpcm;
 #[cfg(feature = "vorbis")] mod vorbis;
 #[cfg(feature = "mp3")] mod mp3;
 #[cfg(feature = "flac")] mod flac;
+#[cfg(feature = "dsd")] mod dsd;
+#[cfg(feature = "flac")] mod md5;
+#[cfg(feature = "flac-parallel")] mod flac_parallel;
+
+pub use self::custom::{CustomBackend, FormatBackend};
+pub use self::events::{DecodeEvent, EventHandler};
+pub use self::raw::{decode_raw_sample, RawSamples};
+#[cfg(feature = "vorbis")] pub use self::vorbis::{VorbisInfo, VorbisSamples};
+#[cfg(feature = "wav")] pub use self::wav::{WavEncoding, WavInfo, WavSamples};
+#[cfg(feature = "wav")] pub use self::riff::{BroadcastInfo, InstrumentInfo};
+#[cfg(feature = "flac")] pub use self::flac::{FlacInfo, FlacSamples};
+#[cfg(feature = "mp3")] pub use self::mp3::Mp3Samples;
+#[cfg(feature = "dsd")] pub use self::dsd::DsdSamples;
 
 /// The type of decoded audio samples.
 pub type Sample = f32;
 
+/// A seekable stream of encoded audio that can be decoded with `Decoder::from_reader`.
+///
+/// This is implemented for every type that is `Read + Seek + Send`.
+pub trait MediaSource: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> MediaSource for T {}
+
+/// The buffered reader that each backend decodes from, regardless of where the audio came from.
+pub type SourceReader = BufReader<Box<dyn MediaSource>>;
+
 /// An audio decoder.
 ///
 /// Use `Decoder::open` or `Decoder::open_raw` to open an audio file and read samples.
+///
+/// `Decoder` is `Send`, so it can be opened on one thread and decoded on another.
 pub struct Decoder {
     decoder: FormatDecoder
 }
 
 /// Specification decsribing how to decode some raw audio samples.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RawAudioSpec {
     /// The sample rate of the audio.
     pub sample_rate: u32,
@@ -34,11 +69,211 @@ pub struct RawAudioSpec {
     /// The byte offset at which to start reading samples.
     pub start_offset: usize,
     /// The maximum number of frames to read.
+    ///
+    /// For planar audio, this is also the length of each channel's plane. If it's `None`, the planes are assumed to
+    /// divide the rest of the stream equally.
     pub max_frames: Option<usize>,
+    /// How the samples of each channel are arranged.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub layout: ChannelLayout,
+}
+
+impl RawAudioSpec {
+    /// Creates the spec for headerless interleaved PCM that starts at the beginning of the stream and runs to its end.
+    /// The other presets are shorthands for common combinations of these fields.
+    #[inline]
+    pub fn pcm(sample_rate: u32, channels: usize, sample_format: RawSampleFormat, endianness: Endian) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            sample_format,
+            endianness,
+            start_offset: 0,
+            max_frames: None,
+            layout: ChannelLayout::Interleaved,
+        }
+    }
+
+    /// Creates the spec for CD audio: 16-bit signed little-endian stereo at 44.1 kHz, as ripped to a `.cdda` or
+    /// headerless `.pcm` file.
+    #[inline]
+    pub fn cd_audio() -> Self {
+        Self::pcm_s16le(44100, 2)
+    }
+
+    /// Creates the spec for 16-bit signed little-endian PCM (`s16le` in FFmpeg's terms).
+    #[inline]
+    pub fn pcm_s16le(sample_rate: u32, channels: usize) -> Self {
+        Self::pcm(sample_rate, channels, RawSampleFormat::Signed16, Endian::Little)
+    }
+
+    /// Creates the spec for 32-bit float little-endian PCM (`f32le` in FFmpeg's terms).
+    #[inline]
+    pub fn pcm_f32le(sample_rate: u32, channels: usize) -> Self {
+        Self::pcm(sample_rate, channels, RawSampleFormat::Float32, Endian::Little)
+    }
+}
+
+/// Options that control how `Decoder::open_with_options` opens and decodes a file.
+///
+/// The defaults accept any file that's valid according to its format's specification.
+/// When decoding untrusted files, consider tightening the limits.
+#[derive(Debug, Clone)]
+pub struct DecoderOptions {
+    /// The largest block size, in frames, that a FLAC file may use.
+    /// Opening a file that declares larger blocks returns a `FormatError`, as does decoding a block larger than this.
+    ///
+    /// Defaults to 65535, the largest block size FLAC allows.
+    pub max_flac_block_size: u32,
+    /// The most MP3 frames that may be decoded from a single stream. Decoding a frame past the limit returns a
+    /// `FormatError` as the final item.
+    ///
+    /// Each MP3 frame decodes to at most 1152 frames of audio, so this caps the decoded length of a stream.
+    /// Defaults to `None`, which decodes every frame.
+    pub max_mp3_frames: Option<u64>,
+    /// The most frames of audio that a single Vorbis packet may decode to.
+    /// Opening a stream whose block sizes allow larger packets returns a `FormatError`, as does decoding a larger packet.
+    ///
+    /// Defaults to 4096, the most a packet can decode to with the largest block size Vorbis allows.
+    pub max_vorbis_packet_frames: u32,
+    /// Whether to identify the format from the file's contents if the file can't be opened as the format its extension
+    /// indicates (or it has no extension).
+    ///
+    /// Defaults to `false`.
+    pub detect_format_from_content: bool,
+    /// Whether to check that a file's contents match the format its extension indicates before opening it that way,
+    /// so that a mislabeled file fails with a clear `FormatError` instead of a confusing one from the wrong backend.
+    ///
+    /// Only contents that are recognized as a different format fail the check, since they could otherwise be anything.
+    /// Combined with `detect_format_from_content`, a mislabeled file is opened as the format of its contents instead.
+    /// Extensions claimed by a custom backend aren't checked. Defaults to `false`.
+    pub verify_magic_bytes: bool,
+    /// The most bytes that may be read from the start of a stream while identifying its format from its contents.
+    ///
+    /// Most formats are recognized from their first few bytes, but MP3 files can have data before the first frame,
+    /// so they're searched for up to this limit. If nothing is recognized within it, `UnrecognizedFormat` is returned.
+    ///
+    /// Defaults to 64 KiB.
+    pub max_probe_bytes: u64,
+    /// Whether to check that a FLAC stream decodes to exactly the number of frames declared in its STREAMINFO block.
+    ///
+    /// When enabled, a stream that ends early or runs long produces a `FormatError` as its final item.
+    /// Streams that don't declare their length aren't checked. When disabled, an early end is reported as
+    /// `IncompleteData` and any extra frames are silently dropped. Defaults to `false`.
+    pub verify_flac_length: bool,
+    /// Whether to trim the silence that MP3 encoders add to the start and end of the audio, so that looped or
+    /// concatenated files play without gaps.
+    ///
+    /// The delay and padding are read from the LAME tag in the Xing or Info frame at the start of the file, which also
+    /// gives the file's length for `AudioInfo::total_frames()`. The Xing frame, which decodes to silence, is dropped too.
+    /// Files without such a frame are decoded in full. Defaults to `false`.
+    pub gapless_mp3: bool,
+    /// Whether to carry on past a corrupt MP3 frame instead of failing with a `FormatError`.
+    ///
+    /// minimp3 already resynchronizes on its own past data that doesn't parse as a frame, so the frames caught here
+    /// are those whose sample rate or channel count differs from the rest of the stream. Each is replaced by silence
+    /// of the same length, which keeps the timing of the rest of the stream, and reported as a
+    /// `DecodeEvent::FrameSkipped`. Defaults to `false`.
+    pub reset_mp3_on_error: bool,
+    /// How to handle a WAV file whose sample rate doesn't agree with the byte rate in its `fmt` chunk.
+    ///
+    /// Defaults to `WavRateCheck::Ignore`, which trusts the sample rate, as most players do.
+    pub wav_rate_check: WavRateCheck,
+    /// Whether to carry on into further RIFF/WAVE files appended to a WAV file, decoding them all as one stream.
+    ///
+    /// Some capture tools append a new file rather than rewrite the header when they resume a recording. The appended
+    /// files must use the same sample format, channel count, and sample rate as the first, or the file fails to open
+    /// with a `FormatError`. Only the sample formats creak decodes itself can be joined this way, which excludes IMA
+    /// ADPCM. Defaults to `false`, which decodes just the first file.
+    pub concatenated_wav: bool,
+    /// A callback that receives diagnostic events while the file is opened and decoded, such as the chunks found in a
+    /// WAV header or the size of each FLAC block. See `DecodeEvent` for the events that are reported.
+    ///
+    /// Defaults to `None`, in which case no events are produced.
+    pub on_event: Option<EventHandler>,
+    /// A pool that decoders draw their block buffers from and return them to, to avoid reallocating them for every
+    /// file when decoding many files. See `DecodeBufferPool` for the formats that use it.
+    ///
+    /// Defaults to `None`, in which case every decoder allocates its own buffers.
+    pub buffer_pool: Option<DecodeBufferPool>,
+    /// Decoders for formats that creak doesn't support itself, tried in order before the built-in backends.
+    /// See `CustomBackend` for how files are matched to them.
+    ///
+    /// Defaults to an empty list.
+    pub custom_backends: Vec<CustomBackend>,
+}
+
+impl Default for DecoderOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_flac_block_size: u16::MAX as u32,
+            max_mp3_frames: None,
+            max_vorbis_packet_frames: 4096,
+            detect_format_from_content: false,
+            verify_magic_bytes: false,
+            max_probe_bytes: 64 * 1024,
+            verify_flac_length: false,
+            gapless_mp3: false,
+            reset_mp3_on_error: false,
+            wav_rate_check: WavRateCheck::Ignore,
+            concatenated_wav: false,
+            on_event: None,
+            buffer_pool: None,
+            custom_backends: vec![],
+        }
+    }
+}
+
+/// How a WAV file whose sample rate and byte rate disagree is handled. See `DecoderOptions::wav_rate_check`.
+///
+/// Uncompressed WAV files declare both a sample rate and a byte rate, which should equal the sample rate times the
+/// size of a frame. Some tools fill in one of them wrongly, and only the byte rate shows that something is off.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum WavRateCheck {
+    /// Use the declared sample rate without checking it.
+    #[default]
+    Ignore,
+    /// Replace the sample rate with the one implied by the byte rate. If the byte rate doesn't imply a whole number of
+    /// frames per second, the file fails to open as with `Reject`.
+    Repair,
+    /// Fail to open the file with a `FormatError` describing the mismatch.
+    Reject,
+}
+
+/// Describes how the samples of each channel are arranged in raw audio.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelLayout {
+    /// The samples of each frame are stored together, one channel after another. This is the usual layout.
+    #[default]
+    Interleaved,
+    /// All of the first channel's samples are stored first, followed by all of the second channel's, and so on.
+    ///
+    /// Each channel's plane starts on a byte boundary, even for packed sample formats.
+    /// Samples are still decoded in interleaved order.
+    Planar,
+}
+
+/// The speaker position that a channel is meant to be played from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    /// The low-frequency effects (subwoofer) channel.
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    BackCenter,
+    SideLeft,
+    SideRight,
 }
 
 /// Represents endianness.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Endian {
     /// Big Endian.
     Big,
@@ -47,7 +282,17 @@ pub enum Endian {
 }
 
 /// Represents supported sample formats for raw audio decoding.
-#[derive(Debug, Copy, Clone)]
+///
+/// The 12- and 20-bit formats are packed, meaning their samples are stored back to back without padding, so a sample
+/// can start partway through a byte. With little-endian byte order, each sample's least significant bits come first and
+/// fill each byte from its least significant bit (so a 12-bit sample takes a whole byte followed by the low half of the
+/// next); with big-endian, its most significant bits come first and fill each byte from its most significant bit.
+/// Any bits left over at the end of the stream that don't make up a whole sample are treated as padding.
+///
+/// More formats may be added in minor releases, so matches on this enum need a wildcard arm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum RawSampleFormat {
     /// 32-bit IEEE floating-point sample format.
     Float32,
@@ -57,10 +302,18 @@ pub enum RawSampleFormat {
     Unsigned8,
     /// Signed 8-bit integer sample format.
     Signed8,
+    /// Unsigned 12-bit integer sample format, packed so that two samples occupy three bytes.
+    Unsigned12,
+    /// Signed 12-bit integer sample format, packed so that two samples occupy three bytes.
+    Signed12,
     /// Unsigned 16-bit integer sample format.
     Unsigned16,
     /// Signed 16-bit integer sample format.
     Signed16,
+    /// Unsigned 20-bit integer sample format, packed so that two samples occupy five bytes.
+    Unsigned20,
+    /// Signed 20-bit integer sample format, packed so that two samples occupy five bytes.
+    Signed20,
     /// Unsigned 24-bit integer sample format.
     Unsigned24,
     /// Signed 24-bit integer sample format.
@@ -72,18 +325,75 @@ pub enum RawSampleFormat {
     /// Unsigned 64-bit integer sample format.
     Unsigned64,
     /// Signed 64-bit integer sample format.
-    Signed64
+    Signed64,
+    /// 8-bit G.711 A-law companded sample format.
+    ALaw,
+    /// 8-bit G.711 mu-law companded sample format.
+    MuLaw,
+}
+
+impl RawSampleFormat {
+    /// Gets the size of a single sample of this format, in bytes.
+    ///
+    /// Packed formats don't use a whole number of bytes per sample, so for those this is the number of bytes
+    /// a lone sample spans, rounded up.
+    #[inline]
+    pub fn size_bytes(&self) -> usize {
+        self.bits_per_sample().div_ceil(8)
+    }
+
+    /// Gets the number of bits that each sample of this format occupies.
+    #[inline]
+    pub fn bits_per_sample(&self) -> usize {
+        match self {
+            RawSampleFormat::Unsigned8 | RawSampleFormat::Signed8 | RawSampleFormat::ALaw | RawSampleFormat::MuLaw => 8,
+            RawSampleFormat::Unsigned12 | RawSampleFormat::Signed12 => 12,
+            RawSampleFormat::Unsigned16 | RawSampleFormat::Signed16 => 16,
+            RawSampleFormat::Unsigned20 | RawSampleFormat::Signed20 => 20,
+            RawSampleFormat::Unsigned24 | RawSampleFormat::Signed24 => 24,
+            RawSampleFormat::Float32 | RawSampleFormat::Unsigned32 | RawSampleFormat::Signed32 => 32,
+            RawSampleFormat::Float64 | RawSampleFormat::Unsigned64 | RawSampleFormat::Signed64 => 64,
+        }
+    }
+
+    /// Gets the number of samples and bytes in the smallest group of samples that ends on a byte boundary.
+    ///
+    /// This is a single sample for every format that isn't packed.
+    #[inline]
+    pub(crate) fn pack_group(&self) -> (usize, usize) {
+        let bits = self.bits_per_sample();
+        let samples = 8 / gcd(bits, 8);
+        (samples, samples * bits / 8)
+    }
+}
+
+#[inline]
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
 }
 
 /// Information about an opened audio file.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AudioInfo {
     sample_rate: u32,
     channels: usize,
     format: AudioFormat,
+    total_frames: Option<u64>,
 }
 
 impl AudioInfo {
+    /// Creates the info for a stream, such as one decoded by a custom `FormatBackend`.
+    #[inline]
+    pub fn new(format: AudioFormat, sample_rate: u32, channels: usize, total_frames: Option<u64>) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            format,
+            total_frames,
+        }
+    }
+
     /// Gets the sample rate of the audio.
     #[inline]
     pub fn sample_rate(&self) -> u32 {
@@ -101,10 +411,190 @@ impl AudioInfo {
     pub fn format(&self) -> AudioFormat {
         self.format
     }
+
+    /// Gets the total number of frames in the audio, if the format declares it up front.
+    ///
+    /// This is known for WAV, FLAC (when the encoder recorded it), and raw audio.
+    #[inline]
+    pub fn total_frames(&self) -> Option<u64> {
+        self.total_frames
+    }
+
+    /// Checks that the audio has the expected sample rate and channel count.
+    ///
+    /// Returns a `FormatError` describing the mismatch if either value differs.
+    pub fn expect(&self, sample_rate: u32, channels: usize) -> Result<(), DecoderError> {
+        match (self.sample_rate == sample_rate, self.channels == channels) {
+            (true, true) => Ok(()),
+            (false, true) => Err(DecoderError::format_error(self.format, format!(
+                "expected a sample rate of {}Hz, but the audio is {}Hz", sample_rate, self.sample_rate
+            ))),
+            (true, false) => Err(DecoderError::format_error(self.format, format!(
+                "expected {} channel(s), but the audio has {}", channels, self.channels
+            ))),
+            (false, false) => Err(DecoderError::format_error(self.format, format!(
+                "expected {} channel(s) at {}Hz, but the audio has {} channel(s) at {}Hz", channels, sample_rate, self.channels, self.sample_rate
+            ))),
+        }
+    }
+
+    /// Gets the duration of the audio, if the total number of frames is known.
+    #[inline]
+    pub fn duration(&self) -> Option<Duration> {
+        match (self.total_frames, self.sample_rate) {
+            (Some(frames), rate) if rate > 0 => Some(Duration::from_secs_f64(frames as f64 / rate as f64)),
+            _ => None,
+        }
+    }
+
+    /// Formats the duration of the audio as `H:MM:SS.mmm`, rounded to the nearest millisecond, if the total number
+    /// of frames is known.
+    ///
+    /// The hours aren't padded and grow past 9 as needed, so an hour and a half of audio is `"1:30:00.000"`.
+    pub fn duration_string(&self) -> Option<String> {
+        let (frames, rate) = match (self.total_frames, self.sample_rate) {
+            (Some(frames), rate) if rate > 0 => (frames as u128, rate as u128),
+            _ => return None,
+        };
+        // Work in whole milliseconds so that the rounding never produces 60 seconds
+        let millis = (frames * 1000 + rate / 2) / rate;
+        Some(format!("{}:{:02}:{:02}.{:03}", millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000))
+    }
+
+    /// Returns a copy of the info for audio transformed to `channels` channels.
+    #[inline]
+    pub(crate) fn with_channels(mut self, channels: usize) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Returns a copy of the info for audio transformed to `total_frames` frames long.
+    #[inline]
+    pub(crate) fn with_total_frames(mut self, total_frames: Option<u64>) -> Self {
+        self.total_frames = total_frames;
+        self
+    }
+}
+
+/// Describes what an opened decoder can do, so that features like seeking can be offered only when they'll work.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    seekable: bool,
+    known_length: bool,
+    random_access: bool,
+}
+
+impl Capabilities {
+    /// Whether `Decoder::seek()` is supported.
+    #[inline]
+    pub fn seekable(&self) -> bool {
+        self.seekable
+    }
+
+    /// Whether the total number of frames is known before decoding, so `AudioInfo::total_frames()` returns `Some`.
+    #[inline]
+    pub fn known_length(&self) -> bool {
+        self.known_length
+    }
+
+    /// Whether seeking to any frame takes constant time, rather than decoding everything before it.
+    #[inline]
+    pub fn random_access(&self) -> bool {
+        self.random_access
+    }
+}
+
+/// A rough indication of how much CPU time decoding a stream takes per sample, from `Decoder::estimated_decode_complexity()`.
+///
+/// The variants are ordered from cheapest to most expensive, so a scheduler can sort work by them. Multiply by
+/// `Decoder::estimated_sample_count()` to compare streams of different lengths.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DecodeComplexity {
+    /// Each sample is converted on its own, or from a small table: WAV and raw audio, including A-law, mu-law, and
+    /// IMA ADPCM.
+    Low,
+    /// Samples are predicted from the ones before them, or filtered down from a higher rate: FLAC and DSD.
+    Medium,
+    /// Samples are synthesized by a lossy transform codec: MP3 and Vorbis. Custom backends are also assumed to be here,
+    /// since their cost isn't known.
+    High,
+}
+
+/// A position in an audio file that decoding can be resumed from later, even by another process.
+///
+/// Get one from `SampleIterator::checkpoint()`, store it with `to_bytes()`, and pass it to `Decoder::resume()`
+/// (or `Decoder::resume_raw()`) to continue decoding from the same frame.
+/// Checkpoints are only available for formats with a fixed mapping between frames and file offsets: WAV and raw audio.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    format: AudioFormat,
+    frame: u64,
+}
+
+impl Checkpoint {
+    /// The size of a checkpoint serialized with `to_bytes()`, in bytes.
+    pub const SIZE: usize = 9;
+
+    /// Gets the format of the audio the checkpoint was taken from.
+    #[inline]
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    /// Gets the frame that decoding resumes from.
+    #[inline]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Serializes the checkpoint as a format tag byte followed by the frame index as a little-endian `u64`.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0] = match self.format {
+            AudioFormat::Wav => 0,
+            AudioFormat::Vorbis => 1,
+            AudioFormat::Mp3 => 2,
+            AudioFormat::Flac => 3,
+            AudioFormat::Dsd => 4,
+            AudioFormat::Raw => 5,
+            AudioFormat::Custom => 6,
+        };
+        bytes[1..].copy_from_slice(&self.frame.to_le_bytes());
+        bytes
+    }
+
+    /// Deserializes a checkpoint produced by `to_bytes()`, returning an `InvalidArgument` error if the bytes are malformed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecoderError> {
+        let (tag, frame) = match bytes {
+            [tag, frame @ ..] if frame.len() == 8 => (*tag, frame),
+            _ => return Err(DecoderError::InvalidArgument(format!("checkpoint must be {} bytes long, but {} were provided", Self::SIZE, bytes.len()))),
+        };
+        let format = match tag {
+            0 => AudioFormat::Wav,
+            1 => AudioFormat::Vorbis,
+            2 => AudioFormat::Mp3,
+            3 => AudioFormat::Flac,
+            4 => AudioFormat::Dsd,
+            5 => AudioFormat::Raw,
+            6 => AudioFormat::Custom,
+            other => return Err(DecoderError::InvalidArgument(format!("checkpoint has an unknown format tag {}", other))),
+        };
+        let mut frame_bytes = [0; 8];
+        frame_bytes.copy_from_slice(frame);
+        Ok(Self {
+            format,
+            frame: u64::from_le_bytes(frame_bytes),
+        })
+    }
 }
 
 /// Indicates the format of an audio stream.
-#[derive(Debug, Copy, Clone)]
+///
+/// Formats may be added in minor releases, so matches on this enum need a wildcard arm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum AudioFormat {
     /// WAV format.
     Wav,
@@ -114,8 +604,87 @@ pub enum AudioFormat {
     Mp3,
     /// FLAC format.
     Flac,
+    /// DSD audio (DSF or DSDIFF), converted to PCM.
+    Dsd,
     /// Raw audio samples.
     Raw,
+    /// A format decoded by a `FormatBackend` registered with `DecoderOptions::custom_backends`.
+    Custom,
+}
+
+impl AudioFormat {
+    /// Gets the file extensions that `Decoder::open` recognizes as this format, in lowercase and without the leading dot.
+    ///
+    /// The Ogg extensions are listed for both Vorbis and FLAC, since Ogg files can carry either codec. `"ogv"` is the
+    /// Ogg video extension; only the first audio stream of such files is decoded. Raw audio has no extensions,
+    /// as it must be opened with `Decoder::open_raw`, and custom formats list theirs in their `CustomBackend`.
+    #[inline]
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            AudioFormat::Wav => &["wav"],
+            AudioFormat::Vorbis => &["ogg", "oga", "ogv"],
+            AudioFormat::Mp3 => &["mp3"],
+            AudioFormat::Flac => &["flac", "ogg", "oga", "ogv"],
+            AudioFormat::Dsd => &["dsf", "dff"],
+            AudioFormat::Raw | AudioFormat::Custom => &[],
+        }
+    }
+
+    /// Returns `true` if the format stores audio without discarding any of it, so re-encoding the decoded samples to a
+    /// lossless format loses nothing further.
+    ///
+    /// WAV, FLAC, and raw audio are lossless, and MP3 and Vorbis are lossy. DSD is stored losslessly, but it's counted
+    /// as lossy here because creak filters it on the way to PCM, so the decoded samples aren't an exact copy. Nothing
+    /// is known about custom formats, so they aren't counted as lossless either. Some WAV and raw sample encodings are
+    /// lossy too; use `Decoder::is_lossless()` to take those into account.
+    #[inline]
+    pub fn is_lossless(&self) -> bool {
+        match self {
+            AudioFormat::Wav | AudioFormat::Flac | AudioFormat::Raw => true,
+            AudioFormat::Vorbis | AudioFormat::Mp3 | AudioFormat::Dsd | AudioFormat::Custom => false,
+        }
+    }
+
+    /// Returns `true` if the feature needed to decode this format is enabled.
+    #[inline]
+    fn is_enabled(&self) -> bool {
+        match self {
+            AudioFormat::Wav => cfg!(feature = "wav"),
+            AudioFormat::Vorbis => cfg!(feature = "vorbis"),
+            AudioFormat::Mp3 => cfg!(feature = "mp3"),
+            AudioFormat::Flac => cfg!(feature = "flac"),
+            AudioFormat::Dsd => cfg!(feature = "dsd"),
+            AudioFormat::Raw | AudioFormat::Custom => true,
+        }
+    }
+
+    /// The prefix used for this format in `FormatError` messages.
+    #[inline]
+    fn error_prefix(&self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Vorbis => "ogg",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Dsd => "dsd",
+            AudioFormat::Raw => "raw",
+            AudioFormat::Custom => "custom",
+        }
+    }
+}
+
+/// Gets every file extension that `Decoder::open` can decode with the features that are enabled, without duplicates.
+///
+/// The extensions are in lowercase and have no leading dot, which makes them suitable for file dialog filters.
+pub fn all_supported_extensions() -> Vec<&'static str> {
+    let mut extensions: Vec<&'static str> = vec![];
+    let formats = [AudioFormat::Wav, AudioFormat::Vorbis, AudioFormat::Mp3, AudioFormat::Flac, AudioFormat::Dsd];
+    for ext in formats.iter().filter(|format| format.is_enabled()).flat_map(|format| format.extensions()) {
+        if !extensions.contains(ext) {
+            extensions.push(ext);
+        }
+    }
+    extensions
 }
 
 impl Display for AudioFormat {
@@ -125,7 +694,40 @@ impl Display for AudioFormat {
             AudioFormat::Vorbis => write!(f, "Vorbis"),
             AudioFormat::Mp3 => write!(f, "MP3"),
             AudioFormat::Flac => write!(f, "FLAC"),
+            AudioFormat::Dsd => write!(f, "DSD"),
             AudioFormat::Raw => write!(f, "Raw"),
+            AudioFormat::Custom => write!(f, "Custom"),
+        }
+    }
+}
+
+impl FromStr for AudioFormat {
+    type Err = DecoderError;
+
+    /// Parses a format name or file extension, ignoring case.
+    ///
+    /// `"ogg"`, `"oga"`, and `"ogv"` are parsed as `Vorbis`, since that's the codec Ogg files usually carry.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "wav" | "wave" => Ok(AudioFormat::Wav),
+            "vorbis" | "ogg" | "oga" | "ogv" => Ok(AudioFormat::Vorbis),
+            "mp3" => Ok(AudioFormat::Mp3),
+            "flac" => Ok(AudioFormat::Flac),
+            "dsd" | "dsf" | "dff" => Ok(AudioFormat::Dsd),
+            "raw" | "pcm" => Ok(AudioFormat::Raw),
+            _ => Err(DecoderError::UnsupportedExtension(s.to_owned())),
+        }
+    }
+}
+
+impl TryFrom<&Path> for AudioFormat {
+    type Error = DecoderError;
+
+    /// Determines the format from the path's file extension, ignoring case.
+    fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        match path.extension() {
+            Some(ext) => ext.to_string_lossy().parse(),
+            None => Err(DecoderError::NoExtension),
         }
     }
 }
@@ -137,134 +739,1546 @@ impl Decoder {
     /// The currently recognized extensions are:
     ///
     /// * **.wav** - WAV.
-    /// * **.ogg** - Ogg Vorbis.
-    /// * **.mp3** - MP3.
+    /// * **.ogg** - Ogg Vorbis or Ogg FLAC, depending on the codec in the file.
+    /// * **.mp3** - MP3. Only mono and stereo streams are supported; stereo samples are interleaved left, then right.
     /// * **.flac** - FLAC.
+    /// * **.dsf**, **.dff** - DSD, converted to PCM at 1/64 of the DSD64 rate (44.1 kHz for any DSD rate).
+    ///
+    /// Other formats can be decoded by registering a `CustomBackend` with `DecoderOptions::custom_backends`.
     #[inline]
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DecoderError> {
-        Ok(Self {
-            decoder: FormatDecoder::open(path)?
-        })
+        Self::open_with_options(path, DecoderOptions::default())
     }
 
-    /// Attempts to open the specified audio file for raw sample decoding.
+    /// Attempts to open the specified audio file for decoding, using the specified options.
     ///
-    /// The format of the source samples is determined from the `RawAudioSpec` passed to the function.
+    /// Formats are detected the same way as `open()`.
     #[inline]
-    pub fn open_raw<P: AsRef<Path>>(path: P, spec: RawAudioSpec) -> Result<Self, DecoderError> {
-        let f = File::open(path).map_err(DecoderError::IOError)?;
+    pub fn open_with_options<P: AsRef<Path>>(path: P, options: DecoderOptions) -> Result<Self, DecoderError> {
         Ok(Self {
-            decoder: FormatDecoder::Raw(RawDecoder::new(BufReader::new(f), spec)?)
+            decoder: FormatDecoder::open(path, &options)?
         })
     }
-}
 
-impl Decoder {
-    /// Gets information about the audio, such as channel count and sample rate.
+    /// Attempts to open the specified audio file for decoding, falling back to identifying the format from the file's
+    /// contents if the file's extension is missing, unrecognized, or doesn't match what's actually in the file.
+    ///
+    /// This is equivalent to `open_with_options()` with `detect_format_from_content` enabled.
     #[inline]
-    pub fn info(&self) -> AudioInfo {
-        self.decoder.info()
+    pub fn open_auto<P: AsRef<Path>>(path: P) -> Result<Self, DecoderError> {
+        Self::open_with_options(path, DecoderOptions {
+            detect_format_from_content: true,
+            ..Default::default()
+        })
     }
 
-    /// Consumes the `Decoder` and returns an iterator over the samples.
-    /// Channels are interleaved.
-    #[inline]
-    pub fn into_samples(self) -> Result<SampleIterator, DecoderError> {
-        self.decoder.into_samples()
+    /// Opens the specified audio file and seeks to a checkpoint taken while decoding it earlier.
+    ///
+    /// Formats are detected the same way as `open()`. Returns a `FormatError` if the file isn't
+    /// in the format the checkpoint was taken from. Raw audio must be resumed with `resume_raw()` instead.
+    pub fn resume<P: AsRef<Path>>(path: P, checkpoint: &Checkpoint) -> Result<Self, DecoderError> {
+        let mut decoder = Self::open(path)?;
+        decoder.seek_to_checkpoint(checkpoint)?;
+        Ok(decoder)
     }
-}
 
-/// Iterates over decoded audio samples. Channels are interleaved.
-pub struct SampleIterator(Box<dyn Iterator<Item = Result<Sample, DecoderError>>>);
+    /// Opens the specified audio file for raw sample decoding and seeks to a checkpoint taken while decoding it earlier.
+    ///
+    /// `spec` must be the same spec the file was decoded with when the checkpoint was taken.
+    pub fn resume_raw<P: AsRef<Path>>(path: P, spec: RawAudioSpec, checkpoint: &Checkpoint) -> Result<Self, DecoderError> {
+        let mut decoder = Self::open_raw(path, spec)?;
+        decoder.seek_to_checkpoint(checkpoint)?;
+        Ok(decoder)
+    }
 
-impl Iterator for SampleIterator {
-    type Item = Result<Sample, DecoderError>;
+    fn seek_to_checkpoint(&mut self, checkpoint: &Checkpoint) -> Result<(), DecoderError> {
+        if checkpoint.format() != self.format() {
+            return Err(DecoderError::format_error(self.format(), format!(
+                "checkpoint was taken from {} audio, but the file contains {} audio", checkpoint.format(), self.format()
+            )))
+        }
+        self.seek(checkpoint.frame())
+    }
 
-    #[inline(always)]
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+    /// Attempts to decode audio from a seekable stream, such as an in-memory `Cursor`.
+    ///
+    /// Since there's no file extension to go by, the format is identified from the stream's contents.
+    /// Returns `DecoderError::UnrecognizedFormat` if the contents don't match any supported format, or `DecoderError::Protected`
+    /// if they're recognized as DRM-protected audio.
+    #[inline]
+    pub fn from_reader<R: Read + Seek + Send + 'static>(reader: R) -> Result<Self, DecoderError> {
+        Self::from_reader_with_options(reader, DecoderOptions::default())
     }
-}
 
-pub(crate) enum FormatDecoder {
-    Raw(self::raw::RawDecoder<BufReader<File>>),
-    #[cfg(feature = "wav")]
-    Wav(self::wav::WavDecoder),
-    #[cfg(feature = "vorbis")]
-    Vorbis(self::vorbis::VorbisDecoder),
-    #[cfg(feature = "mp3")]
-    Mp3(self::mp3::Mp3Decoder),
-    #[cfg(feature = "flac")]
-    Flac(self::flac::FlacDecoder),
-}
+    /// Attempts to decode audio from a seekable stream, using the specified options.
+    ///
+    /// Formats are detected the same way as `from_reader()`.
+    #[inline]
+    pub fn from_reader_with_options<R: Read + Seek + Send + 'static>(reader: R, options: DecoderOptions) -> Result<Self, DecoderError> {
+        Ok(Self {
+            decoder: FormatDecoder::from_reader(BufReader::new(Box::new(reader)), &options)?
+        })
+    }
 
-impl FormatDecoder {
+    /// Attempts to decode WAV audio from a stream that can't seek, such as standard input or a socket.
+    ///
+    /// The header is read up front, then samples are decoded in order as they arrive.
+    /// Since the length of a live stream isn't known, `AudioInfo::total_frames()` returns `None`, and the samples
+    /// run until the end of the `data` chunk, or the end of the stream if the chunk's length was left unset.
+    /// Seeking isn't supported. Only the sample formats creak can decode itself are accepted: 8/16/24/32-bit PCM,
+    /// 32/64-bit float, and A-law/mu-law.
     #[inline]
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DecoderError> {
-        macro_rules! get_decoder {
-            ($in_ext:expr, $($ext:literal => requires $feature:literal for $init:expr),*) => {
-                match $in_ext {
-                    $(
-                        #[cfg(feature = $feature)]
-                        $ext => { return Ok($init) }
-                        #[cfg(not(feature = $feature))]
-                        $ext => { return Err(DecoderError::DisabledExtension { feature: $feature, extension: $ext }) }
-                    )*
-                    other => return Err(DecoderError::UnsupportedExtension(other.to_owned()))
-                }
-            }
-        }
+    #[allow(unused_variables)]
+    pub fn from_wav_stream<R: Read + Send + 'static>(reader: R) -> Result<Self, DecoderError> {
+        #[cfg(feature = "wav")]
+        return Ok(Self {
+            decoder: FormatDecoder::Wav(self::wav::WavDecoder::new_stream(Box::new(reader))?)
+        });
+        #[cfg(not(feature = "wav"))]
+        Err(DecoderError::DisabledExtension { feature: "wav", extension: "wav" })
+    }
 
-        // Check the file extension to see which backend to use
-        if let Some(ext) = path.as_ref().extension().and_then(|ext| ext.to_str()) {
-            get_decoder!(ext,
-                "wav" => requires "wav" for FormatDecoder::Wav(self::wav::WavDecoder::open(path)?),
-                "ogg" => requires "vorbis" for FormatDecoder::Vorbis(self::vorbis::VorbisDecoder::open(path)?),
-                "mp3" => requires "mp3" for FormatDecoder::Mp3(self::mp3::Mp3Decoder::open(path)?),
-                "flac" => requires "flac" for FormatDecoder::Flac(self::flac::FlacDecoder::open(path)?)
-            )
-        }
-        Err(DecoderError::NoExtension)
+    /// Maps the specified audio file into memory and decodes from the mapped bytes.
+    ///
+    /// This avoids copying large files through read calls; the operating system pages the file in as it's decoded.
+    /// As with `from_reader()`, the format is identified from the file's contents rather than its extension.
+    ///
+    /// # Safety caveats
+    ///
+    /// The map reflects the file on disk for as long as the decoder lives. If another process truncates the file while
+    /// it's mapped, reading the missing pages raises a bus error (`SIGBUS`) that crashes the process, and if the file is
+    /// modified, the decoded samples change underneath the decoder. Only use this on files that nothing else writes to.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap<P: AsRef<Path>>(path: P) -> Result<Self, DecoderError> {
+        let file = File::open(path)?;
+        // SAFETY: the caveats above are the caller's to uphold; creak only ever reads from the map
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Self::from_reader(Cursor::new(map))
     }
 
+    /// Requests the audio at the specified URL and decodes it as it downloads.
+    ///
+    /// The stream is read through an `HttpReader`, which uses range requests to seek, so the format is identified from
+    /// its contents as with `from_reader()`. To configure the requests, open an `HttpReader` with your own
+    /// `ureq::Agent` and pass it to `from_reader()` instead.
+    #[cfg(feature = "http")]
     #[inline]
-    pub fn into_samples(self) -> Result<SampleIterator, DecoderError> {
-        match self {
-            FormatDecoder::Raw(d) => Ok(SampleIterator(d.into_samples()?)),
-            #[cfg(feature = "wav")]
-            FormatDecoder::Wav(d) => Ok(SampleIterator(d.into_samples()?)),
-            #[cfg(feature = "vorbis")]
-            FormatDecoder::Vorbis(d) => Ok(SampleIterator(d.into_samples()?)),
-            #[cfg(feature = "mp3")]
-            FormatDecoder::Mp3(d) => Ok(SampleIterator(d.into_samples()?)),
-            #[cfg(feature = "flac")]
-            FormatDecoder::Flac(d) => Ok(SampleIterator(d.into_samples()?))
-        }
+    pub fn open_url(url: &str) -> Result<Self, DecoderError> {
+        Self::from_reader(crate::HttpReader::open(url)?)
     }
 
+    /// Reads the whole of the specified audio file into memory, closes it, and decodes from the buffered bytes.
+    ///
+    /// This suits small files, and avoids holding many file handles open when scanning a large library.
+    /// As with `from_reader()`, the format is identified from the file's contents rather than its extension.
     #[inline]
-    pub fn info(&self) -> AudioInfo {
-        match self {
-            FormatDecoder::Raw(d) => d.info(),
-            #[cfg(feature = "wav")]
-            FormatDecoder::Wav(d) => d.info(),
-            #[cfg(feature = "vorbis")]
-            FormatDecoder::Vorbis(d) => d.info(),
-            #[cfg(feature = "mp3")]
-            FormatDecoder::Mp3(d) => d.info(),
+    pub fn open_buffered<P: AsRef<Path>>(path: P) -> Result<Self, DecoderError> {
+        let data = std::fs::read(path)?;
+        Self::from_reader(Cursor::new(data))
+    }
+
+    /// Reads just enough of the specified audio file to get its info, then closes it.
+    ///
+    /// This is useful for scanning many files without decoding them. Formats are detected the same way as `open()`.
+    /// WAV, MP3, and FLAC files are probed from their headers alone, so a file that probes successfully
+    /// may still fail to decode. MP3 files never report a frame count, whether probed or opened.
+    #[inline]
+    pub fn probe<P: AsRef<Path>>(path: P) -> Result<AudioInfo, DecoderError> {
+        FormatDecoder::probe(path, &DecoderOptions::default())
+    }
+
+    /// Attempts to open the specified audio file for raw sample decoding.
+    ///
+    /// The format of the source samples is determined from the `RawAudioSpec` passed to the function.
+    #[inline]
+    pub fn open_raw<P: AsRef<Path>>(path: P, spec: RawAudioSpec) -> Result<Self, DecoderError> {
+        let f = File::open(path)?;
+        if f.metadata()?.len() == 0 {
+            return Err(DecoderError::EmptyStream(AudioFormat::Raw))
+        }
+        Ok(Self {
+            decoder: FormatDecoder::Raw(RawDecoder::new(BufReader::new(Box::new(f) as Box<dyn MediaSource>), spec)?)
+        })
+    }
+
+    /// Attempts to open the specified audio file for raw sample decoding, reading it through a buffer of `capacity` bytes.
+    ///
+    /// A buffer larger than the default 8 KiB can reduce the number of reads issued when decoding large files.
+    #[inline]
+    pub fn open_raw_with_capacity<P: AsRef<Path>>(path: P, spec: RawAudioSpec, capacity: usize) -> Result<Self, DecoderError> {
+        let f = File::open(path)?;
+        if f.metadata()?.len() == 0 {
+            return Err(DecoderError::EmptyStream(AudioFormat::Raw))
+        }
+        Ok(Self {
+            decoder: FormatDecoder::Raw(RawDecoder::new(BufReader::with_capacity(capacity, Box::new(f) as Box<dyn MediaSource>), spec)?)
+        })
+    }
+
+    /// Attempts to open the specified WAV file, restricting decoding to the frames stored between the byte offsets
+    /// `byte_start` and `byte_end`.
+    ///
+    /// This allows a large file to be split between several workers. The range must lie within the file's `data`
+    /// chunk, and both ends must fall on frame boundaries, that is, a multiple of the frame size (the WAV block
+    /// alignment) from the start of the chunk. Otherwise, `DecoderError::InvalidArgument` is returned.
+    /// The resulting decoder behaves as if the range were the whole file, so `AudioInfo::total_frames()` and seeking
+    /// are relative to `byte_start`. Only the sample formats creak can decode itself are accepted: 8/16/24/32-bit PCM,
+    /// 32/64-bit float, and A-law/mu-law.
+    #[inline]
+    #[allow(unused_variables)]
+    pub fn open_range<P: AsRef<Path>>(path: P, byte_start: u64, byte_end: u64) -> Result<Self, DecoderError> {
+        #[cfg(feature = "wav")]
+        return Ok(Self {
+            decoder: FormatDecoder::Wav(self::wav::WavDecoder::new_range(BufReader::new(Box::new(File::open(path)?)), byte_start, byte_end)?)
+        });
+        #[cfg(not(feature = "wav"))]
+        Err(DecoderError::DisabledExtension { feature: "wav", extension: "wav" })
+    }
+
+    /// Attempts to open the specified WAV file, decoding the samples that start at the byte offset `data_offset`
+    /// instead of the ones the file's `data` chunk points to.
+    ///
+    /// This is an escape hatch for recovering audio from files with corrupt headers, and shouldn't be needed for
+    /// well-formed files. Only the chunks up to the end of the `fmt` chunk are parsed; the `data` and `fact` chunks are
+    /// ignored, and the samples are taken to run to the end of the file, so any chunks stored after the audio are
+    /// decoded as samples too. Set `data_offset` to the first byte of sample data, which is 44 in a canonical WAV file.
+    /// If it doesn't fall on a frame boundary, every sample is decoded from the wrong bytes, which often sounds like loud
+    /// noise. The same sample formats as `open_range()` are accepted, as well as IMA ADPCM.
+    #[inline]
+    #[allow(unused_variables)]
+    pub fn open_with_data_offset<P: AsRef<Path>>(path: P, data_offset: u64) -> Result<Self, DecoderError> {
+        #[cfg(feature = "wav")]
+        return Ok(Self {
+            decoder: FormatDecoder::Wav(self::wav::WavDecoder::new_with_data_offset(BufReader::new(Box::new(File::open(path)?)), data_offset)?)
+        });
+        #[cfg(not(feature = "wav"))]
+        Err(DecoderError::DisabledExtension { feature: "wav", extension: "wav" })
+    }
+
+    /// Attempts to open the specified audio file for raw sample decoding, restricting decoding to the frames stored
+    /// between the byte offsets `byte_start` and `byte_end`.
+    ///
+    /// The range is counted from the start of the file, and must start at or after `spec.start_offset`. Both ends must
+    /// fall on frame boundaries counted from `spec.start_offset`; for packed formats, they must also be byte boundaries,
+    /// which only every few frames are. Otherwise, `DecoderError::InvalidArgument` is returned. `spec.max_frames`
+    /// still limits the total number of frames counted from `spec.start_offset`. Planar audio isn't supported.
+    #[inline]
+    pub fn open_raw_range<P: AsRef<Path>>(path: P, spec: RawAudioSpec, byte_start: u64, byte_end: u64) -> Result<Self, DecoderError> {
+        Self::open_raw(path, self::raw::range_spec(&spec, byte_start, byte_end)?)
+    }
+}
+
+impl Decoder {
+    /// Gets information about the audio, such as channel count and sample rate.
+    #[inline]
+    pub fn info(&self) -> AudioInfo {
+        self.decoder.info()
+    }
+
+    /// Gets the sample rate of the audio.
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.info().sample_rate()
+    }
+
+    /// Gets the number of channels in the audio.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.info().channels()
+    }
+
+    /// Gets the original format of the audio.
+    #[inline]
+    pub fn format(&self) -> AudioFormat {
+        self.info().format()
+    }
+
+    /// Returns `true` if the stream decodes to exactly the samples that were encoded.
+    ///
+    /// This refines `AudioFormat::is_lossless()` with what's known about the particular stream: WAV and raw audio
+    /// encoded as A-law, mu-law, or IMA ADPCM are lossy, as is WAV in an encoding creak doesn't recognize, and custom
+    /// backends report it through `FormatBackend::is_lossless()`.
+    #[inline]
+    pub fn is_lossless(&self) -> bool {
+        self.decoder.is_lossless()
+    }
+
+    /// Gets the number of samples that decoding the rest of the stream will produce, across all channels, for
+    /// estimating how long it will take.
+    ///
+    /// This is exact for formats that declare their length up front, counted from the current position for seekable
+    /// ones. Returns `None` when the length isn't known, such as for Vorbis, or MP3 unless `DecoderOptions::gapless_mp3` found it.
+    #[inline]
+    pub fn estimated_sample_count(&self) -> Option<u64> {
+        let total_frames = self.info().total_frames()?;
+        let remaining = total_frames.saturating_sub(self.decoder.position().unwrap_or(0));
+        Some(remaining.saturating_mul(self.channels() as u64))
+    }
+
+    /// Gets a rough indication of how much CPU time decoding the stream takes per sample, based on its format.
+    #[inline]
+    pub fn estimated_decode_complexity(&self) -> DecodeComplexity {
+        match self.format() {
+            AudioFormat::Wav | AudioFormat::Raw => DecodeComplexity::Low,
+            AudioFormat::Flac | AudioFormat::Dsd => DecodeComplexity::Medium,
+            AudioFormat::Mp3 | AudioFormat::Vorbis | AudioFormat::Custom => DecodeComplexity::High,
+        }
+    }
+
+    /// Gets what the decoder supports for this particular stream.
+    ///
+    /// WAV and raw audio are seekable with random access and have a known length, except for WAV streams opened with
+    /// `from_wav_stream()`, which can't seek. FLAC has a known length when the encoder recorded it, and DSD always has
+    /// one, but neither is seekable yet. MP3 and Vorbis are neither. Custom backends report their own seeking support.
+    #[inline]
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            seekable: self.decoder.position().is_some(),
+            known_length: self.info().total_frames().is_some(),
+            random_access: self.decoder.random_access(),
+        }
+    }
+
+    /// Moves the start of decoding to the specified frame, so that the iterator returned by `into_samples()` begins there.
+    ///
+    /// Positions past the end of the audio are clamped to the end, so the iterator returns no samples.
+    /// Seeking is supported for WAV and raw audio; other formats return `DecoderError::SeekNotSupported`.
+    pub fn seek(&mut self, frame: u64) -> Result<(), DecoderError> {
+        self.decoder.seek(frame)
+    }
+
+    /// Moves the start of decoding to the frame at the specified time, rounded down to a whole frame.
+    ///
+    /// This is a convenience over `seek()`, so the same formats are supported. Positions past the end of the audio
+    /// are clamped to the end rather than treated as an error, so the iterator returns no samples.
+    #[inline]
+    pub fn seek_to(&mut self, position: Duration) -> Result<(), DecoderError> {
+        let frame = (position.as_secs_f64() * self.sample_rate() as f64).floor() as u64;
+        self.seek(frame)
+    }
+
+    /// Gets WAV-specific information about the stream, such as how its samples are encoded.
+    ///
+    /// Returns `None` if the audio is not WAV.
+    #[cfg(feature = "wav")]
+    #[inline]
+    pub fn wav_info(&self) -> Option<WavInfo> {
+        match &self.decoder {
+            FormatDecoder::Wav(d) => Some(d.wav_info()),
+            _ => None,
+        }
+    }
+
+    /// Gets the Broadcast WAV metadata from the file's `bext` chunk, such as its description and time reference.
+    ///
+    /// Returns `None` if the audio is not WAV or the file has no `bext` chunk before its sample data.
+    #[cfg(feature = "wav")]
+    #[inline]
+    pub fn broadcast_info(&self) -> Option<BroadcastInfo> {
+        match &self.decoder {
+            FormatDecoder::Wav(d) => d.broadcast_info(),
+            _ => None,
+        }
+    }
+
+    /// Gets the sampler metadata from the file's `inst` chunk, such as its root note and key range.
+    ///
+    /// Returns `None` if the audio is not WAV or the file has no `inst` chunk. The chunk is found before or after the
+    /// sample data, except for streams opened with `from_wav_stream()` and files opened with `open_range()` or
+    /// `open_with_data_offset()`, which only report an `inst` chunk stored before the sample data.
+    #[cfg(feature = "wav")]
+    #[inline]
+    pub fn instrument_info(&self) -> Option<InstrumentInfo> {
+        match &self.decoder {
+            FormatDecoder::Wav(d) => d.instrument_info(),
+            _ => None,
+        }
+    }
+
+    /// Gets the speaker position of each channel, in the order the channels are interleaved.
+    ///
+    /// Returns `None` if the format doesn't define a channel order. Currently only Ogg Vorbis does, and creak passes
+    /// its channels through in stream order, which the Vorbis specification defines for up to 8 channels:
+    ///
+    /// * **1 channel** - mono, reported as front center.
+    /// * **2 channels** - front left, front right.
+    /// * **3 channels** - front left, front center, front right.
+    /// * **4 channels** - front left, front right, back left, back right.
+    /// * **5 channels** - front left, front center, front right, back left, back right.
+    /// * **6 channels (5.1)** - front left, front center, front right, back left, back right, LFE.
+    /// * **7 channels (6.1)** - front left, front center, front right, side left, side right, back center, LFE.
+    /// * **8 channels (7.1)** - front left, front center, front right, side left, side right, back left, back right, LFE.
+    ///
+    /// Vorbis streams with more channels use an order chosen by the application that wrote them, so they return `None`.
+    /// Note that the Vorbis 5.1 order differs from the WAV order (L, R, C, LFE, Ls, Rs); use `reorder_channels()` to convert.
+    #[inline]
+    pub fn speakers(&self) -> Option<Vec<Speaker>> {
+        match &self.decoder {
+            #[cfg(feature = "vorbis")]
+            FormatDecoder::Vorbis(d) => self::vorbis::speakers(d.info().channels()).map(<[Speaker]>::to_vec),
+            _ => None,
+        }
+    }
+
+    /// Gets Vorbis-specific information about the stream, such as the vendor string and stream serial.
+    ///
+    /// Returns `None` if the audio is not Ogg Vorbis.
+    #[cfg(feature = "vorbis")]
+    #[inline]
+    pub fn vorbis_info(&self) -> Option<VorbisInfo> {
+        match &self.decoder {
+            FormatDecoder::Vorbis(d) => Some(d.vorbis_info()),
+            _ => None,
+        }
+    }
+
+    /// Gets FLAC-specific information about the stream, such as its MD5 signature.
+    ///
+    /// Returns `None` if the audio is not FLAC.
+    #[cfg(feature = "flac")]
+    #[inline]
+    pub fn flac_info(&self) -> Option<FlacInfo> {
+        match &self.decoder {
+            FormatDecoder::Flac(d) => Some(d.flac_info()),
+            _ => None,
+        }
+    }
+
+    /// Consumes the `Decoder`, decodes the entire stream, and checks the decoded audio against the MD5 signature
+    /// stored in the FLAC STREAMINFO block. Returns `Ok(true)` if the signature matches.
+    ///
+    /// Returns a `FormatError` if the audio is not FLAC or the file has no signature.
+    #[cfg(feature = "flac")]
+    pub fn verify_md5(self) -> Result<bool, DecoderError> {
+        match self.decoder {
+            FormatDecoder::Flac(d) => d.verify_md5(),
+            other => Err(DecoderError::format_error(other.info().format(), "MD5 verification is only supported for FLAC files")),
+        }
+    }
+
+    /// Gets the `hound` reader backing the decoder, if the audio is a WAV file decoded with `hound`.
+    ///
+    /// Returns `None` for other formats, and for WAV sample formats that creak decodes itself.
+    /// The reader is positioned at the start of the sample data; since it's borrowed immutably, it can't be used to read samples.
+    #[cfg(all(feature = "backend-access", feature = "wav"))]
+    #[inline]
+    pub fn as_wav_reader(&self) -> Option<&hound::WavReader<SourceReader>> {
+        match &self.decoder {
+            FormatDecoder::Wav(d) => d.hound_reader(),
+            _ => None,
+        }
+    }
+
+    /// Gets the `lewton` reader backing the decoder, if the audio is Ogg Vorbis.
+    #[cfg(all(feature = "backend-access", feature = "vorbis"))]
+    #[inline]
+    pub fn as_vorbis_reader(&self) -> Option<&lewton::inside_ogg::OggStreamReader<SourceReader>> {
+        match &self.decoder {
+            FormatDecoder::Vorbis(d) => Some(d.lewton_reader()),
+            _ => None,
+        }
+    }
+
+    /// Gets the `minimp3` decoder backing the decoder, if the audio is MP3.
+    ///
+    /// Note that the first frame has already been read from it in order to determine the audio info.
+    #[cfg(all(feature = "backend-access", feature = "mp3"))]
+    #[inline]
+    pub fn as_mp3_reader(&self) -> Option<&minimp3::Decoder<SourceReader>> {
+        match &self.decoder {
+            FormatDecoder::Mp3(d) => Some(d.minimp3_reader()),
+            _ => None,
+        }
+    }
+
+    /// Gets the `claxon` reader backing the decoder, if the audio is FLAC (native or in an Ogg container).
+    #[cfg(all(feature = "backend-access", feature = "flac"))]
+    #[inline]
+    pub fn as_flac_reader(&self) -> Option<&claxon::FlacReader<Box<dyn io::Read + Send>>> {
+        match &self.decoder {
+            FormatDecoder::Flac(d) => Some(d.claxon_reader()),
+            _ => None,
+        }
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the samples.
+    /// Channels are interleaved.
+    #[inline]
+    pub fn into_samples(self) -> Result<SampleIterator, DecoderError> {
+        self.decoder.into_samples()
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the samples, hard-clamped to the range [-1.0, 1.0].
+    /// Channels are interleaved.
+    ///
+    /// Clipping detection is enabled on the returned iterator, so `clipped_sample_count()` reports how many samples were clamped.
+    #[inline]
+    pub fn into_samples_clamped(self) -> Result<SampleIterator, DecoderError> {
+        Ok(self.into_samples()?.with_clamping())
+    }
+
+    /// Consumes the `Decoder` and decodes a FLAC file's frames on `threads` threads, then returns an iterator over the samples.
+    /// Passing 0 for `threads` uses one thread per CPU.
+    ///
+    /// The whole file is decoded before this returns, so it's best suited to batch processing of large files.
+    /// FLAC-in-Ogg streams are decoded serially, and non-FLAC audio returns a `FormatError`.
+    #[cfg(feature = "flac-parallel")]
+    pub fn into_samples_parallel(self, threads: usize) -> Result<SampleIterator, DecoderError> {
+        match self.decoder {
+            FormatDecoder::Flac(d) => {
+                let info = d.info();
+                Ok(SampleIterator::new(d.into_samples_parallel(threads)?, info))
+            },
+            other => Err(DecoderError::format_error(other.info().format(), "parallel decoding is only supported for FLAC files")),
+        }
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the samples of a WAV file, as a concrete type rather than the
+    /// boxed iterator inside `SampleIterator`, so that the compiler can inline the decoding into the caller's loop.
+    ///
+    /// The samples are the same as those from `into_samples()`, starting at the frame last seeked to, but the
+    /// `SampleIterator` extras such as checkpoints and metering aren't available. Returns a `FormatError` if the audio
+    /// isn't WAV.
+    #[cfg(feature = "wav")]
+    pub fn into_wav_samples(self) -> Result<WavSamples, DecoderError> {
+        match self.decoder {
+            FormatDecoder::Wav(d) => d.into_wav_samples(),
+            other => Err(DecoderError::format_error(other.info().format(), "audio is not WAV")),
+        }
+    }
+
+    /// Consumes the `Decoder` and returns an iterator of a concrete type over the samples of an MP3 file.
+    ///
+    /// See `into_wav_samples()` for how this differs from `into_samples()`. Returns a `FormatError` if the audio isn't MP3.
+    #[cfg(feature = "mp3")]
+    pub fn into_mp3_samples(self) -> Result<Mp3Samples, DecoderError> {
+        match self.decoder {
+            FormatDecoder::Mp3(d) => Ok(d.into_mp3_samples()),
+            other => Err(DecoderError::format_error(other.info().format(), "audio is not MP3")),
+        }
+    }
+
+    /// Consumes the `Decoder` and returns an iterator of a concrete type over the samples of an Ogg Vorbis file.
+    ///
+    /// See `into_wav_samples()` for how this differs from `into_samples()`. Returns a `FormatError` if the audio isn't
+    /// Vorbis.
+    #[cfg(feature = "vorbis")]
+    pub fn into_vorbis_samples(self) -> Result<VorbisSamples, DecoderError> {
+        match self.decoder {
+            FormatDecoder::Vorbis(d) => d.into_vorbis_samples(),
+            other => Err(DecoderError::format_error(other.info().format(), "audio is not Vorbis")),
+        }
+    }
+
+    /// Consumes the `Decoder` and returns an iterator of a concrete type over the samples of a FLAC or Ogg FLAC file.
+    ///
+    /// See `into_wav_samples()` for how this differs from `into_samples()`. Returns a `FormatError` if the audio isn't
+    /// FLAC.
+    #[cfg(feature = "flac")]
+    pub fn into_flac_samples(self) -> Result<FlacSamples, DecoderError> {
+        match self.decoder {
+            FormatDecoder::Flac(d) => Ok(d.into_flac_samples()),
+            other => Err(DecoderError::format_error(other.info().format(), "audio is not FLAC")),
+        }
+    }
+
+    /// Consumes the `Decoder` and returns an iterator of a concrete type over the PCM samples converted from a DSD file.
+    ///
+    /// See `into_wav_samples()` for how this differs from `into_samples()`. Returns a `FormatError` if the audio isn't
+    /// DSD.
+    #[cfg(feature = "dsd")]
+    pub fn into_dsd_samples(self) -> Result<DsdSamples, DecoderError> {
+        match self.decoder {
+            FormatDecoder::Dsd(d) => Ok(d.into_dsd_samples()),
+            other => Err(DecoderError::format_error(other.info().format(), "audio is not DSD")),
+        }
+    }
+
+    /// Consumes the `Decoder` and returns an iterator of a concrete type over the samples of a file opened with
+    /// `open_raw()`.
+    ///
+    /// See `into_wav_samples()` for how this differs from `into_samples()`. Returns a `FormatError` if the audio wasn't
+    /// opened as raw audio.
+    pub fn into_raw_samples(self) -> Result<RawSamples, DecoderError> {
+        match self.decoder {
+            FormatDecoder::Raw(d) => Ok(RawSamples::new(d.into_layout_samples())),
+            other => Err(DecoderError::format_error(other.info().format(), "audio was not opened as raw audio")),
+        }
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the samples converted to signed 16-bit integers.
+    /// Channels are interleaved.
+    ///
+    /// Pass `Dither::None` for bit-exact rounding, or one of the other `Dither` modes to mask quantization distortion.
+    #[inline]
+    pub fn into_samples_i16(self, dither: Dither) -> Result<I16SampleIterator, DecoderError> {
+        let channels = self.info().channels();
+        Ok(I16SampleIterator::new(self.into_samples()?, channels, dither))
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over its frames, each paired with its index.
+    ///
+    /// Indices count from the start of the audio, so after a `seek()` the first frame has the index that was sought to.
+    #[inline]
+    pub fn into_enumerated_frames(self) -> Result<EnumeratedFrames, DecoderError> {
+        let start_frame = self.decoder.position().unwrap_or(0);
+        Ok(EnumeratedFrames::new(self.into_samples()?, start_frame))
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over mid/side samples computed from a stereo source.
+    ///
+    /// Each output frame contains the mid channel `(L + R) / 2` followed by the side channel `(L - R) / 2`.
+    /// Returns a `FormatError` if the source doesn't have exactly two channels.
+    #[inline]
+    pub fn into_samples_midside(self) -> Result<SampleIterator, DecoderError> {
+        let channels = self.channels();
+        if channels != 2 {
+            return Err(DecoderError::format_error(self.format(), format!("mid/side conversion requires stereo audio, but the source has {} channel(s)", channels)))
+        }
+        let info = self.info();
+        Ok(SampleIterator::new(Box::new(MidSide::new(self.into_samples()?)), info))
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the samples mixed to exactly `target` channels.
+    ///
+    /// The returned iterator's `channels()` and `info()` report `target` channels. The supported conversions are:
+    ///
+    /// * **Same channel count** - passed through unchanged.
+    /// * **Mono to any** - the mono channel is copied to every output channel.
+    /// * **Any to mono** - the channels are averaged. 6-channel audio is first downmixed to stereo as below.
+    /// * **5.1 to stereo** - per ITU-R BS.775, `L' = L + 0.7071 C + 0.7071 Ls` and `R' = R + 0.7071 C + 0.7071 Rs`,
+    ///   with the LFE channel dropped. Both outputs are scaled by 0.4142 (1 / (1 + 2 × 0.7071)) so they can't clip.
+    ///
+    /// 6-channel audio is assumed to be in the standard WAV order (L, R, C, LFE, Ls, Rs).
+    /// Any other conversion returns a `FormatError`.
+    pub fn remap_channels(self, target: usize) -> Result<SampleIterator, DecoderError> {
+        const CENTER_GAIN: Sample = std::f32::consts::FRAC_1_SQRT_2;
+        const SURROUND_SCALE: Sample = 1.0 / (1.0 + 2.0 * CENTER_GAIN);
+
+        let channels = self.channels();
+        let matrix: Vec<Vec<Sample>> = match (channels, target) {
+            (n, m) if n == m => return self.into_samples(),
+            (_, 0) | (0, _) => return Err(DecoderError::format_error(self.format(), "cannot remap audio to or from zero channels")),
+            (1, m) => vec![vec![1.0]; m],
+            (6, 2) => vec![
+                [1.0, 0.0, CENTER_GAIN, 0.0, CENTER_GAIN, 0.0].iter().map(|gain| gain * SURROUND_SCALE).collect(),
+                [0.0, 1.0, CENTER_GAIN, 0.0, 0.0, CENTER_GAIN].iter().map(|gain| gain * SURROUND_SCALE).collect(),
+            ],
+            (6, 1) => vec![[1.0, 1.0, 2.0 * CENTER_GAIN, 0.0, CENTER_GAIN, CENTER_GAIN].iter().map(|gain| gain * SURROUND_SCALE / 2.0).collect()],
+            (n, 1) => vec![vec![1.0 / n as Sample; n]],
+            (n, m) => return Err(DecoderError::format_error(self.format(), format!("cannot remap {} channel(s) to {}", n, m))),
+        };
+        let info = self.info().with_channels(target);
+        Ok(SampleIterator::new(Box::new(ChannelRemap::new(self.into_samples()?, matrix)), info))
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the samples with the channels rearranged into the order given by
+    /// `target`, for feeding audio to an engine that expects a different channel order than the format uses.
+    ///
+    /// The returned iterator's `channels()` and `info()` report `target.len()` channels. Source channels missing from `target` are dropped,
+    /// and a speaker listed twice gets a copy of the same channel. No mixing is done, so every speaker in `target` must
+    /// be present in the source, or an `InvalidArgument` error is returned. Formats for which `speakers()` returns `None`
+    /// have no known channel order, and return a `FormatError`.
+    pub fn reorder_channels(self, target: &[Speaker]) -> Result<SampleIterator, DecoderError> {
+        let source = match self.speakers() {
+            Some(source) => source,
+            None => return Err(DecoderError::format_error(self.format(), format!("the channel order of this {} stream is unknown", self.format()))),
+        };
+        if target == &source[..] {
+            return self.into_samples()
+        }
+        let mut matrix = Vec::with_capacity(target.len());
+        for speaker in target {
+            let channel = match source.iter().position(|source_speaker| source_speaker == speaker) {
+                Some(channel) => channel,
+                None => return Err(DecoderError::InvalidArgument(format!("source has no {:?} channel to reorder", speaker))),
+            };
+            let mut gains = vec![0.0; source.len()];
+            gains[channel] = 1.0;
+            matrix.push(gains);
+        }
+        let info = self.info().with_channels(target.len());
+        Ok(SampleIterator::new(Box::new(ChannelRemap::new(self.into_samples()?, matrix)), info))
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the samples with linear fade-in and fade-out ramps applied.
+    ///
+    /// The fades are applied per frame, so all channels are ramped uniformly.
+    ///
+    /// A fade-out requires knowing where the audio ends, so it's only available for formats that declare their length
+    /// up front (WAV, FLAC, and raw audio). Requesting a nonzero `fade_out` for any other format returns a `FormatError`.
+    ///
+    /// After a seek, the fade-in starts from the current position rather than the start of the stream.
+    pub fn with_fades(self, fade_in: Duration, fade_out: Duration) -> Result<SampleIterator, DecoderError> {
+        let info = self.info();
+        let to_frames = |duration: Duration| (duration.as_secs_f64() * info.sample_rate() as f64).round() as u64;
+        let remaining_frames = match info.total_frames() {
+            Some(frames) => frames.saturating_sub(self.decoder.position().unwrap_or(0)),
+            None if fade_out.is_zero() => u64::MAX,
+            None => return Err(DecoderError::format_error(info.format(), format!("fade-out requires a known length, which {} audio doesn't provide", info.format())))
+        };
+        let fades = Fades::new(self.into_samples()?, info.channels(), remaining_frames, to_frames(fade_in), to_frames(fade_out));
+        Ok(SampleIterator::new(Box::new(fades), info))
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the samples with a soft limiter applied.
+    ///
+    /// Samples within [-`threshold`, `threshold`] pass through unchanged. Above the threshold, the excess is compressed
+    /// with a tanh curve that approaches full scale without reaching it:
+    ///
+    /// `y = sign(x) * (t + (1 - t) * tanh((|x| - t) / (1 - t)))`
+    ///
+    /// The curve is continuous and has a slope of 1 at the threshold, so there's no audible corner, but every sample
+    /// above the threshold is colored somewhat. A lower threshold is gentler on peaks but colors more of the signal.
+    /// Unlike `SampleIterator::with_clamping()`, nothing is discarded.
+    ///
+    /// `threshold` must be in the range [0.0, 1.0), or an `InvalidArgument` error is returned.
+    pub fn with_limiter(self, threshold: f32) -> Result<SampleIterator, DecoderError> {
+        if !(0.0..1.0).contains(&threshold) {
+            return Err(DecoderError::InvalidArgument(format!("limiter threshold must be in the range [0.0, 1.0), but was {}", threshold)))
+        }
+        let knee = 1.0 - threshold;
+        Ok(self.into_samples()?.map_samples(move |sample| {
+            let magnitude = sample.abs();
+            if magnitude <= threshold {
+                sample
+            } else {
+                (threshold + knee * ((magnitude - threshold) / knee).tanh()).copysign(sample)
+            }
+        }))
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the samples with leading and trailing silence removed.
+    ///
+    /// A frame counts as silent if the peak level across its channels is below `threshold_dbfs`.
+    /// Trailing silence is detected by holding back runs of silent frames until a louder frame follows them,
+    /// so this works on any source, but a long silent stretch in the middle of the audio is buffered in memory.
+    ///
+    /// How much is trimmed isn't known until the end is reached, so the returned iterator's `info()` has no total frames.
+    pub fn trim_silence(self, threshold_dbfs: f32) -> Result<SampleIterator, DecoderError> {
+        let info = self.info().with_total_frames(None);
+        let channels = info.channels();
+        let threshold = 10f32.powf(threshold_dbfs / 20.0);
+        Ok(SampleIterator::new(Box::new(TrimSilence::new(self.into_samples()?, channels, threshold)), info))
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over exactly `frames` frames of audio, such as for a model that
+    /// takes fixed-length clips.
+    ///
+    /// Longer audio is cut off after `frames` frames, and shorter audio is followed by frames in which every sample is
+    /// `pad_value`. If the source ends partway through a frame, the rest of that frame is padded too. An error from the
+    /// source is passed through and ends the iterator, so only then are fewer samples returned. The returned iterator's
+    /// `info()` reports `frames` as the total frames.
+    pub fn fit_frames(self, frames: u64, pad_value: Sample) -> Result<SampleIterator, DecoderError> {
+        let info = self.info().with_total_frames(Some(frames));
+        let total_samples = frames.saturating_mul(info.channels() as u64);
+        Ok(SampleIterator::new(Box::new(FitFrames::new(self.into_samples()?, total_samples, pad_value)), info))
+    }
+
+    /// Consumes the `Decoder` and measures the integrated loudness and true-peak level of the audio, per ITU-R BS.1770-4.
+    ///
+    /// The samples are processed in a single streaming pass, so the whole file is never held in memory.
+    /// Channels are weighted according to their position; 5- and 6-channel audio is assumed to be in the standard
+    /// WAV order (L, R, C, [LFE,] Ls, Rs), and any other layout weighs every channel equally.
+    #[inline]
+    pub fn measure_loudness(self) -> Result<LoudnessResult, DecoderError> {
+        let info = self.info();
+        crate::loudness::measure(&info, self.into_samples()?)
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over the magnitude spectra of the audio, for visualization.
+    ///
+    /// The audio is downmixed to mono as with `remap_channels(1)`, then analyzed in Hann-windowed blocks of `fft_size`
+    /// samples, each starting `hop` samples after the last. `fft_size` must be a power of two no smaller than 2, and `hop`
+    /// must be nonzero; otherwise an `InvalidArgument` error is returned. See `SpectrumFrames` for how the magnitudes are scaled.
+    pub fn spectrum_frames(self, fft_size: usize, hop: usize) -> Result<SpectrumFrames, DecoderError> {
+        if fft_size < 2 || !fft_size.is_power_of_two() {
+            return Err(DecoderError::InvalidArgument(format!("FFT size must be a power of two of at least 2, but was {}", fft_size)))
+        }
+        if hop == 0 {
+            return Err(DecoderError::InvalidArgument("spectrum hop size must be nonzero".to_owned()))
+        }
+        Ok(SpectrumFrames::new(self.remap_channels(1)?, fft_size, hop))
+    }
+
+    /// Consumes the `Decoder` and computes the minimum and maximum sample of each bucket of `samples_per_peak` frames,
+    /// for drawing a waveform overview without keeping the decoded audio around.
+    ///
+    /// The audio is downmixed to mono as with `remap_channels(1)` before it's bucketed, and the last bucket may cover
+    /// fewer frames than the rest. `samples_per_peak` must be nonzero; otherwise an `InvalidArgument` error is returned.
+    /// Decoding stops at the first error.
+    pub fn compute_peaks(self, samples_per_peak: usize) -> Result<Vec<(f32, f32)>, DecoderError> {
+        if samples_per_peak == 0 {
+            return Err(DecoderError::InvalidArgument("peak buckets must hold at least one sample".to_owned()))
+        }
+        let buckets = self.info().total_frames().map_or(0, |frames| frames.div_ceil(samples_per_peak as u64));
+        let mut peaks = Vec::with_capacity(buckets.min(MAX_RESERVED_LEN as u64) as usize);
+        let mut bucket = (Sample::INFINITY, Sample::NEG_INFINITY);
+        let mut bucket_len = 0;
+        for sample in self.remap_channels(1)? {
+            let sample = sample?;
+            bucket = (bucket.0.min(sample), bucket.1.max(sample));
+            bucket_len += 1;
+            if bucket_len == samples_per_peak {
+                peaks.push(bucket);
+                bucket = (Sample::INFINITY, Sample::NEG_INFINITY);
+                bucket_len = 0;
+            }
+        }
+        if bucket_len > 0 {
+            peaks.push(bucket);
+        }
+        Ok(peaks)
+    }
+
+    /// Consumes the `Decoder` and decodes all of its samples into memory.
+    ///
+    /// Decoding stops at the first error.
+    pub fn decode_all(self) -> Result<DecodedAudio, DecoderError> {
+        let info = self.info();
+        let declared_samples = info.total_frames().unwrap_or(0).saturating_mul(info.channels() as u64);
+        let mut samples = Vec::with_capacity(declared_samples.min(MAX_RESERVED_LEN as u64) as usize);
+        for sample in self.into_samples()? {
+            samples.push(sample?);
+        }
+        Ok(DecodedAudio::new(info, samples))
+    }
+
+    /// Consumes the `Decoder` and decodes samples frame by frame into `out`, a caller-allocated matrix of shape
+    /// `[frames, channels_stride]` stored in row-major order, and returns the number of frames written.
+    ///
+    /// Frame `i` is written to `out[i * channels_stride..i * channels_stride + channels()]`, and any columns past the
+    /// last channel are left as they were. Decoding stops when `out` can't fit another frame or the audio ends. A partial
+    /// frame at the end of the audio isn't counted. `channels_stride` must be at least `channels()` and nonzero, or an
+    /// `InvalidArgument` error is returned. Decoding stops at the first error, leaving the frames before it in `out`.
+    pub fn read_to_matrix(self, out: &mut [Sample], channels_stride: usize) -> Result<usize, DecoderError> {
+        let channels = self.channels();
+        if channels_stride == 0 || channels_stride < channels {
+            return Err(DecoderError::InvalidArgument(format!("matrix stride of {} can't fit {} channel(s)", channels_stride, channels)))
+        }
+        // The last row doesn't need its padding columns, so it only has to fit the channels
+        let capacity = match out.len().checked_sub(channels) {
+            Some(rest) => rest / channels_stride + 1,
+            None => 0,
+        };
+        let mut samples = self.into_samples()?;
+        for frame in 0..capacity {
+            let row = &mut out[frame * channels_stride..frame * channels_stride + channels];
+            for slot in row {
+                match samples.next() {
+                    Some(sample) => *slot = sample?,
+                    None => return Ok(frame),
+                }
+            }
+        }
+        Ok(capacity)
+    }
+
+    /// Consumes the `Decoder` and decodes all of the remaining samples into planar form, with each channel's samples
+    /// stored contiguously.
+    ///
+    /// None of the formats creak decodes store their samples this way, so the whole stream is decoded interleaved and
+    /// then rearranged. While that happens both copies are held in memory, which is twice the size of the decoded audio.
+    /// Use `into_planar_blocks()` to bound the memory used instead. Decoding stops at the first error.
+    pub fn into_planar(self) -> Result<PlanarSamples, DecoderError> {
+        let audio = self.decode_all()?;
+        Ok(PlanarSamples::from_interleaved(audio.samples(), audio.channels(), audio.sample_rate()))
+    }
+
+    /// Consumes the `Decoder` and returns an iterator over planar blocks of `block_frames` frames each.
+    ///
+    /// Only one block is decoded and rearranged at a time, so memory use is bounded by the block size however long the
+    /// stream is. `block_frames` must be nonzero; otherwise an `InvalidArgument` error is returned.
+    pub fn into_planar_blocks(self, block_frames: usize) -> Result<PlanarBlocks, DecoderError> {
+        if block_frames == 0 {
+            return Err(DecoderError::InvalidArgument("planar blocks must hold at least one frame".to_owned()))
+        }
+        Ok(PlanarBlocks::new(self.into_samples()?, block_frames))
+    }
+
+    /// Consumes the `Decoder` and passes the decoded samples to `f` in blocks, until the audio ends or `f` returns `Break`.
+    ///
+    /// Each block holds up to `DECODE_BLOCK_FRAMES` whole frames of interleaved samples; only the final block may be shorter.
+    /// Decoding stops at the first error, after any samples decoded before it have been passed to `f`.
+    pub fn decode_with<F: FnMut(&[Sample]) -> ControlFlow<()>>(self, mut f: F) -> Result<(), DecoderError> {
+        let block_len = DECODE_BLOCK_FRAMES * self.channels().max(1);
+        let mut block = Vec::with_capacity(block_len);
+        let mut samples = self.into_samples()?;
+        loop {
+            block.clear();
+            let mut error = None;
+            for sample in samples.by_ref().take(block_len) {
+                match sample {
+                    Ok(sample) => block.push(sample),
+                    Err(err) => {
+                        error = Some(err);
+                        break
+                    }
+                }
+            }
+
+            if !block.is_empty() && f(&block).is_break() {
+                return Ok(())
+            }
+            if let Some(err) = error {
+                return Err(err)
+            }
+            if block.len() < block_len {
+                return Ok(())
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Decoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let info = self.info();
+        f.debug_struct("Decoder")
+            .field("format", &info.format())
+            .field("sample_rate", &info.sample_rate())
+            .field("channels", &info.channels())
+            .finish()
+    }
+}
+
+/// The number of frames in each block passed to the callback in `Decoder::decode_with()`.
+pub const DECODE_BLOCK_FRAMES: usize = 1024;
+
+/// The most items reserved up front for output whose size comes from a header. A corrupt or hostile header can
+/// declare far more audio than the file holds, so anything past this is allocated as the audio is actually decoded.
+const MAX_RESERVED_LEN: usize = 1 << 24;
+
+impl IntoIterator for Decoder {
+    type Item = Result<Sample, DecoderError>;
+    type IntoIter = SampleIterator;
+
+    /// Consumes the `Decoder` and returns an iterator over the samples, as with `into_samples()`.
+    ///
+    /// If the iterator can't be created, the error is returned as the first and only item.
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        let info = self.info();
+        self.into_samples()
+            .unwrap_or_else(|err| SampleIterator::new(Box::new(std::iter::once(Err(err))), info))
+    }
+}
+
+/// Opens the specified audio file and decodes all of its samples at once.
+///
+/// Decoding stops at the first error. See `Decoder::decode_all()` to decode a file opened with other options.
+#[inline]
+pub fn decode_all<P: AsRef<Path>>(path: P) -> Result<DecodedAudio, DecoderError> {
+    Decoder::open(path)?.decode_all()
+}
+
+/// Iterates over decoded audio samples. Channels are interleaved.
+///
+/// `SampleIterator` is `Send`, so it can be moved to another thread for decoding.
+pub struct SampleIterator {
+    samples: Box<dyn Iterator<Item = Result<Sample, DecoderError>> + Send>,
+    /// Describes the samples as they come out of the iterator, after any transforms.
+    info: AudioInfo,
+    /// Samples that have been read ahead by `peek_frames` but not yet returned by `next`.
+    lookahead: VecDeque<Sample>,
+    /// An error encountered while reading ahead, returned once the lookahead buffer has been drained.
+    lookahead_error: Option<DecoderError>,
+    /// Number of samples outside [-1.0, 1.0] returned so far, if clipping detection is enabled.
+    clipped_samples: Option<u64>,
+    clamp: bool,
+    /// Level meters for the samples returned so far, if metering is enabled.
+    meters: Option<Meters>,
+    /// Tracks the current position for checkpoints, if the format supports resuming.
+    resume: Option<ResumeTracker>,
+}
+
+struct ResumeTracker {
+    format: AudioFormat,
+    start_frame: u64,
+    samples_read: u64,
+}
+
+impl SampleIterator {
+    #[inline]
+    pub(crate) fn new(samples: Box<dyn Iterator<Item = Result<Sample, DecoderError>> + Send>, info: AudioInfo) -> Self {
+        Self {
+            samples,
+            info,
+            lookahead: VecDeque::new(),
+            lookahead_error: None,
+            clipped_samples: None,
+            clamp: false,
+            meters: None,
+            resume: None,
+        }
+    }
+
+    /// Enables checkpoints, counting frames from `start_frame`.
+    #[inline]
+    pub(crate) fn with_resume_point(mut self, format: AudioFormat, start_frame: u64) -> SampleIterator {
+        self.resume = Some(ResumeTracker {
+            format,
+            start_frame,
+            samples_read: 0,
+        });
+        self
+    }
+
+    /// Gets a checkpoint at the first frame that hasn't been fully returned by `next()` yet.
+    ///
+    /// Returns `None` if the format doesn't support resuming (see `Checkpoint`), or if the iterator
+    /// came from an adapter that changes the timing of the samples, such as `trim_silence()`.
+    #[inline]
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.resume.as_ref().map(|resume| Checkpoint {
+            format: resume.format,
+            frame: resume.start_frame + resume.samples_read / self.info.channels().max(1) as u64,
+        })
+    }
+
+    /// Enables counting of samples that fall outside the nominal range [-1.0, 1.0].
+    ///
+    /// The samples themselves are passed through unchanged. Read the count with `clipped_sample_count()`.
+    #[inline]
+    pub fn with_clip_detection(mut self) -> SampleIterator {
+        self.clipped_samples.get_or_insert(0);
+        self
+    }
+
+    /// Enables clipping detection, and clamps every out-of-range sample to [-1.0, 1.0].
+    #[inline]
+    pub fn with_clamping(mut self) -> SampleIterator {
+        self.clamp = true;
+        self.with_clip_detection()
+    }
+
+    /// Gets the number of samples returned so far that were outside the range [-1.0, 1.0] before any clamping.
+    ///
+    /// Samples that have only been peeked with `peek_frames()` aren't counted (or clamped) until they're returned by `next()`.
+    /// Returns `None` if clipping detection wasn't enabled with `with_clip_detection()` or `with_clamping()`.
+    #[inline]
+    pub fn clipped_sample_count(&self) -> Option<u64> {
+        self.clipped_samples
+    }
+
+    /// Enables per-channel peak and RMS metering of the samples as they're returned.
+    ///
+    /// The samples themselves are passed through unchanged, so this adds level metering to the normal decode path
+    /// without a separate analysis pass. Read the levels with `meters()` at any point during decoding.
+    #[inline]
+    pub fn with_meters(mut self) -> SampleIterator {
+        let channels = self.info.channels();
+        self.meters.get_or_insert_with(|| Meters::new(channels));
+        self
+    }
+
+    /// Gets the level meters for the samples returned so far, after any clamping.
+    ///
+    /// As with clipping detection, samples that have only been peeked with `peek_frames()` aren't measured until
+    /// they're returned by `next()`. Returns `None` if metering wasn't enabled with `with_meters()`.
+    #[inline]
+    pub fn meters(&self) -> Option<&Meters> {
+        self.meters.as_ref()
+    }
+
+    /// Clears the level meters, so a live meter can show the levels of each period between readings.
+    /// Does nothing if metering wasn't enabled with `with_meters()`.
+    #[inline]
+    pub fn reset_meters(&mut self) {
+        if let Some(meters) = self.meters.as_mut() {
+            meters.reset();
+        }
+    }
+
+    /// Gets the number of interleaved channels.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.info.channels()
+    }
+
+    /// Gets the sample rate of the audio.
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.info.sample_rate()
+    }
+
+    /// Gets information about the samples as this iterator returns them, after any transforms applied by the adapter
+    /// it came from, such as a different channel count from `Decoder::remap_channels()`.
+    ///
+    /// The format is always that of the source. The total frames is the length of the whole transformed stream, not
+    /// what's left of it, and is `None` if a transform makes the length unknown.
+    #[inline]
+    pub fn info(&self) -> AudioInfo {
+        self.info.clone()
+    }
+
+    /// Returns the next `n` frames of interleaved samples without consuming them.
+    ///
+    /// The peeked samples are still returned by subsequent calls to `next()`. The returned slice is shorter
+    /// than `n` frames if the stream ends or an error occurs first; the error itself is returned by `next()`
+    /// once the samples before it have been consumed.
+    pub fn peek_frames(&mut self, n: usize) -> &[Sample] {
+        let wanted = n * self.info.channels().max(1);
+        while self.lookahead.len() < wanted && self.lookahead_error.is_none() {
+            match self.samples.next() {
+                Some(Ok(sample)) => self.lookahead.push_back(sample),
+                Some(Err(err)) => self.lookahead_error = Some(err),
+                None => break,
+            }
+        }
+        let available = wanted.min(self.lookahead.len());
+        &self.lookahead.make_contiguous()[..available]
+    }
+
+    /// Returns an iterator that multiplies every sample by a linear gain factor.
+    ///
+    /// Samples are not clamped, so a gain above 1.0 can push them outside the range [-1.0, 1.0]
+    /// and cause clipping if the output is later converted to a fixed-point format.
+    #[inline]
+    pub fn with_gain(self, gain: f32) -> SampleIterator {
+        self.map_samples(move |sample| sample * gain)
+    }
+
+    /// Applies `f` to every sample. Since the timing of the samples doesn't change, checkpoints keep working.
+    #[inline]
+    fn map_samples<F: Fn(Sample) -> Sample + Send + 'static>(mut self, f: F) -> SampleIterator {
+        let info = self.info();
+        let resume = self.resume.take();
+        let mut samples = SampleIterator::new(Box::new(self.map(move |sample| sample.map(&f))), info);
+        samples.resume = resume;
+        samples
+    }
+
+    /// Converts the iterator into a reader that serializes the samples as interleaved 32-bit float PCM bytes.
+    #[inline]
+    pub fn into_pcm_reader(self, endian: Endian) -> PcmReader {
+        PcmReader::new(self, endian)
+    }
+
+    /// Returns an iterator that adds the samples of `other` to these, ending when either stream ends.
+    ///
+    /// Both streams must have the same channel count and sample rate, or an `InvalidArgument` error is returned.
+    /// The sum isn't scaled or clamped, so apply a gain to either stream first if it could clip. Checkpoints aren't
+    /// available from the mixed stream. Use `mix_padded()` to keep going until the longer stream ends.
+    #[inline]
+    pub fn mix(self, other: SampleIterator) -> Result<SampleIterator, DecoderError> {
+        self.mix_streams(other, false)
+    }
+
+    /// Returns an iterator that adds the samples of `other` to these, as with `mix()`, but continues until both streams
+    /// end, as if the shorter one were padded with silence.
+    #[inline]
+    pub fn mix_padded(self, other: SampleIterator) -> Result<SampleIterator, DecoderError> {
+        self.mix_streams(other, true)
+    }
+
+    fn mix_streams(self, other: SampleIterator, pad: bool) -> Result<SampleIterator, DecoderError> {
+        if self.channels() != other.channels() {
+            return Err(DecoderError::InvalidArgument(format!("cannot mix {}-channel audio with {}-channel audio", self.channels(), other.channels())))
+        }
+        if self.sample_rate() != other.sample_rate() {
+            return Err(DecoderError::InvalidArgument(format!("cannot mix {}Hz audio with {}Hz audio", self.sample_rate(), other.sample_rate())))
+        }
+        // The mix is only as long as the shorter stream, or the longer one when padding, so both lengths must be known
+        let total_frames = match (self.info.total_frames(), other.info.total_frames()) {
+            (Some(a), Some(b)) => Some(if pad { a.max(b) } else { a.min(b) }),
+            _ => None,
+        };
+        let info = self.info().with_total_frames(total_frames);
+        Ok(SampleIterator::new(Box::new(Mix::new(self, other, pad)), info))
+    }
+
+    /// Returns an iterator that applies a gain specified in decibels to every sample.
+    ///
+    /// See `with_gain` for details on clipping.
+    #[inline]
+    pub fn with_gain_db(self, gain_db: f32) -> SampleIterator {
+        self.with_gain(10f32.powf(gain_db / 20.0))
+    }
+}
+
+impl std::fmt::Debug for SampleIterator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The boxed sample source can't be printed, so only the stream parameters are shown
+        f.debug_struct("SampleIterator")
+            .field("channels", &self.info.channels())
+            .field("sample_rate", &self.info.sample_rate())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Iterator for SampleIterator {
+    type Item = Result<Sample, DecoderError>;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = match self.lookahead.pop_front() {
+            Some(sample) => Ok(sample),
+            None => match self.lookahead_error.take() {
+                Some(err) => Err(err),
+                None => self.samples.next()?,
+            },
+        };
+
+        if let (Ok(_), Some(resume)) = (&sample, self.resume.as_mut()) {
+            resume.samples_read += 1;
+        }
+
+        let sample = match (sample, self.clipped_samples.as_mut()) {
+            (Ok(sample), Some(clipped_samples)) if !(-1.0..=1.0).contains(&sample) => {
+                *clipped_samples += 1;
+                Ok(if self.clamp { sample.clamp(-1.0, 1.0) } else { sample })
+            },
+            (sample, _) => sample,
+        };
+
+        if let (Ok(sample), Some(meters)) = (&sample, self.meters.as_mut()) {
+            meters.measure(*sample);
+        }
+        Some(sample)
+    }
+}
+
+/// Wraps a sample iterator with a known expected length, and reports `DecoderError::IncompleteData`
+/// if the underlying stream ends before all of the expected samples have been read.
+pub(crate) struct ExpectedLength<I: Iterator<Item = Result<Sample, DecoderError>>> {
+    samples: I,
+    remaining: u64,
+}
+
+impl<I: Iterator<Item = Result<Sample, DecoderError>>> ExpectedLength<I> {
+    #[inline]
+    pub fn new(samples: I, expected_samples: u64) -> Self {
+        Self {
+            samples,
+            remaining: expected_samples,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Sample, DecoderError>>> Iterator for ExpectedLength<I> {
+    type Item = Result<Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None
+        }
+        match self.samples.next() {
+            Some(Ok(sample)) => {
+                self.remaining -= 1;
+                Some(Ok(sample))
+            },
+            Some(Err(err)) => {
+                self.remaining = 0;
+                Some(Err(err))
+            },
+            None => {
+                // The stream ended early, so the file was probably truncated
+                self.remaining = 0;
+                Some(Err(DecoderError::IncompleteData))
+            }
+        }
+    }
+}
+
+pub(crate) enum FormatDecoder {
+    Raw(self::raw::RawDecoder<SourceReader>),
+    #[cfg(feature = "wav")]
+    Wav(self::wav::WavDecoder),
+    #[cfg(feature = "vorbis")]
+    Vorbis(Box<self::vorbis::VorbisDecoder>),
+    #[cfg(feature = "mp3")]
+    Mp3(self::mp3::Mp3Decoder),
+    #[cfg(feature = "flac")]
+    Flac(self::flac::FlacDecoder),
+    #[cfg(feature = "dsd")]
+    Dsd(self::dsd::DsdDecoder),
+    Custom(Box<dyn FormatBackend>),
+}
+
+impl FormatDecoder {
+    #[inline]
+    pub fn open<P: AsRef<Path>>(path: P, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        let path = path.as_ref();
+        let open_source = || File::open(path).map(|f| BufReader::new(Box::new(f) as Box<dyn MediaSource>)).map_err(DecoderError::IOError);
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        let err = match ext {
+            Some(ext) => {
+                // Catch empty files up front, since each backend would report them with a more obscure error
+                if let Ok(format) = ext.parse::<AudioFormat>() {
+                    if std::fs::metadata(path)?.len() == 0 {
+                        return Err(DecoderError::EmptyStream(format))
+                    }
+                }
+                let opened = Self::verify_magic_bytes(open_source()?, ext, options).and_then(|source| Self::open_as(source, Some(path), ext, options));
+                match opened {
+                    Ok(decoder) => return Ok(decoder.detected(options, false)),
+                    Err(err) => err,
+                }
+            },
+            None => DecoderError::NoExtension,
+        };
+
+        // Errors that content detection can't do anything about are returned as-is
+        if !options.detect_format_from_content || matches!(err, DecoderError::IOError(_) | DecoderError::EmptyStream(_)) {
+            return Err(err)
+        }
+        let mut source = open_source()?;
+        if let Some(backend) = self::custom::find_by_magic(&mut source, &options.custom_backends, options.max_probe_bytes)? {
+            return Ok(FormatDecoder::Custom(backend.open(source)?).detected(options, true))
+        }
+        match self::sniff::sniff_extension(&mut source, options.max_probe_bytes)? {
+            Some(detected) if Some(detected) != ext => Ok(Self::open_as(source, Some(path), detected, options)?.detected(options, true)),
+            Some(_) => Err(err),
+            None => match self::sniff::find_protected_container(&mut source, options.max_probe_bytes)? {
+                Some(container) => Err(DecoderError::Protected(container)),
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Opens a stream that didn't come from a file, identifying the format from its contents.
+    pub fn from_reader(mut source: SourceReader, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        if let Some(backend) = self::custom::find_by_magic(&mut source, &options.custom_backends, options.max_probe_bytes)? {
+            return Ok(FormatDecoder::Custom(backend.open(source)?).detected(options, true))
+        }
+        match self::sniff::sniff_extension(&mut source, options.max_probe_bytes)? {
+            Some(ext) => Ok(Self::open_as(source, None, ext, options)?.detected(options, true)),
+            None => match self::sniff::find_protected_container(&mut source, options.max_probe_bytes)? {
+                Some(container) => Err(DecoderError::Protected(container)),
+                None => Err(DecoderError::UnrecognizedFormat),
+            },
+        }
+    }
+
+    /// Gets the info of the audio file at `path`, reading only its headers where the format allows it.
+    ///
+    /// Files that can't be probed this way, and any file whose headers fail to parse, are opened in full instead,
+    /// so a file that fails to probe fails the same way it would in `open()`.
+    #[allow(unused_variables)]
+    pub fn probe<P: AsRef<Path>>(path: P, options: &DecoderOptions) -> Result<AudioInfo, DecoderError> {
+        let path = path.as_ref();
+        let open_source = || -> Result<SourceReader, DecoderError> { Ok(BufReader::new(Box::new(File::open(path)?) as Box<dyn MediaSource>)) };
+        let info = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if self::custom::find_by_extension(&options.custom_backends, ext).is_some() => None,
+            #[cfg(feature = "wav")]
+            Some("wav") => self::wav::WavDecoder::probe(open_source()?, options).ok(),
+            #[cfg(feature = "mp3")]
+            Some("mp3") => self::mp3::probe(open_source()?, options.max_probe_bytes).ok().flatten(),
+            #[cfg(feature = "flac")]
+            Some("flac") => self::flac::probe(open_source()?, options).ok(),
+            _ => None,
+        };
+        match info {
+            Some(info) => Ok(info),
+            None => Ok(Self::open(path, options)?.info()),
+        }
+    }
+
+    /// Reports the format of a newly opened decoder to the event handler, if there is one.
+    #[inline]
+    fn detected(self, options: &DecoderOptions, from_content: bool) -> Self {
+        if let Some(handler) = &options.on_event {
+            handler.emit(DecodeEvent::FormatDetected { format: self.info().format(), from_content });
+        }
+        self
+    }
+
+    /// Checks that the stream isn't recognizably a different format than `ext` indicates, if
+    /// `DecoderOptions::verify_magic_bytes` is enabled, and returns it rewound to its start.
+    fn verify_magic_bytes(mut source: SourceReader, ext: &str, options: &DecoderOptions) -> Result<SourceReader, DecoderError> {
+        if !options.verify_magic_bytes || self::custom::find_by_extension(&options.custom_backends, ext).is_some() {
+            return Ok(source)
+        }
+        // Raw audio has no magic bytes to check
+        let expected = match ext.parse::<AudioFormat>() {
+            Ok(AudioFormat::Raw) | Err(_) => return Ok(source),
+            Ok(format) => format,
+        };
+        match self::sniff::sniff_extension(&mut source, options.max_probe_bytes)? {
+            Some(detected) if detected.parse::<AudioFormat>().ok() != Some(expected) => Err(DecoderError::format_error(expected, format!(
+                "file has a '.{}' extension, but its contents are those of a '.{}' file", ext, detected
+            ))),
+            _ => Ok(source),
+        }
+    }
+
+    /// Opens the stream using the backend for the specified extension.
+    ///
+    /// `path` is the file the stream was opened from, if any.
+    #[allow(unused_variables)]
+    fn open_as(source: SourceReader, path: Option<&Path>, ext: &str, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        macro_rules! get_decoder {
+            ($in_ext:expr, $($ext:literal => requires $feature:literal for $init:expr),*) => {
+                match $in_ext {
+                    $(
+                        #[cfg(feature = $feature)]
+                        $ext => { return Ok($init) }
+                        #[cfg(not(feature = $feature))]
+                        $ext => { return Err(DecoderError::DisabledExtension { feature: $feature, extension: $ext }) }
+                    )*
+                    other => return Err(DecoderError::UnsupportedExtension(other.to_owned()))
+                }
+            }
+        }
+
+        if let Some(backend) = self::custom::find_by_extension(&options.custom_backends, ext) {
+            return Ok(FormatDecoder::Custom(backend.open(source)?))
+        }
+
+        // Ogg files can contain any of several codecs, so the Ogg container picks the backend
+        if matches!(ext, "ogg" | "oga" | "ogv") {
+            return Self::open_ogg(source, options)
+        }
+
+        // Any extension added here should also be listed by `AudioFormat::extensions()`
+        get_decoder!(ext,
+            "wav" => requires "wav" for FormatDecoder::Wav(self::wav::WavDecoder::new(source, options)?),
+            "mp3" => requires "mp3" for FormatDecoder::Mp3(self::mp3::Mp3Decoder::new(source, options)?),
+            "flac" => requires "flac" for FormatDecoder::Flac(self::flac::FlacDecoder::new(source, path, options)?),
+            "dsf" => requires "dsd" for FormatDecoder::Dsd(self::dsd::DsdDecoder::new(source)?),
+            "dff" => requires "dsd" for FormatDecoder::Dsd(self::dsd::DsdDecoder::new(source)?)
+        )
+    }
+
+    #[allow(unused_variables)]
+    fn open_ogg(mut source: SourceReader, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        // Video files multiplex the audio with other streams, which usually come first
+        let stream = self::ogg::sniff_audio_stream(&mut source)?;
+        if !stream.is_first {
+            source = BufReader::new(Box::new(self::ogg::StreamFilter::new(source, stream.stream_serial)));
+        }
+        match stream.codec {
+            #[cfg(feature = "vorbis")]
+            OggCodec::Vorbis => Ok(FormatDecoder::Vorbis(Box::new(self::vorbis::VorbisDecoder::new(source, options)?))),
+            #[cfg(not(feature = "vorbis"))]
+            OggCodec::Vorbis => Err(DecoderError::DisabledExtension { feature: "vorbis", extension: "ogg" }),
+            #[cfg(feature = "flac")]
+            OggCodec::Flac => Ok(FormatDecoder::Flac(self::flac::FlacDecoder::new_ogg(source, options)?)),
+            #[cfg(not(feature = "flac"))]
+            OggCodec::Flac => Err(DecoderError::DisabledExtension { feature: "flac", extension: "ogg" }),
+            OggCodec::Unknown => Err(DecoderError::format_error(AudioFormat::Vorbis, "stream uses an unrecognized codec")),
+            OggCodec::Theora => Err(DecoderError::format_error(AudioFormat::Vorbis, "file has no audio stream, only Theora video")),
+            other => Err(DecoderError::format_error(AudioFormat::Vorbis, format!("{} streams are not supported", other.name()))),
+        }
+    }
+
+    /// Gets the frame that decoding starts from, if the format supports seeking.
+    #[inline]
+    pub fn position(&self) -> Option<u64> {
+        match self {
+            FormatDecoder::Raw(d) => Some(d.position()),
+            #[cfg(feature = "wav")]
+            FormatDecoder::Wav(d) => d.position(),
+            FormatDecoder::Custom(d) => d.position(),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn random_access(&self) -> bool {
+        match self {
+            FormatDecoder::Raw(_) => true,
+            #[cfg(feature = "wav")]
+            FormatDecoder::Wav(d) => d.random_access(),
+            FormatDecoder::Custom(d) => d.random_access(),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    #[inline]
+    pub fn seek(&mut self, frame: u64) -> Result<(), DecoderError> {
+        match self {
+            FormatDecoder::Raw(d) => d.seek(frame),
+            #[cfg(feature = "wav")]
+            FormatDecoder::Wav(d) => d.seek(frame),
+            FormatDecoder::Custom(d) => d.seek(frame),
+            #[allow(unreachable_patterns)]
+            _ => Err(DecoderError::SeekNotSupported(self.info().format())),
+        }
+    }
+
+    #[inline]
+    pub fn into_samples(self) -> Result<SampleIterator, DecoderError> {
+        let info = self.info();
+        let position = self.position();
+        let samples = match self {
+            FormatDecoder::Raw(d) => SampleIterator::new(d.into_samples()?, info.clone()),
+            #[cfg(feature = "wav")]
+            FormatDecoder::Wav(d) => SampleIterator::new(d.into_samples()?, info.clone()),
+            #[cfg(feature = "vorbis")]
+            FormatDecoder::Vorbis(d) => SampleIterator::new(d.into_samples()?, info.clone()),
+            #[cfg(feature = "mp3")]
+            FormatDecoder::Mp3(d) => SampleIterator::new(d.into_samples()?, info.clone()),
+            #[cfg(feature = "flac")]
+            FormatDecoder::Flac(d) => SampleIterator::new(d.into_samples()?, info.clone()),
+            #[cfg(feature = "dsd")]
+            FormatDecoder::Dsd(d) => SampleIterator::new(d.into_samples()?, info.clone()),
+            FormatDecoder::Custom(d) => SampleIterator::new(d.into_samples()?, info.clone()),
+        };
+        Ok(match position {
+            Some(frame) => samples.with_resume_point(info.format(), frame),
+            None => samples,
+        })
+    }
+
+    #[inline]
+    pub fn info(&self) -> AudioInfo {
+        match self {
+            FormatDecoder::Raw(d) => d.info(),
+            #[cfg(feature = "wav")]
+            FormatDecoder::Wav(d) => d.info(),
+            #[cfg(feature = "vorbis")]
+            FormatDecoder::Vorbis(d) => d.info(),
+            #[cfg(feature = "mp3")]
+            FormatDecoder::Mp3(d) => d.info(),
             #[cfg(feature = "flac")]
             FormatDecoder::Flac(d) => d.info(),
+            #[cfg(feature = "dsd")]
+            FormatDecoder::Dsd(d) => d.info(),
+            FormatDecoder::Custom(d) => d.info(),
+        }
+    }
+
+    #[inline]
+    pub fn is_lossless(&self) -> bool {
+        match self {
+            FormatDecoder::Raw(d) => match d.spec().sample_format {
+                RawSampleFormat::ALaw | RawSampleFormat::MuLaw => false,
+                RawSampleFormat::Float32 | RawSampleFormat::Float64
+                    | RawSampleFormat::Unsigned8 | RawSampleFormat::Signed8
+                    | RawSampleFormat::Unsigned12 | RawSampleFormat::Signed12
+                    | RawSampleFormat::Unsigned16 | RawSampleFormat::Signed16
+                    | RawSampleFormat::Unsigned20 | RawSampleFormat::Signed20
+                    | RawSampleFormat::Unsigned24 | RawSampleFormat::Signed24
+                    | RawSampleFormat::Unsigned32 | RawSampleFormat::Signed32
+                    | RawSampleFormat::Unsigned64 | RawSampleFormat::Signed64 => true,
+            },
+            #[cfg(feature = "wav")]
+            FormatDecoder::Wav(d) => matches!(d.wav_info().encoding(), WavEncoding::Pcm | WavEncoding::Float),
+            FormatDecoder::Custom(d) => d.is_lossless(),
+            #[allow(unreachable_patterns)]
+            other => other.info().format().is_lossless(),
         }
     }
 }
 
 /// An error encountered while decoding an audio file.
+///
+/// New kinds of error may be added in minor releases, so matches on this enum need a wildcard arm.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DecoderError {
     /// I/O error.
     IOError(io::Error),
     /// Error specific to the audio format.
-    FormatError(String),
+    FormatError {
+        /// The format of the audio that caused the error.
+        format: AudioFormat,
+        message: String,
+    },
     /// No extension was provided on the input file.
     NoExtension,
     /// The extension on the input file is not supported for decoding.
@@ -276,6 +2290,47 @@ pub enum DecoderError {
         extension: &'static str,
         feature: &'static str,
     },
+    /// The input file is empty, so it can't contain audio in the expected format.
+    EmptyStream(AudioFormat),
+    /// The audio format doesn't support seeking.
+    SeekNotSupported(AudioFormat),
+    /// The format of a stream couldn't be identified from its contents.
+    UnrecognizedFormat,
+    /// An argument passed by the caller is out of range or malformed.
+    InvalidArgument(String),
+    /// The stream is encrypted or DRM-protected audio, which can't be decoded. Holds the name of the container format.
+    ///
+    /// This is only detected when the format is identified from the stream's contents.
+    Protected(&'static str),
+}
+
+impl DecoderError {
+    #[inline]
+    pub(crate) fn format_error(format: AudioFormat, message: impl Into<String>) -> Self {
+        DecoderError::FormatError {
+            format,
+            message: message.into(),
+        }
+    }
+
+    /// Checks that a stream declares at least one channel and a nonzero sample rate,
+    /// since a corrupt header that doesn't would break every calculation involving time.
+    pub(crate) fn check_stream_params(format: AudioFormat, sample_rate: u32, channels: usize) -> Result<(), Self> {
+        if channels == 0 {
+            return Err(DecoderError::format_error(format, "file contains zero channels"))
+        }
+        if sample_rate == 0 {
+            return Err(DecoderError::format_error(format, "file declares a sample rate of 0 Hz"))
+        }
+        Ok(())
+    }
+}
+
+impl From<io::Error> for DecoderError {
+    #[inline]
+    fn from(error: io::Error) -> Self {
+        DecoderError::IOError(error)
+    }
 }
 
 impl Error for DecoderError {
@@ -292,11 +2347,16 @@ impl Display for DecoderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             DecoderError::IOError(err) => write!(f, "IO error: {}", err),
-            DecoderError::FormatError(err) => write!(f, "format error: {}", err),
+            DecoderError::FormatError { format, message } => write!(f, "format error: {}: {}", format.error_prefix(), message),
             DecoderError::NoExtension => write!(f, "file has no extension"),
             DecoderError::UnsupportedExtension(ext) => write!(f, "extension '{}' is not supported", ext),
             DecoderError::DisabledExtension { extension, feature } => write!(f, "feature '{}' is required to read '{}' files, but is not enabled", feature, extension),
             DecoderError::IncompleteData => write!(f, "incomplete data"),
+            DecoderError::EmptyStream(format) => write!(f, "file is empty, but was expected to contain {} audio", format),
+            DecoderError::SeekNotSupported(format) => write!(f, "seeking is not supported for {} audio", format),
+            DecoderError::UnrecognizedFormat => write!(f, "stream does not contain audio in any recognized format"),
+            DecoderError::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            DecoderError::Protected(container) => write!(f, "stream is DRM-protected {} audio, which can't be decoded", container),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,205 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use super::riff::WavHeader;
+use crate::pool::{give_buffer, take_buffer};
+use crate::{AudioFormat, DecodeBufferPool, DecoderError, ExpectedLength, Sample, SourceReader};
+
+/// Quantizer step sizes, indexed by the step index.
+const STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107,
+    118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796, 876, 963,
+    1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894,
+    6484, 7132, 7845, 8630, 9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794,
+    32767,
+];
+
+/// Adjustment to the step index after each nibble, indexed by the nibble's magnitude bits.
+const INDEX_TABLE: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+/// Decodes IMA ADPCM (format tag `0x0011`) WAV data, which creak decodes itself.
+///
+/// The data is split into blocks of `block_align` bytes, each of which restarts the predictor from its header,
+/// so the decoder can seek to any block without decoding the ones before it.
+pub struct ImaAdpcmDecoder {
+    reader: SourceReader,
+    channels: usize,
+    block_align: u64,
+    /// Number of frames in each full block.
+    block_frames: u64,
+    data_offset: u64,
+    total_frames: u64,
+    /// The frame that decoding starts from.
+    start_frame: u64,
+    buffer_pool: Option<DecodeBufferPool>,
+}
+
+impl ImaAdpcmDecoder {
+    pub fn new(reader: SourceReader, header: &WavHeader, buffer_pool: Option<DecodeBufferPool>) -> Result<Self, DecoderError> {
+        if header.bits_per_sample != 4 {
+            return Err(DecoderError::format_error(AudioFormat::Wav, format!("IMA ADPCM with {} bits per sample is not supported", header.bits_per_sample)))
+        }
+        let channels = header.channels as usize;
+        let block_align = header.block_align as u64;
+        let header_len = 4 * channels as u64;
+        if block_align <= header_len || !(block_align - header_len).is_multiple_of(header_len) {
+            return Err(DecoderError::format_error(AudioFormat::Wav, format!(
+                "IMA ADPCM block alignment of {} bytes doesn't fit a whole number of samples for {} channel(s)", block_align, channels
+            )))
+        }
+        Ok(Self {
+            reader,
+            channels,
+            block_align,
+            block_frames: header.ima_block_frames(block_align),
+            data_offset: header.data_offset,
+            total_frames: header.total_frames(),
+            start_frame: 0,
+            buffer_pool,
+        })
+    }
+
+    /// Gets the frame that decoding starts from.
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.start_frame
+    }
+
+    /// Moves the start of decoding to the specified frame, clamped to the end of the stream.
+    #[inline]
+    pub fn seek(&mut self, frame: u64) {
+        self.start_frame = frame.min(self.total_frames);
+    }
+
+    pub fn into_samples(mut self) -> Result<ExpectedLength<ImaAdpcmSampleIterator>, DecoderError> {
+        // Start from the block containing the first frame, and skip the frames before it once it's decoded
+        let block = self.start_frame / self.block_frames;
+        self.reader.seek(SeekFrom::Start(self.data_offset + block * self.block_align))?;
+        let expected_samples = (self.total_frames - self.start_frame) * self.channels as u64;
+        let samples = ImaAdpcmSampleIterator {
+            reader: self.reader,
+            channels: self.channels,
+            block: take_buffer(self.buffer_pool.as_ref(), self.block_align as usize),
+            block_align: self.block_align,
+            samples: take_buffer(self.buffer_pool.as_ref(), self.block_frames as usize * self.channels),
+            cursor: 0,
+            skip: (self.start_frame % self.block_frames) as usize * self.channels,
+            remaining: expected_samples,
+            finished: false,
+            buffer_pool: self.buffer_pool,
+        };
+        Ok(ExpectedLength::new(samples, expected_samples))
+    }
+}
+
+pub struct ImaAdpcmSampleIterator {
+    reader: SourceReader,
+    channels: usize,
+    /// The bytes of the current block.
+    block: Vec<u8>,
+    block_align: u64,
+    /// The samples of the current block, interleaved.
+    samples: Vec<Sample>,
+    cursor: usize,
+    /// Number of samples to discard from the first block, when decoding starts partway through it.
+    skip: usize,
+    /// Number of samples left before the length declared by the header, past which any padding in the last block lies.
+    remaining: u64,
+    finished: bool,
+    /// The pool the buffers came from, which they're returned to when the iterator is dropped.
+    buffer_pool: Option<DecodeBufferPool>,
+}
+
+impl Drop for ImaAdpcmSampleIterator {
+    fn drop(&mut self) {
+        give_buffer(self.buffer_pool.as_ref(), std::mem::take(&mut self.block));
+        give_buffer(self.buffer_pool.as_ref(), std::mem::take(&mut self.samples));
+    }
+}
+
+impl ImaAdpcmSampleIterator {
+    /// Reads and decodes the next block. Leaves `samples` empty at the end of the data.
+    fn read_block(&mut self) -> Result<(), DecoderError> {
+        self.block.clear();
+        self.reader.by_ref().take(self.block_align).read_to_end(&mut self.block)?;
+        self.samples.clear();
+        self.cursor = 0;
+        let header_len = 4 * self.channels;
+        if self.block.len() < header_len {
+            return Ok(())
+        }
+
+        // The last block can be cut short, so decode only the complete chunks that are there
+        let chunks = (self.block.len() - header_len) / header_len;
+        self.samples.resize((1 + chunks * 8) * self.channels, 0.0);
+        for channel in 0..self.channels {
+            let header = &self.block[channel * 4..channel * 4 + 4];
+            let mut state = ImaAdpcmState {
+                predictor: i16::from_le_bytes([header[0], header[1]]) as i32,
+                step_index: (header[2] as i32).min(88),
+            };
+            self.samples[channel] = state.predictor as Sample / i16::MAX as Sample;
+            for chunk in 0..chunks {
+                let offset = header_len + chunk * header_len + channel * 4;
+                for (i, byte) in self.block[offset..offset + 4].iter().enumerate() {
+                    // Samples are packed low nibble first
+                    for (j, nibble) in [byte & 0x0f, byte >> 4].iter().enumerate() {
+                        let frame = 1 + chunk * 8 + i * 2 + j;
+                        self.samples[frame * self.channels + channel] = state.decode(*nibble) as Sample / i16::MAX as Sample;
+                    }
+                }
+            }
+        }
+
+        let skipped = self.skip.min(self.samples.len());
+        self.cursor = skipped;
+        self.skip -= skipped;
+        Ok(())
+    }
+}
+
+impl Iterator for ImaAdpcmSampleIterator {
+    type Item = Result<Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.remaining == 0 || self.finished {
+                return None
+            }
+            if let Some(sample) = self.samples.get(self.cursor) {
+                self.cursor += 1;
+                self.remaining -= 1;
+                return Some(Ok(*sample))
+            }
+            if let Err(err) = self.read_block() {
+                self.finished = true;
+                return Some(Err(err))
+            }
+            if self.samples.is_empty() {
+                self.finished = true;
+            }
+        }
+    }
+}
+
+/// The predictor state of one channel.
+struct ImaAdpcmState {
+    predictor: i32,
+    step_index: i32,
+}
+
+impl ImaAdpcmState {
+    /// Decodes the next 4-bit sample and updates the state.
+    #[inline]
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = STEP_TABLE[self.step_index as usize];
+        let mut diff = step >> 3;
+        if nibble & 1 != 0 { diff += step >> 2 }
+        if nibble & 2 != 0 { diff += step >> 1 }
+        if nibble & 4 != 0 { diff += step }
+        if nibble & 8 != 0 { diff = -diff }
+        self.predictor = (self.predictor + diff).clamp(i16::MIN as i32, i16::MAX as i32);
+        self.step_index = (self.step_index + INDEX_TABLE[(nibble & 7) as usize]).clamp(0, 88);
+        self.predictor as i16
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use crate::{AudioInfo, DecoderError, Sample, SourceReader};
+
+/// A decoder for a format that creak doesn't support itself, registered with `DecoderOptions::custom_backends`.
+///
+/// Backends report `AudioFormat::Custom` as their format. Seeking is optional: by default, `position()` returns `None`
+/// and `seek()` returns `SeekNotSupported`.
+pub trait FormatBackend: Send {
+    /// Gets information about the opened stream.
+    fn info(&self) -> AudioInfo;
+
+    /// Consumes the backend and returns an iterator over its interleaved samples.
+    fn into_samples(self: Box<Self>) -> Result<Box<dyn Iterator<Item = Result<Sample, DecoderError>> + Send>, DecoderError>;
+
+    /// Returns `true` if the stream decodes to exactly the samples that were encoded. Defaults to `false`.
+    #[inline]
+    fn is_lossless(&self) -> bool {
+        false
+    }
+
+    /// Gets the frame that decoding starts from, if the backend supports seeking.
+    #[inline]
+    fn position(&self) -> Option<u64> {
+        None
+    }
+
+    /// Moves the start of decoding to the specified frame.
+    #[inline]
+    fn seek(&mut self, frame: u64) -> Result<(), DecoderError> {
+        let _ = frame;
+        Err(DecoderError::SeekNotSupported(self.info().format()))
+    }
+
+    /// Returns `true` if `seek()` reaches any frame in constant time, rather than decoding everything before it.
+    /// Reported by `Capabilities::random_access()`. Defaults to `false`.
+    #[inline]
+    fn random_access(&self) -> bool {
+        false
+    }
+}
+
+type OpenBackend = dyn Fn(SourceReader) -> Result<Box<dyn FormatBackend>, DecoderError> + Send + Sync;
+
+/// Registers a `FormatBackend` to be opened for files with certain extensions or leading bytes.
+///
+/// Custom backends are tried before the built-in ones, so they can also take over a format creak already decodes.
+/// A file is matched by its extension when it's opened from a path, and by its magic bytes when the format is
+/// identified from its contents (with `DecoderOptions::detect_format_from_content`, or by `Decoder::from_reader`).
+#[derive(Clone)]
+pub struct CustomBackend {
+    name: String,
+    extensions: Vec<String>,
+    magic: Vec<Vec<u8>>,
+    open: Arc<OpenBackend>,
+}
+
+impl CustomBackend {
+    /// Creates a backend named `name` that opens streams with `open`. It matches no files until extensions or magic
+    /// bytes are added to it.
+    ///
+    /// `open` receives the stream positioned at its start, and should return an error if it can't decode it.
+    #[inline]
+    pub fn new<F>(name: impl Into<String>, open: F) -> Self
+    where
+        F: Fn(SourceReader) -> Result<Box<dyn FormatBackend>, DecoderError> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            extensions: vec![],
+            magic: vec![],
+            open: Arc::new(open),
+        }
+    }
+
+    /// Adds a file extension, without the leading dot, that this backend opens. Extensions are matched ignoring case.
+    #[inline]
+    pub fn with_extension(mut self, extension: impl Into<String>) -> Self {
+        self.extensions.push(extension.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Adds a byte sequence that streams this backend opens start with.
+    #[inline]
+    pub fn with_magic(mut self, magic: impl Into<Vec<u8>>) -> Self {
+        self.magic.push(magic.into());
+        self
+    }
+
+    /// Gets the name of the backend.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the extensions this backend opens, in lowercase.
+    #[inline]
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    #[inline]
+    pub(crate) fn open(&self, source: SourceReader) -> Result<Box<dyn FormatBackend>, DecoderError> {
+        (self.open)(source)
+    }
+
+    #[inline]
+    fn matches_extension(&self, ext: &str) -> bool {
+        self.extensions.iter().any(|own| own.eq_ignore_ascii_case(ext))
+    }
+
+    #[inline]
+    fn matches_magic(&self, data: &[u8]) -> bool {
+        self.magic.iter().any(|magic| !magic.is_empty() && data.starts_with(magic))
+    }
+}
+
+impl fmt::Debug for CustomBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomBackend")
+            .field("name", &self.name)
+            .field("extensions", &self.extensions)
+            .field("magic", &self.magic)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Finds the first backend that opens files with the extension `ext`.
+#[inline]
+pub fn find_by_extension<'a>(backends: &'a [CustomBackend], ext: &str) -> Option<&'a CustomBackend> {
+    backends.iter().find(|backend| backend.matches_extension(ext))
+}
+
+/// Finds the first backend whose magic bytes the stream starts with, reading no more than `max_bytes`.
+/// The stream is rewound to its start afterwards.
+pub fn find_by_magic<'a>(source: &mut SourceReader, backends: &'a [CustomBackend], max_bytes: u64) -> Result<Option<&'a CustomBackend>, DecoderError> {
+    let longest = backends.iter().flat_map(|backend| backend.magic.iter()).map(Vec::len).max().unwrap_or(0) as u64;
+    if longest == 0 {
+        return Ok(None)
+    }
+    let mut head = vec![];
+    source.seek(SeekFrom::Start(0))?;
+    source.by_ref().take(longest.min(max_bytes)).read_to_end(&mut head)?;
+    source.seek(SeekFrom::Start(0))?;
+    Ok(backends.iter().find(|backend| backend.matches_magic(&head)))
+}
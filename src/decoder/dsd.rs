@@ -0,0 +1,453 @@
+use std::convert::TryFrom;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{AudioFormat, AudioInfo, DecoderError, ExpectedLength, Sample, SourceReader};
+
+/// The DSD64 sample rate, which is 64 times the CD sample rate.
+const DSD64_RATE: u32 = 2_822_400;
+/// Number of DSD bits that are decimated into one PCM sample at DSD64.
+const DSD64_DECIMATION: usize = 64;
+/// Number of low-pass filter taps at DSD64. The filter is scaled up with the DSD rate.
+const DSD64_FILTER_TAPS: usize = 4096;
+/// Cutoff frequency of the decimation filter, in Hz.
+const FILTER_CUTOFF: f64 = 21_000.0;
+/// The bit pattern DSD encoders use for digital silence.
+const DSD_SILENCE: u8 = 0x69;
+/// The number of bytes per channel in each block of a DSF file, which the format fixes.
+const DSF_BLOCK_SIZE: usize = 4096;
+
+/// The container a DSD stream was read from, which determines how the channel data is laid out.
+#[derive(Debug, Copy, Clone)]
+enum DsdLayout {
+    /// DSF: each channel is stored in blocks of `block_size` bytes, and bits may be stored LSB-first.
+    Dsf {
+        block_size: usize,
+        lsb_first: bool,
+    },
+    /// DSDIFF: channels are interleaved byte by byte, MSB-first.
+    Dff,
+}
+
+pub struct DsdDecoder {
+    reader: SourceReader,
+    layout: DsdLayout,
+    channels: usize,
+    dsd_rate: u32,
+    /// Number of DSD bytes per channel.
+    bytes_per_channel: u64,
+}
+
+impl DsdDecoder {
+    pub fn new(mut reader: SourceReader) -> Result<Self, DecoderError> {
+        let mut magic = [0; 4];
+        read_bytes(&mut reader, &mut magic)?;
+        let decoder = match &magic {
+            b"DSD " => Self::open_dsf(reader)?,
+            b"FRM8" => Self::open_dff(reader)?,
+            _ => return Err(DecoderError::format_error(AudioFormat::Dsd, "no DSF or DSDIFF header found"))
+        };
+
+        DecoderError::check_stream_params(AudioFormat::Dsd, decoder.dsd_rate, decoder.channels)?;
+        if !decoder.dsd_rate.is_multiple_of(DSD64_RATE) {
+            return Err(DecoderError::format_error(AudioFormat::Dsd, format!("sample rate {} Hz is not a multiple of the DSD64 rate", decoder.dsd_rate)))
+        }
+        Ok(decoder)
+    }
+
+    fn open_dsf(mut reader: SourceReader) -> Result<Self, DecoderError> {
+        // The rest of the "DSD " chunk only holds file size and metadata offset
+        let mut dsd_chunk = [0; 24];
+        read_bytes(&mut reader, &mut dsd_chunk)?;
+
+        let mut fmt = [0; 52];
+        read_bytes(&mut reader, &mut fmt)?;
+        if &fmt[0..4] != b"fmt " {
+            return Err(DecoderError::format_error(AudioFormat::Dsd, "fmt chunk not found"))
+        }
+        let le_u32 = |offset: usize| u32::from_le_bytes([fmt[offset], fmt[offset + 1], fmt[offset + 2], fmt[offset + 3]]);
+        let format_id = le_u32(16);
+        let channels = le_u32(24) as usize;
+        let dsd_rate = le_u32(28);
+        let bits_per_sample = le_u32(32);
+        let mut sample_count = [0; 8];
+        sample_count.copy_from_slice(&fmt[36..44]);
+        let sample_count = u64::from_le_bytes(sample_count);
+        let block_size = le_u32(44) as usize;
+
+        if format_id != 0 {
+            return Err(DecoderError::format_error(AudioFormat::Dsd, "only uncompressed DSD is supported"))
+        }
+        let lsb_first = match bits_per_sample {
+            1 => true,
+            8 => false,
+            other => return Err(DecoderError::format_error(AudioFormat::Dsd, format!("invalid bits per sample: {}", other)))
+        };
+        if block_size != DSF_BLOCK_SIZE {
+            return Err(DecoderError::format_error(AudioFormat::Dsd, format!("block size is {} bytes, but DSF requires {}", block_size, DSF_BLOCK_SIZE)))
+        }
+
+        // Skip past the fmt chunk (in case it's longer than usual) to the data chunk header
+        let mut fmt_len = [0; 8];
+        fmt_len.copy_from_slice(&fmt[4..12]);
+        let fmt_len = u64::from_le_bytes(fmt_len);
+        let data_offset = match 28u64.checked_add(fmt_len) {
+            Some(data_offset) => data_offset,
+            None => return Err(DecoderError::format_error(AudioFormat::Dsd, format!("fmt chunk length {} is too large", fmt_len))),
+        };
+        reader.seek(SeekFrom::Start(data_offset))?;
+        let mut data_header = [0; 12];
+        read_bytes(&mut reader, &mut data_header)?;
+        if &data_header[0..4] != b"data" {
+            return Err(DecoderError::format_error(AudioFormat::Dsd, "data chunk not found"))
+        }
+
+        Ok(Self {
+            reader,
+            layout: DsdLayout::Dsf {
+                block_size,
+                lsb_first,
+            },
+            channels,
+            dsd_rate,
+            bytes_per_channel: sample_count / 8,
+        })
+    }
+
+    fn open_dff(mut reader: SourceReader) -> Result<Self, DecoderError> {
+        let mut form = [0; 12];
+        read_bytes(&mut reader, &mut form)?;
+        if &form[8..12] != b"DSD " {
+            return Err(DecoderError::format_error(AudioFormat::Dsd, "DSDIFF form type is not DSD"))
+        }
+
+        let mut channels = 0;
+        let mut dsd_rate = 0;
+        loop {
+            let (chunk_id, chunk_len) = read_dff_chunk_header(&mut reader)?;
+            match &chunk_id {
+                // Property chunks are containers, so step into them rather than skipping them
+                b"PROP" => {
+                    let rest = remaining_len(&chunk_id, chunk_len, 4)?;
+                    let mut prop_type = [0; 4];
+                    read_bytes(&mut reader, &mut prop_type)?;
+                    if &prop_type != b"SND " {
+                        skip_dff_chunk(&mut reader, rest)?;
+                    }
+                },
+                b"FS  " => {
+                    let rest = remaining_len(&chunk_id, chunk_len, 4)?;
+                    let mut buf = [0; 4];
+                    read_bytes(&mut reader, &mut buf)?;
+                    dsd_rate = u32::from_be_bytes(buf);
+                    skip_dff_chunk(&mut reader, rest)?;
+                },
+                b"CHNL" => {
+                    let rest = remaining_len(&chunk_id, chunk_len, 2)?;
+                    let mut buf = [0; 2];
+                    read_bytes(&mut reader, &mut buf)?;
+                    channels = u16::from_be_bytes(buf) as usize;
+                    skip_dff_chunk(&mut reader, rest)?;
+                },
+                b"CMPR" => {
+                    let rest = remaining_len(&chunk_id, chunk_len, 4)?;
+                    let mut buf = [0; 4];
+                    read_bytes(&mut reader, &mut buf)?;
+                    if &buf != b"DSD " {
+                        return Err(DecoderError::format_error(AudioFormat::Dsd, "only uncompressed DSD is supported"))
+                    }
+                    skip_dff_chunk(&mut reader, rest)?;
+                },
+                b"DSD " => {
+                    // The frame count is derived from the bit count, which must fit in a u64
+                    let bytes_per_channel = chunk_len / channels.max(1) as u64;
+                    if bytes_per_channel.checked_mul(8).is_none() {
+                        return Err(DecoderError::format_error(AudioFormat::Dsd, format!("chunk length {} is too large", chunk_len)))
+                    }
+                    return Ok(Self {
+                        reader,
+                        layout: DsdLayout::Dff,
+                        channels,
+                        dsd_rate,
+                        bytes_per_channel,
+                    })
+                },
+                b"DST " => return Err(DecoderError::format_error(AudioFormat::Dsd, "only uncompressed DSD is supported")),
+                _ => skip_dff_chunk(&mut reader, chunk_len)?,
+            }
+        }
+    }
+}
+
+impl DsdDecoder {
+    /// Gets the number of DSD bits that are decimated into each output sample.
+    #[inline]
+    fn decimation(&self) -> usize {
+        DSD64_DECIMATION * (self.dsd_rate / DSD64_RATE) as usize
+    }
+
+    #[inline]
+    pub fn info(&self) -> AudioInfo {
+        AudioInfo {
+            format: AudioFormat::Dsd,
+            sample_rate: self.dsd_rate / self.decimation() as u32,
+            channels: self.channels,
+            total_frames: Some(self.bytes_per_channel * 8 / self.decimation() as u64),
+        }
+    }
+
+    #[inline]
+    pub fn into_samples(self) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>> + Send>, DecoderError> {
+        Ok(Box::new(self.into_dsd_samples()))
+    }
+
+    pub fn into_dsd_samples(self) -> DsdSamples {
+        let total_frames = self.info().total_frames().unwrap_or(0);
+        let rate_multiple = (self.dsd_rate / DSD64_RATE) as usize;
+        let filter = DecimationFilter::new(DSD64_FILTER_TAPS * rate_multiple, FILTER_CUTOFF / self.dsd_rate as f64);
+        let iter = DsdSampleIterator {
+            history: vec![vec![DSD_SILENCE; filter.tables.len()]; self.channels],
+            history_cursor: 0,
+            bytes_per_sample: self.decimation() / 8,
+            frame: Vec::with_capacity(self.channels),
+            frame_bytes: vec![0; self.channels],
+            frame_cursor: 0,
+            frames_left: total_frames,
+            failed: false,
+            filter,
+            bytes: DsdBytes {
+                reader: self.reader,
+                layout: self.layout,
+                channels: self.channels,
+                bytes_left: self.bytes_per_channel,
+                block: vec![],
+                block_cursor: 0,
+                block_len: 0,
+            },
+        };
+        DsdSamples {
+            samples: ExpectedLength::new(iter, total_frames * self.channels as u64),
+        }
+    }
+}
+
+/// The samples of a DSD file, converted to PCM, as an iterator of a concrete type.
+///
+/// See `Decoder::into_dsd_samples()`.
+pub struct DsdSamples {
+    samples: ExpectedLength<DsdSampleIterator>,
+}
+
+impl Iterator for DsdSamples {
+    type Item = Result<crate::Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.samples.next()
+    }
+}
+
+/// A windowed-sinc low-pass FIR filter, precomputed as one lookup table per byte of filter history.
+///
+/// Since every DSD bit is either +1 or -1, the contribution of any 8 consecutive taps is fully determined by one byte,
+/// so each output sample only costs one table lookup per byte instead of one multiply per bit.
+struct DecimationFilter {
+    tables: Vec<[f32; 256]>,
+}
+
+impl DecimationFilter {
+    fn new(taps: usize, cutoff: f64) -> Self {
+        use std::f64::consts::PI;
+
+        // Blackman-windowed sinc, normalized for unity gain at DC
+        let center = (taps - 1) as f64 / 2.0;
+        let mut coefficients: Vec<f64> = (0..taps)
+            .map(|i| {
+                let x = i as f64 - center;
+                let sinc = if x == 0.0 { 2.0 * cutoff } else { (2.0 * PI * cutoff * x).sin() / (PI * x) };
+                let phase = 2.0 * PI * i as f64 / (taps - 1) as f64;
+                sinc * (0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos())
+            })
+            .collect();
+        let sum: f64 = coefficients.iter().sum();
+        coefficients.iter_mut().for_each(|c| *c /= sum);
+
+        // Table `k` covers the byte `k` bytes before the newest one; within a byte, the LSB is the newest bit
+        let tables = coefficients.chunks(8)
+            .map(|taps| {
+                let mut table = [0.0; 256];
+                for (byte, value) in table.iter_mut().enumerate() {
+                    *value = taps.iter()
+                        .enumerate()
+                        .map(|(bit, tap)| if byte & (1 << bit) != 0 { *tap } else { -*tap })
+                        .sum::<f64>() as f32;
+                }
+                table
+            })
+            .collect();
+
+        Self {
+            tables
+        }
+    }
+}
+
+/// Reads DSD bytes from either container layout, one byte per channel at a time, with the bits in MSB-first order.
+struct DsdBytes {
+    reader: SourceReader,
+    layout: DsdLayout,
+    channels: usize,
+    /// Number of bytes per channel left to read.
+    bytes_left: u64,
+    block: Vec<u8>,
+    /// Index of the next byte to read per channel within the current block.
+    block_cursor: usize,
+    block_len: usize,
+}
+
+impl DsdBytes {
+    /// Reads the next byte of every channel into `frame`. Returns `Ok(false)` at the end of the stream.
+    fn read_frame(&mut self, frame: &mut [u8]) -> Result<bool, DecoderError> {
+        if self.bytes_left == 0 {
+            return Ok(false)
+        }
+
+        if self.block_cursor >= self.block_len {
+            // DSF blocks hold `block_size` bytes per channel; DSDIFF data has no blocks, so read it in convenient chunks
+            let bytes_per_channel = match self.layout {
+                DsdLayout::Dsf { block_size, .. } => block_size,
+                DsdLayout::Dff => 4096,
+            };
+            self.block.resize(bytes_per_channel * self.channels, 0);
+            let read_len = match self.layout {
+                DsdLayout::Dsf { .. } => self.block.len(),
+                DsdLayout::Dff => (bytes_per_channel as u64).min(self.bytes_left) as usize * self.channels,
+            };
+            read_bytes(&mut self.reader, &mut self.block[..read_len]).map_err(|err| match err {
+                DecoderError::FormatError { .. } => DecoderError::IncompleteData,
+                other => other,
+            })?;
+            self.block_cursor = 0;
+            self.block_len = bytes_per_channel;
+        }
+
+        for (channel, byte) in frame.iter_mut().enumerate() {
+            *byte = match self.layout {
+                DsdLayout::Dsf { block_size, lsb_first } => {
+                    let byte = self.block[channel * block_size + self.block_cursor];
+                    if lsb_first { byte.reverse_bits() } else { byte }
+                },
+                DsdLayout::Dff => self.block[self.block_cursor * self.channels + channel],
+            };
+        }
+        self.block_cursor += 1;
+        self.bytes_left -= 1;
+        Ok(true)
+    }
+}
+
+struct DsdSampleIterator {
+    bytes: DsdBytes,
+    filter: DecimationFilter,
+    /// Circular buffer of the most recent bytes of each channel, one per filter table.
+    history: Vec<Vec<u8>>,
+    /// Index in `history` where the next byte will be written.
+    history_cursor: usize,
+    bytes_per_sample: usize,
+    frame: Vec<Sample>,
+    /// Reused to hold the next byte of every channel, so decoding a frame doesn't allocate.
+    frame_bytes: Vec<u8>,
+    frame_cursor: usize,
+    frames_left: u64,
+    failed: bool,
+}
+
+impl DsdSampleIterator {
+    fn decode_frame(&mut self) -> Result<bool, DecoderError> {
+        let history_len = self.filter.tables.len();
+        for _ in 0..self.bytes_per_sample {
+            if !self.bytes.read_frame(&mut self.frame_bytes)? {
+                return Ok(false)
+            }
+            for (history, byte) in self.history.iter_mut().zip(self.frame_bytes.iter()) {
+                history[self.history_cursor] = *byte;
+            }
+            self.history_cursor = (self.history_cursor + 1) % history_len;
+        }
+
+        self.frame.clear();
+        for history in self.history.iter() {
+            let mut sum = 0.0;
+            for (age, table) in self.filter.tables.iter().enumerate() {
+                let index = (self.history_cursor + history_len - 1 - age) % history_len;
+                sum += table[history[index] as usize];
+            }
+            self.frame.push(sum);
+        }
+        self.frame_cursor = 0;
+        Ok(true)
+    }
+}
+
+impl Iterator for DsdSampleIterator {
+    type Item = Result<crate::Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frame_cursor >= self.frame.len() {
+            if self.failed || self.frames_left == 0 {
+                return None
+            }
+            match self.decode_frame() {
+                Ok(true) => self.frames_left -= 1,
+                Ok(false) => return None,
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err))
+                }
+            }
+        }
+
+        let sample = self.frame[self.frame_cursor];
+        self.frame_cursor += 1;
+        Some(Ok(sample))
+    }
+}
+
+#[inline]
+fn read_dff_chunk_header<R: Read>(reader: &mut R) -> Result<([u8; 4], u64), DecoderError> {
+    let mut header = [0; 12];
+    read_bytes(reader, &mut header)?;
+    let mut chunk_len = [0; 8];
+    chunk_len.copy_from_slice(&header[4..12]);
+    Ok(([header[0], header[1], header[2], header[3]], u64::from_be_bytes(chunk_len)))
+}
+
+/// Gets the length of a DSDIFF chunk left after its first `read_len` bytes, which must fit in the chunk.
+#[inline]
+fn remaining_len(chunk_id: &[u8; 4], chunk_len: u64, read_len: u64) -> Result<u64, DecoderError> {
+    match chunk_len.checked_sub(read_len) {
+        Some(rest) => Ok(rest),
+        None => Err(DecoderError::format_error(AudioFormat::Dsd, format!(
+            "'{}' chunk is {} bytes long, but must be at least {}", String::from_utf8_lossy(chunk_id), chunk_len, read_len
+        ))),
+    }
+}
+
+/// Skips the rest of a DSDIFF chunk, including the pad byte after odd-length chunks.
+#[inline]
+fn skip_dff_chunk<R: Seek>(reader: &mut R, remaining_len: u64) -> Result<(), DecoderError> {
+    let skip = remaining_len.checked_add(remaining_len & 1).and_then(|skip| i64::try_from(skip).ok());
+    match skip {
+        Some(skip) => reader.seek(SeekFrom::Current(skip))?,
+        None => return Err(DecoderError::format_error(AudioFormat::Dsd, format!("chunk length {} is too large", remaining_len))),
+    };
+    Ok(())
+}
+
+#[inline]
+fn read_bytes<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), DecoderError> {
+    reader.read_exact(buf).map_err(|err| match err.kind() {
+        std::io::ErrorKind::UnexpectedEof => DecoderError::format_error(AudioFormat::Dsd, "unexpected end of file"),
+        _ => DecoderError::IOError(err),
+    })
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::AudioFormat;
+
+/// A diagnostic event reported while a file is opened or decoded.
+///
+/// Events describe how the decoder is interpreting the file, which helps when investigating files that decode
+/// incorrectly. They're only produced when `DecoderOptions::on_event` is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeEvent {
+    /// The format of the file was chosen.
+    FormatDetected {
+        format: AudioFormat,
+        /// Whether the format was identified from the file's contents rather than its extension.
+        from_content: bool,
+    },
+    /// A RIFF chunk was found while reading a WAV header.
+    ChunkParsed {
+        id: [u8; 4],
+        /// Byte offset of the chunk's contents.
+        offset: u64,
+        /// Length of the chunk's contents in bytes, as declared by its header.
+        len: u64,
+    },
+    /// Data that isn't audio was skipped, such as an ID3v2 tag at the start of an MP3 file.
+    SkippedBytes {
+        /// Byte offset of the skipped data.
+        offset: u64,
+        count: u64,
+    },
+    /// A FLAC block or MP3 frame was decoded.
+    FrameStart {
+        /// Index of the first audio frame in the block, counted from the start of the stream.
+        frame: u64,
+        /// Number of audio frames in the block.
+        len: u64,
+    },
+    /// A corrupt MP3 frame was replaced by silence, because `DecoderOptions::reset_mp3_on_error` is enabled.
+    FrameSkipped {
+        /// Index of the first audio frame that was replaced, counted from the start of the stream.
+        frame: u64,
+        /// Number of silent audio frames put in its place.
+        len: u64,
+    },
+}
+
+/// A callback that receives `DecodeEvent`s.
+///
+/// Handlers are shared between the decoder and its sample iterator, so they must be `Send` and `Sync`.
+#[derive(Clone)]
+pub struct EventHandler(Arc<dyn Fn(&DecodeEvent) + Send + Sync>);
+
+impl EventHandler {
+    /// Creates a handler that calls `callback` for every event.
+    #[inline]
+    pub fn new<F: Fn(&DecodeEvent) + Send + Sync + 'static>(callback: F) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    #[inline]
+    pub(crate) fn emit(&self, event: DecodeEvent) {
+        (self.0)(&event)
+    }
+}
+
+impl fmt::Debug for EventHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventHandler(..)")
+    }
+}
+
@@ -1,28 +1,127 @@
-use std::{fs::File, io::Read, path::Path};
+use std::{io::Read, path::Path};
+#[cfg(feature = "flac-parallel")]
+use std::path::PathBuf;
 
-use claxon::FlacReader;
+use claxon::{Block, FlacReader, FlacReaderOptions};
 
-use crate::{AudioFormat, AudioInfo, DecoderError};
+use super::md5::Md5;
+use super::ogg::OggFlacReader;
+use crate::pool::{give_buffer, take_buffer};
+use crate::{AudioFormat, AudioInfo, DecodeBufferPool, DecodeEvent, DecoderError, DecoderOptions, EventHandler, ExpectedLength, SourceReader};
+
+/// FLAC-specific information about an opened FLAC stream.
+#[derive(Debug, Clone)]
+pub struct FlacInfo {
+    md5_signature: [u8; 16],
+    bits_per_sample: u32,
+}
+
+impl FlacInfo {
+    /// Gets the MD5 signature of the unencoded audio data, as stored in the STREAMINFO block.
+    ///
+    /// Returns `None` if the encoder didn't compute a signature.
+    #[inline]
+    pub fn md5_signature(&self) -> Option<[u8; 16]> {
+        if self.md5_signature == [0; 16] {
+            None
+        } else {
+            Some(self.md5_signature)
+        }
+    }
+
+    /// Gets the bit depth of the unencoded samples.
+    #[inline]
+    pub fn bits_per_sample(&self) -> u32 {
+        self.bits_per_sample
+    }
+}
 
 pub struct FlacDecoder {
-    reader: FlacReader<File>,
+    reader: FlacReader<Box<dyn Read + Send>>,
     sample_rate: u32,
     channels: usize,
+    /// The largest block size allowed by the decoder options.
+    max_block_size: u32,
+    /// Whether to report a mismatch between the decoded length and the length in STREAMINFO as an error.
+    verify_length: bool,
+    on_event: Option<EventHandler>,
+    buffer_pool: Option<DecodeBufferPool>,
+    /// Path of the native FLAC file being decoded, used to decode its frames in parallel.
+    #[cfg(feature = "flac-parallel")]
+    path: Option<PathBuf>,
 }
 
 impl FlacDecoder {
+    /// Opens a native FLAC stream. If it came from a file, `path` lets its frames be decoded in parallel later.
     #[inline]
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DecoderError> {
-        let reader = FlacReader::open(path).map_err(flac_err_as_decoder_err)?;
+    #[allow(unused_variables)]
+    pub fn new(source: SourceReader, path: Option<&Path>, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        #[allow(unused_mut)]
+        let mut decoder = Self::from_reader(Box::new(source), options)?;
+        #[cfg(feature = "flac-parallel")]
+        {
+            decoder.path = path.map(Path::to_owned);
+        }
+        Ok(decoder)
+    }
+
+    /// Opens a FLAC stream encapsulated in an Ogg container.
+    #[inline]
+    pub fn new_ogg(source: SourceReader, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        Self::from_reader(Box::new(OggFlacReader::new(source)?), options)
+    }
+
+    #[inline]
+    fn from_reader(source: Box<dyn Read + Send>, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        let reader = FlacReader::new(source).map_err(flac_err_as_decoder_err)?;
+        check_streaminfo(&reader.streaminfo(), options)?;
         let (sample_rate, channels) = (reader.streaminfo().sample_rate, reader.streaminfo().channels);
+
         Ok(Self {
+            max_block_size: options.max_flac_block_size,
+            verify_length: options.verify_flac_length,
+            on_event: options.on_event.clone(),
+            buffer_pool: options.buffer_pool.clone(),
             sample_rate,
             channels: channels as _,
-            reader
+            reader,
+            #[cfg(feature = "flac-parallel")]
+            path: None,
         })
     }
 }
 
+/// Gets the info of a native FLAC stream from its STREAMINFO block, without reading the rest of its metadata.
+pub fn probe(source: SourceReader, options: &DecoderOptions) -> Result<AudioInfo, DecoderError> {
+    let reader = FlacReader::new_ext(source, FlacReaderOptions { metadata_only: true, read_vorbis_comment: false })?;
+    let streaminfo = reader.streaminfo();
+    check_streaminfo(&streaminfo, options)?;
+    Ok(AudioInfo {
+        format: AudioFormat::Flac,
+        sample_rate: streaminfo.sample_rate,
+        channels: streaminfo.channels as usize,
+        total_frames: streaminfo.samples,
+    })
+}
+
+/// Checks that a stream described by `streaminfo` can be decoded within the limits set by `options`.
+fn check_streaminfo(streaminfo: &claxon::metadata::StreamInfo, options: &DecoderOptions) -> Result<(), DecoderError> {
+    DecoderError::check_stream_params(AudioFormat::Flac, streaminfo.sample_rate, streaminfo.channels as usize)?;
+
+    // The block buffer is sized from STREAMINFO, so don't trust a corrupt or malicious header with the allocation
+    if streaminfo.max_block_size as u32 > options.max_flac_block_size {
+        return Err(DecoderError::format_error(AudioFormat::Flac, format!("block size {} exceeds the limit of {}", streaminfo.max_block_size, options.max_flac_block_size)))
+    }
+
+    // claxon only decodes frames whose headers spell out the bit depth, which FLAC can only do for these depths.
+    // Other depths defer to STREAMINFO, and 32-bit frames use a header code that claxon 0.4 still treats as reserved,
+    // so both would fail on the first frame with a cryptic error even though max_sample_value() handles 32 bits
+    if !SUPPORTED_BIT_DEPTHS.contains(&streaminfo.bits_per_sample) {
+        return Err(DecoderError::format_error(AudioFormat::Flac, format!("{}-bit audio is not supported", streaminfo.bits_per_sample)))
+    }
+    Ok(())
+}
+
 impl FlacDecoder {
     #[inline]
     pub fn info(&self) -> AudioInfo {
@@ -30,66 +129,308 @@ impl FlacDecoder {
             format: AudioFormat::Flac,
             sample_rate: self.sample_rate,
             channels: self.channels,
+            total_frames: self.reader.streaminfo().samples,
+        }
+    }
+
+    #[cfg(feature = "backend-access")]
+    #[inline]
+    pub fn claxon_reader(&self) -> &FlacReader<Box<dyn Read + Send>> {
+        &self.reader
+    }
+
+    #[inline]
+    pub fn flac_info(&self) -> FlacInfo {
+        let streaminfo = self.reader.streaminfo();
+        FlacInfo {
+            md5_signature: streaminfo.md5sum,
+            bits_per_sample: streaminfo.bits_per_sample,
+        }
+    }
+
+    /// Decodes the whole stream and checks the MD5 of the decoded audio against the signature in STREAMINFO.
+    pub fn verify_md5(mut self) -> Result<bool, DecoderError> {
+        let info = self.flac_info();
+        let expected = match info.md5_signature() {
+            Some(signature) => signature,
+            None => return Err(DecoderError::format_error(AudioFormat::Flac, "file has no MD5 signature"))
+        };
+
+        // The signature covers the interleaved samples, little-endian, in the smallest whole number of bytes
+        let bytes_per_sample = info.bits_per_sample.div_ceil(8) as usize;
+        let mut md5 = Md5::new();
+        let mut blocks = self.reader.blocks();
+        let mut buffer = vec![];
+        let mut bytes = vec![];
+        while let Some(block) = blocks.read_next_or_eof(buffer).map_err(flac_err_as_decoder_err)? {
+            bytes.clear();
+            for frame in 0..block.duration() {
+                for channel in 0..block.channels() {
+                    bytes.extend_from_slice(&block.sample(channel, frame).to_le_bytes()[..bytes_per_sample]);
+                }
+            }
+            md5.update(&bytes);
+            buffer = block.into_buffer();
+        }
+
+        Ok(md5.finish() == expected)
+    }
+
+    #[inline]
+    pub fn into_samples(self) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>> + Send>, DecoderError> {
+        Ok(Box::new(self.into_flac_samples()))
+    }
+
+    #[inline]
+    pub fn into_flac_samples(self) -> FlacSamples {
+        let (declared_frames, channels, verify_length) = (self.reader.streaminfo().samples, self.channels, self.verify_length);
+        FlacSamples {
+            samples: with_declared_length(FlacSampleIterator::new(self.reader, self.max_block_size, self.on_event, self.buffer_pool), declared_frames, channels, verify_length),
+        }
+    }
+
+    /// Decodes the frames on `threads` threads (or one per CPU if `threads` is 0) and returns an iterator over the samples.
+    ///
+    /// Only native FLAC files can be split up this way; FLAC-in-Ogg streams fall back to `into_samples()`.
+    #[cfg(feature = "flac-parallel")]
+    pub fn into_samples_parallel(self, threads: usize) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>> + Send>, DecoderError> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => return self.into_samples(),
+        };
+        let streaminfo = self.reader.streaminfo();
+        let samples = super::flac_parallel::decode_parallel(&path, streaminfo.bits_per_sample, self.max_block_size, threads)?;
+        Ok(Box::new(with_declared_length(samples, streaminfo.samples, self.channels, self.verify_length)))
+    }
+}
+
+/// The samples of a FLAC file, as an iterator of a concrete type.
+///
+/// See `Decoder::into_flac_samples()`.
+pub struct FlacSamples {
+    samples: DeclaredLength<FlacSampleIterator<Box<dyn Read + Send>>>,
+}
+
+impl Iterator for FlacSamples {
+    type Item = Result<crate::Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.samples.next()
+    }
+}
+
+/// Wraps the samples of a stream so that its length is checked against the frame count declared in STREAMINFO, if any.
+fn with_declared_length<I>(samples: I, declared_frames: Option<u64>, channels: usize, verify: bool) -> DeclaredLength<I>
+where I: Iterator<Item = Result<crate::Sample, DecoderError>>
+{
+    // A nonzero sample count in STREAMINFO lets us tell a clean ending apart from a truncated file
+    match declared_frames {
+        Some(frames) if verify => DeclaredLength::Verified(VerifiedLength {
+            samples,
+            channels: channels.max(1) as u64,
+            expected_samples: frames * channels as u64,
+            read_samples: 0,
+            finished: false,
+        }),
+        Some(frames) => DeclaredLength::Expected(ExpectedLength::new(samples, frames * channels as u64)),
+        None => DeclaredLength::Unknown(samples),
+    }
+}
+
+/// The samples of a stream, checked against its declared length in the way `with_declared_length()` chose.
+enum DeclaredLength<I: Iterator<Item = Result<crate::Sample, DecoderError>>> {
+    Verified(VerifiedLength<I>),
+    Expected(ExpectedLength<I>),
+    Unknown(I),
+}
+
+impl<I: Iterator<Item = Result<crate::Sample, DecoderError>>> Iterator for DeclaredLength<I> {
+    type Item = Result<crate::Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            DeclaredLength::Verified(samples) => samples.next(),
+            DeclaredLength::Expected(samples) => samples.next(),
+            DeclaredLength::Unknown(samples) => samples.next(),
         }
     }
+}
+
+/// Reports a `FormatError` if a stream decodes to more or fewer samples than STREAMINFO declares.
+struct VerifiedLength<I: Iterator<Item = Result<crate::Sample, DecoderError>>> {
+    samples: I,
+    channels: u64,
+    expected_samples: u64,
+    read_samples: u64,
+    finished: bool,
+}
+
+impl<I: Iterator<Item = Result<crate::Sample, DecoderError>>> Iterator for VerifiedLength<I> {
+    type Item = Result<crate::Sample, DecoderError>;
 
     #[inline]
-    pub fn into_samples(self) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>>>, DecoderError> {
-        Ok(Box::new(FlacSampleIterator::new(self.reader)))
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None
+        }
+        match self.samples.next() {
+            Some(Ok(_)) if self.read_samples == self.expected_samples => {
+                self.finished = true;
+                Some(Err(DecoderError::format_error(AudioFormat::Flac, format!("stream runs past the {} frames declared in STREAMINFO", self.expected_samples / self.channels))))
+            },
+            Some(Ok(sample)) => {
+                self.read_samples += 1;
+                Some(Ok(sample))
+            },
+            Some(Err(err)) => {
+                self.finished = true;
+                Some(Err(err))
+            },
+            None if self.read_samples < self.expected_samples => {
+                self.finished = true;
+                Some(Err(DecoderError::format_error(AudioFormat::Flac, format!(
+                    "decoded {} frames, but STREAMINFO declares {}", self.read_samples / self.channels, self.expected_samples / self.channels
+                ))))
+            },
+            None => None,
+        }
+    }
+}
+
+/// The bit depths that can be decoded.
+const SUPPORTED_BIT_DEPTHS: [u32; 5] = [8, 12, 16, 20, 24];
+
+/// Returns the magnitude of a full-scale sample at the given bit depth.
+///
+/// As with WAV, samples are divided by the largest positive value, so the most negative sample maps slightly below -1.0.
+/// Wasted bits need no special handling, since claxon shifts the samples back up to the full bit depth.
+#[inline]
+pub(super) fn max_sample_value(bits_per_sample: u32) -> f32 {
+    (i32::MAX >> (32 - bits_per_sample)) as f32
+}
+
+/// Appends the samples in `block` to `samples`, with the channels interleaved.
+#[inline]
+pub(super) fn interleave_block(block: &Block, max_sample_value: f32, samples: &mut Vec<crate::Sample>) {
+    samples.reserve(block.len() as usize);
+    for frame in 0..block.duration() {
+        for channel in 0..block.channels() {
+            samples.push(block.sample(channel, frame) as f32 / max_sample_value);
+        }
     }
 }
 
 struct FlacSampleIterator<R: Read> {
     reader: FlacReader<R>,
+    /// Decoding buffer, which `claxon` fills with one channel after another.
     cur_block: Vec<i32>,
-    cur_block_len: usize,
+    /// The samples of the current block, interleaved.
+    cur_samples: Vec<crate::Sample>,
     max_sample_value: f32,
+    max_block_size: u32,
     block_cursor: usize,
+    finished: bool,
+    /// Index of the first audio frame in the next block.
+    next_frame: u64,
+    on_event: Option<EventHandler>,
+    /// The pool the buffers came from, which they're returned to when the iterator is dropped.
+    buffer_pool: Option<DecodeBufferPool>,
 }
 
-impl<'a, R: Read + 'a> FlacSampleIterator<R> {
-    fn new(reader: FlacReader<R>) -> Self {
+impl<R: Read> FlacSampleIterator<R> {
+    fn new(reader: FlacReader<R>, max_block_size: u32, on_event: Option<EventHandler>, buffer_pool: Option<DecodeBufferPool>) -> Self {
         let info = reader.streaminfo();
+        let capacity = info.max_block_size as usize * info.channels as usize;
         
         Self {
-            cur_block: Vec::with_capacity(info.max_block_size as usize * info.channels as usize),
-            max_sample_value: (i32::MAX >> (32 - info.bits_per_sample)) as f32,
+            cur_block: take_buffer(buffer_pool.as_ref(), capacity),
+            cur_samples: take_buffer(buffer_pool.as_ref(), capacity),
+            max_sample_value: max_sample_value(info.bits_per_sample),
+            max_block_size,
             reader,
-            cur_block_len: 0,
-            block_cursor: 0
+            block_cursor: 0,
+            finished: false,
+            next_frame: 0,
+            on_event,
+            buffer_pool,
         }
     }
 }
 
+impl<R: Read> Drop for FlacSampleIterator<R> {
+    fn drop(&mut self) {
+        give_buffer(self.buffer_pool.as_ref(), std::mem::take(&mut self.cur_block));
+        give_buffer(self.buffer_pool.as_ref(), std::mem::take(&mut self.cur_samples));
+    }
+}
+
 impl<R: Read> Iterator for FlacSampleIterator<R> {
     type Item = Result<crate::Sample, DecoderError>;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None
+        }
+
         loop {
-            if self.block_cursor < self.cur_block_len {
-                let sample_float = self.cur_block[self.block_cursor] as f32 / self.max_sample_value;
+            if let Some(sample) = self.cur_samples.get(self.block_cursor) {
                 self.block_cursor += 1;
-                return Some(Ok(sample_float));
+                return Some(Ok(*sample));
             }
 
             self.block_cursor = 0;
-            let block_buffer = std::mem::replace(&mut self.cur_block, vec![]);
+            // claxon decodes into the buffer it's given and hands it back through `into_buffer()`, so the same
+            // allocation is used for every block; `take` only leaves an empty, unallocated `Vec` in its place meanwhile
+            let block_buffer = std::mem::take(&mut self.cur_block);
             match self.reader.blocks().read_next_or_eof(block_buffer) {
+                Ok(Some(block)) if block.duration() > self.max_block_size => {
+                    self.finished = true;
+                    return Some(Err(DecoderError::format_error(AudioFormat::Flac, format!("block size {} exceeds the limit of {}", block.duration(), self.max_block_size))))
+                }
                 Ok(Some(block)) => {
-                    self.cur_block_len = block.len() as _;
+                    // claxon derives `Block::time()` from the size of the block itself, which is wrong for a short final
+                    // block in a fixed-blocksize stream, so keep count instead
+                    if let Some(handler) = &self.on_event {
+                        handler.emit(DecodeEvent::FrameStart { frame: self.next_frame, len: block.duration() as u64 });
+                    }
+                    self.next_frame += block.duration() as u64;
+                    self.cur_samples.clear();
+                    interleave_block(&block, self.max_sample_value, &mut self.cur_samples);
                     self.cur_block = block.into_buffer();
                 }
-                _ => return None,
+                Ok(None) => return None,
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(flac_err_as_decoder_err(err)))
+                }
             }
         }
     }
 }
 
-fn flac_err_as_decoder_err(error: claxon::Error) -> DecoderError {
+pub(super) fn flac_err_as_decoder_err(error: claxon::Error) -> DecoderError {
     match error {
+        claxon::Error::IoError(ioerr) if ioerr.kind() == std::io::ErrorKind::UnexpectedEof => DecoderError::IncompleteData,
+        // Errors from the Ogg container are passed to claxon wrapped in an I/O error, but they're really format errors
+        claxon::Error::IoError(ioerr) if ioerr.get_ref().is_some_and(|inner| inner.is::<ogg::OggReadError>()) => {
+            let inner = ioerr.into_inner().and_then(|inner| inner.downcast::<ogg::OggReadError>().ok());
+            match inner {
+                Some(err) => super::ogg::ogg_err_to_decoder_err(*err),
+                None => DecoderError::format_error(AudioFormat::Flac, "Ogg container error"),
+            }
+        },
         claxon::Error::IoError(ioerr) => DecoderError::IOError(ioerr),
-        claxon::Error::FormatError(fmterr) => DecoderError::FormatError(format!("flac: format error: {}", fmterr)),
-        claxon::Error::Unsupported(what) => DecoderError::FormatError(format!("flac: unsupported: {}", what)),
+        claxon::Error::FormatError(fmterr) => DecoderError::format_error(AudioFormat::Flac, format!("format error: {}", fmterr)),
+        claxon::Error::Unsupported(what) => DecoderError::format_error(AudioFormat::Flac, format!("unsupported: {}", what)),
+    }
+}
+
+impl From<claxon::Error> for DecoderError {
+    #[inline]
+    fn from(error: claxon::Error) -> Self {
+        flac_err_as_decoder_err(error)
     }
-}
\ No newline at end of file
+}
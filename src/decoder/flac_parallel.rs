@@ -0,0 +1,96 @@
+use std::{fs, io::Cursor, path::Path, thread};
+
+use claxon::frame::FrameReader;
+
+use super::flac::{flac_err_as_decoder_err, interleave_block, max_sample_value};
+use crate::{AudioFormat, DecoderError, Sample};
+
+/// Decodes the frames of a native FLAC file on `threads` threads and returns an iterator over the samples, in order.
+///
+/// The frame data is split into one contiguous range per thread, with each range starting on a frame boundary.
+/// Since the whole file is decoded before the iterator is returned, this trades memory for throughput.
+pub fn decode_parallel(path: &Path, bits_per_sample: u32, max_block_size: u32, threads: usize) -> Result<impl Iterator<Item = Result<Sample, DecoderError>> + Send, DecoderError> {
+    let data = fs::read(path)?;
+    let frames = &data[frames_offset(&data)?..];
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+
+    // Find where each thread's range starts; duplicate boundaries simply produce empty ranges
+    let mut boundaries = vec![0];
+    for i in 1..threads {
+        let target = frames.len() * i / threads;
+        boundaries.push(find_frame_start(frames, target.max(*boundaries.last().unwrap())));
+    }
+    boundaries.push(frames.len());
+
+    let max_sample_value = max_sample_value(bits_per_sample);
+    let mut ranges: Vec<(Vec<Sample>, Option<DecoderError>)> = thread::scope(|scope| {
+        let workers: Vec<_> = boundaries.windows(2)
+            .map(|range| {
+                let range = &frames[range[0]..range[1]];
+                scope.spawn(move || decode_range(range, max_sample_value, max_block_size))
+            })
+            .collect();
+        workers.into_iter()
+            .map(|worker| worker.join().unwrap_or_else(|_| (vec![], Some(DecoderError::format_error(AudioFormat::Flac, "decoder thread panicked")))))
+            .collect()
+    });
+
+    // Decoding stops at the first error, so drop everything after the range that failed
+    if let Some(failed) = ranges.iter().position(|(_, err)| err.is_some()) {
+        ranges.truncate(failed + 1);
+    }
+
+    Ok(ranges.into_iter().flat_map(|(samples, err)| samples.into_iter().map(Ok).chain(err.map(Err))))
+}
+
+/// Returns the offset of the first audio frame, just past the metadata blocks.
+fn frames_offset(data: &[u8]) -> Result<usize, DecoderError> {
+    if !data.starts_with(b"fLaC") {
+        return Err(DecoderError::format_error(AudioFormat::Flac, "no FLAC signature found"))
+    }
+
+    let mut offset = 4;
+    loop {
+        let header = data.get(offset..offset + 4).ok_or(DecoderError::IncompleteData)?;
+        offset += 4 + u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        if header[0] & 0x80 != 0 {
+            return Ok(offset.min(data.len()))
+        }
+    }
+}
+
+/// Returns the offset of the first frame that starts at or after `from`, or the end of the data if there is none.
+///
+/// A sync code can also appear by chance inside a frame, so a candidate only counts if a whole frame decodes from it.
+fn find_frame_start(frames: &[u8], from: usize) -> usize {
+    (from..frames.len().saturating_sub(1))
+        .find(|&pos| {
+            frames[pos] == 0xff
+                && frames[pos + 1] & 0xfe == 0xf8
+                && matches!(FrameReader::new(Cursor::new(&frames[pos..])).read_next_or_eof(vec![]), Ok(Some(_)))
+        })
+        .unwrap_or(frames.len())
+}
+
+/// Decodes every frame in `frames`, returning the samples decoded before any error along with the error.
+fn decode_range(frames: &[u8], max_sample_value: f32, max_block_size: u32) -> (Vec<Sample>, Option<DecoderError>) {
+    let mut cursor = Cursor::new(frames);
+    let mut samples = vec![];
+    let mut buffer = vec![];
+    loop {
+        match FrameReader::new(&mut cursor).read_next_or_eof(buffer) {
+            Ok(Some(block)) if block.duration() > max_block_size => {
+                return (samples, Some(DecoderError::format_error(AudioFormat::Flac, format!("block size {} exceeds the limit of {}", block.duration(), max_block_size))))
+            },
+            Ok(Some(block)) => {
+                interleave_block(&block, max_sample_value, &mut samples);
+                buffer = block.into_buffer();
+            },
+            Ok(None) => return (samples, None),
+            Err(err) => return (samples, Some(flac_err_as_decoder_err(err))),
+        }
+    }
+}
@@ -1,57 +1,241 @@
-use std::{fs::File, path::Path, io::Read};
+use std::convert::TryInto;
+use std::io::{Read, Seek, SeekFrom};
 use minimp3::{Decoder as Mp3Reader, Frame, Error as Mp3Error};
 
-use crate::{AudioFormat, AudioInfo, DecoderError};
+use super::sniff::parse_mp3_frame_header;
+use crate::{AudioFormat, AudioInfo, DecodeEvent, DecoderError, DecoderOptions, EventHandler, ExpectedLength, SourceReader};
+
+/// The delay of the decoder's synthesis filter bank, in frames, which encoders leave out of the delay they record.
+const DECODER_DELAY: u64 = 528 + 1;
 
 pub struct Mp3Decoder {
-    reader: Mp3Reader<File>,
+    reader: Mp3Reader<SourceReader>,
     first_frame: Frame,
     sample_rate: u32,
     channels: usize,
+    on_event: Option<EventHandler>,
+    /// The encoder delay and padding to trim, if gapless decoding is enabled and the stream records them.
+    gapless: Option<GaplessInfo>,
+    /// The most MP3 frames that may be decoded, from the decoder options.
+    max_frames: Option<u64>,
+    reset_on_error: bool,
+}
+
+/// The encoder delay and padding of a stream, from the Xing or Info frame that starts it.
+#[derive(Debug, Copy, Clone)]
+struct GaplessInfo {
+    /// Number of frames to discard from the start, including the Xing frame itself and the decoder delay.
+    skip_frames: u64,
+    /// Number of frames left once the delay and padding are trimmed.
+    total_frames: u64,
 }
 
 impl Mp3Decoder {
     #[inline]
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DecoderError> {
-        let f = File::open(path).map_err(|err| DecoderError::IOError(err))?;
-        let mut reader = Mp3Reader::new(f);
+    pub fn new(mut source: SourceReader, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        let gapless = if options.gapless_mp3 { read_gapless_info(&mut source)? } else { None };
+
+        // minimp3 skips over anything that isn't a frame without saying so, so find a leading ID3v2 tag to report it
+        if let Some(handler) = &options.on_event {
+            if let Some(tag_len) = id3v2_tag_len(&mut source)? {
+                handler.emit(DecodeEvent::SkippedBytes { offset: 0, count: tag_len });
+            }
+        }
+
+        let mut reader = Mp3Reader::new(source);
         let first_frame = loop {
             match reader.next_frame() {
                 Ok(frame) => break frame,
                 Err(Mp3Error::SkippedData) => continue,
-                Err(Mp3Error::Eof) => return Err(DecoderError::FormatError("mp3: no audio data".to_owned())),
+                Err(Mp3Error::Eof) => return Err(DecoderError::format_error(AudioFormat::Mp3, "no audio data")),
                 Err(other) => return Err(mp3_err_to_decoder_err(other))
             }
         };
 
+        DecoderError::check_stream_params(AudioFormat::Mp3, first_frame.sample_rate as u32, first_frame.channels)?;
+
+        // MPEG-1/2 Layer III carries at most two channels; multichannel extensions aren't decoded by minimp3
+        if !(1..=2).contains(&first_frame.channels) {
+            return Err(DecoderError::format_error(AudioFormat::Mp3, format!("{} channels are not supported; only mono and stereo streams can be decoded", first_frame.channels)))
+        }
+
+        if options.max_mp3_frames == Some(0) {
+            return Err(frame_limit_error(0))
+        }
+
+        if let Some(handler) = &options.on_event {
+            handler.emit(DecodeEvent::FrameStart { frame: 0, len: (first_frame.data.len() / first_frame.channels) as u64 });
+        }
+
         Ok(Self {
             sample_rate: first_frame.sample_rate as _,
             channels: first_frame.channels as _,
             first_frame,
             reader,
+            on_event: options.on_event.clone(),
+            gapless,
+            max_frames: options.max_mp3_frames,
+            reset_on_error: options.reset_mp3_on_error,
         })
     }
 }
 
+/// Reads the encoder delay and padding from the Xing or Info frame at the start of the stream, after any ID3v2 tag.
+///
+/// The stream is left at the start.
+fn read_gapless_info(source: &mut SourceReader) -> Result<Option<GaplessInfo>, DecoderError> {
+    let tag_len = id3v2_tag_len(source)?.unwrap_or(0);
+    source.seek(SeekFrom::Start(tag_len))?;
+    // No frame is longer than this, even at the highest bitrate and lowest sample rate
+    let mut frame = vec![];
+    source.by_ref().take(4096).read_to_end(&mut frame)?;
+    source.seek(SeekFrom::Start(0))?;
+    Ok(parse_gapless_info(&frame))
+}
+
+/// Parses the Xing or Info frame that starts `frame`, which encoders write in place of the first audio frame.
+///
+/// The frame decodes to silence; LAME and compatible encoders extend it with the delay they added before the audio and
+/// the padding they added after it. Returns `None` if `frame` isn't such a frame or it doesn't record the frame count.
+fn parse_gapless_info(frame: &[u8]) -> Option<GaplessInfo> {
+    let header = parse_mp3_frame_header(frame)?;
+    let tag = frame.get(header.main_data_offset..header.len)?;
+    if !tag.starts_with(b"Xing") && !tag.starts_with(b"Info") {
+        return None
+    }
+    let read_u32 = |offset: usize| tag.get(offset..offset + 4).map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()));
+    let flags = read_u32(4)?;
+    if flags & 0x01 == 0 {
+        return None
+    }
+    let frames = read_u32(8)? as u64 * header.samples_per_frame as u64;
+    // The frame count is followed by the byte count, the seek table, and the quality, if their flags are set
+    let extension = 12 + [(0x02, 4), (0x04, 100), (0x08, 4)].iter().filter(|(flag, _)| flags & flag != 0).map(|(_, len)| len).sum::<usize>();
+    // The extension starts with the encoder's name, such as "LAME3.100"; its delay and padding are packed into 24 bits
+    let (delay, padding) = match tag.get(extension..extension + 24) {
+        Some(ext) if ext[0] != 0 => (
+            ((ext[21] as u64) << 4 | (ext[22] >> 4) as u64) + DECODER_DELAY,
+            (((ext[22] & 0x0f) as u64) << 8 | ext[23] as u64).saturating_sub(DECODER_DELAY),
+        ),
+        _ => (0, 0),
+    };
+    Some(GaplessInfo {
+        skip_frames: header.samples_per_frame as u64 + delay,
+        total_frames: frames.saturating_sub(delay + padding),
+    })
+}
+
+/// Gets the info of an MP3 stream from its first frame header, without decoding the frame.
+///
+/// Returns `None` if no frame is found within `max_probe_bytes` of the end of any ID3v2 tag.
+pub fn probe(mut source: SourceReader, max_probe_bytes: u64) -> Result<Option<AudioInfo>, DecoderError> {
+    let tag_len = id3v2_tag_len(&mut source)?.unwrap_or(0);
+    source.seek(SeekFrom::Start(tag_len))?;
+    // Frames are rarely more than a couple of kilobytes long, so start small and only read further in to skip junk
+    let mut data = vec![];
+    let mut want = 4096;
+    loop {
+        let read = source.by_ref().take(want - data.len() as u64).read_to_end(&mut data)?;
+        if let Some(header) = super::sniff::find_mp3_frame(&data) {
+            return Ok(Some(AudioInfo {
+                format: AudioFormat::Mp3,
+                sample_rate: header.sample_rate,
+                channels: header.channels,
+                total_frames: None,
+            }))
+        }
+        if read == 0 || want >= max_probe_bytes {
+            return Ok(None)
+        }
+        want = (want * 2).min(max_probe_bytes);
+    }
+}
+
+/// Gets the length of the ID3v2 tag at the start of the stream, including its header and footer, if there is one.
+///
+/// The stream is left at the start.
+fn id3v2_tag_len(source: &mut SourceReader) -> Result<Option<u64>, DecoderError> {
+    let mut header = [0; 10];
+    let found = source.read_exact(&mut header).is_ok() && &header[..3] == b"ID3";
+    source.seek(SeekFrom::Start(0))?;
+    if !found {
+        return Ok(None)
+    }
+    // The tag size is stored as a 28-bit "synchsafe" integer, with the top bit of each byte left clear
+    let size = header[6..10].iter().fold(0u64, |size, byte| (size << 7) | (byte & 0x7f) as u64);
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    Ok(Some(header.len() as u64 + size + footer))
+}
+
 impl Mp3Decoder {
+    #[cfg(feature = "backend-access")]
+    #[inline]
+    pub fn minimp3_reader(&self) -> &Mp3Reader<SourceReader> {
+        &self.reader
+    }
+
     #[inline]
     pub fn info(&self) -> AudioInfo {
         AudioInfo {
             format: AudioFormat::Mp3,
             sample_rate: self.sample_rate,
             channels: self.channels,
+            total_frames: self.gapless.map(|gapless| gapless.total_frames),
         }
     }
 
     #[inline]
-    pub fn into_samples(self) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>>>, DecoderError> {
-        Ok(Box::new(Mp3SampleIterator {
+    pub fn into_samples(self) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>> + Send>, DecoderError> {
+        Ok(Box::new(self.into_mp3_samples()))
+    }
+
+    pub fn into_mp3_samples(self) -> Mp3Samples {
+        let channels = self.channels as u64;
+        let samples = Mp3SampleIterator {
             expected_channels: self.channels,
             expected_sample_rate: self.sample_rate,
+            next_frame: (self.first_frame.data.len() / self.channels) as u64,
             cur_frame: self.first_frame,
             frame_cursor: 0,
-            reader: self.reader
-        }))
+            reader: self.reader,
+            on_event: self.on_event,
+            skip: self.gapless.map_or(0, |gapless| gapless.skip_frames * channels),
+            frames_decoded: 1,
+            max_frames: self.max_frames,
+            reset_on_error: self.reset_on_error,
+            finished: false,
+        };
+        Mp3Samples {
+            samples: match self.gapless {
+                Some(gapless) => Mp3Length::Trimmed(ExpectedLength::new(samples, gapless.total_frames * channels)),
+                None => Mp3Length::Full(samples),
+            },
+        }
+    }
+}
+
+/// The samples of an MP3 file, as an iterator of a concrete type.
+///
+/// See `Decoder::into_mp3_samples()`.
+pub struct Mp3Samples {
+    samples: Mp3Length,
+}
+
+/// The samples of an MP3 stream, either as decoded or with the encoder's padding trimmed for gapless playback.
+enum Mp3Length {
+    Full(Mp3SampleIterator<SourceReader>),
+    Trimmed(ExpectedLength<Mp3SampleIterator<SourceReader>>),
+}
+
+impl Iterator for Mp3Samples {
+    type Item = Result<crate::Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.samples {
+            Mp3Length::Full(samples) => samples.next(),
+            Mp3Length::Trimmed(samples) => samples.next(),
+        }
     }
 }
 
@@ -61,6 +245,18 @@ struct Mp3SampleIterator<R: Read> {
     expected_sample_rate: u32,
     cur_frame: Frame,
     frame_cursor: usize,
+    /// Index of the first audio frame in the next MP3 frame.
+    next_frame: u64,
+    on_event: Option<EventHandler>,
+    /// Number of samples left to discard from the start of the stream for gapless decoding.
+    skip: u64,
+    /// Number of MP3 frames decoded so far, including the current one.
+    frames_decoded: u64,
+    max_frames: Option<u64>,
+    /// Whether to replace frames that don't match the stream with silence rather than fail.
+    reset_on_error: bool,
+    /// Whether decoding stopped at the frame limit.
+    finished: bool,
 }
 
 impl<R: Read> Iterator for Mp3SampleIterator<R> {
@@ -68,22 +264,61 @@ impl<R: Read> Iterator for Mp3SampleIterator<R> {
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_sample()? {
+                Ok(_) if self.skip > 0 => self.skip -= 1,
+                sample => return Some(sample),
+            }
+        }
+    }
+}
+
+impl<R: Read> Mp3SampleIterator<R> {
+    #[inline]
+    fn next_sample(&mut self) -> Option<Result<crate::Sample, DecoderError>> {
         // Read next frame in if current frame is exhausted
         if self.frame_cursor >= self.cur_frame.data.len() {
+            if self.finished {
+                return None
+            }
             self.frame_cursor = 0;
             self.cur_frame = loop {
                 match self.reader.next_frame() {
-                    Ok(frame) => {
+                    Ok(mut frame) => {
                         // Skip empty frames
-                        if frame.data.len() == 0 { continue }
-                        // Make sure the sample rates match
-                        if frame.sample_rate as u32 != self.expected_sample_rate {
-                            return Some(Err(DecoderError::FormatError("mp3: streams with variable sample rates are not supported".to_owned())))
+                        if frame.data.is_empty() { continue }
+                        // Make sure the sample rates and channel counts match
+                        let mismatch = if frame.sample_rate as u32 != self.expected_sample_rate {
+                            Some("streams with variable sample rates are not supported")
+                        } else if frame.channels != self.expected_channels {
+                            Some("streams with variable channel counts are not supported")
+                        } else {
+                            None
+                        };
+                        if let Some(message) = mismatch {
+                            if !self.reset_on_error {
+                                return Some(Err(DecoderError::format_error(AudioFormat::Mp3, message)))
+                            }
+                            // Take the frame to be corrupt, and keep its length so the frames after it stay in time
+                            let len = frame.data.len() / frame.channels.max(1);
+                            if let Some(handler) = &self.on_event {
+                                handler.emit(DecodeEvent::FrameSkipped { frame: self.next_frame, len: len as u64 });
+                            }
+                            frame.data = vec![0; len * self.expected_channels];
+                            frame.channels = self.expected_channels;
+                            frame.sample_rate = self.expected_sample_rate as _;
+                        }
+                        if self.max_frames.is_some_and(|max| self.frames_decoded >= max) {
+                            self.finished = true;
+                            self.cur_frame.data.clear();
+                            return Some(Err(frame_limit_error(self.frames_decoded)))
                         }
-                        // Make sure the channel counts match
-                        if frame.channels != self.expected_channels {
-                            return Some(Err(DecoderError::FormatError("mp3: streams with variable channel counts are not supported".to_owned())))
+                        self.frames_decoded += 1;
+                        let len = (frame.data.len() / frame.channels.max(1)) as u64;
+                        if let Some(handler) = &self.on_event {
+                            handler.emit(DecodeEvent::FrameStart { frame: self.next_frame, len });
                         }
+                        self.next_frame += len;
                         break frame
                     },
                     Err(Mp3Error::SkippedData) => continue,
@@ -99,11 +334,24 @@ impl<R: Read> Iterator for Mp3SampleIterator<R> {
     }
 }
 
+#[inline]
+fn frame_limit_error(max_frames: u64) -> DecoderError {
+    DecoderError::format_error(AudioFormat::Mp3, format!("frame count exceeds the limit of {}", max_frames))
+}
+
 #[inline]
 fn mp3_err_to_decoder_err(error: minimp3::Error) -> DecoderError {
     match error {
         minimp3::Error::Io(ioerr) => DecoderError::IOError(ioerr),
-        minimp3::Error::InsufficientData => DecoderError::FormatError(format!("mp3: insufficient data")),
-        _ => unimplemented!()
+        minimp3::Error::InsufficientData => DecoderError::format_error(AudioFormat::Mp3, "insufficient data"),
+        minimp3::Error::SkippedData => DecoderError::format_error(AudioFormat::Mp3, "skipped data that isn't an MP3 frame"),
+        minimp3::Error::Eof => DecoderError::IncompleteData,
+    }
+}
+
+impl From<minimp3::Error> for DecoderError {
+    #[inline]
+    fn from(error: minimp3::Error) -> Self {
+        mp3_err_to_decoder_err(error)
     }
 }
\ No newline at end of file
@@ -0,0 +1,317 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use super::raw::read_full;
+use crate::{AudioFormat, DecoderError};
+#[cfg(feature = "flac")]
+use crate::SourceReader;
+
+/// The codec carried by the first logical stream of an Ogg file.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OggCodec {
+    Vorbis,
+    Flac,
+    Opus,
+    Speex,
+    /// Theora video, which is skipped when looking for an audio stream.
+    Theora,
+    Unknown,
+}
+
+impl OggCodec {
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        match self {
+            OggCodec::Vorbis => "Vorbis",
+            OggCodec::Flac => "FLAC",
+            OggCodec::Opus => "Opus",
+            OggCodec::Speex => "Speex",
+            OggCodec::Theora => "Theora",
+            OggCodec::Unknown => "unknown",
+        }
+    }
+
+    /// Returns `true` if the codec is an audio codec, whether or not creak can decode it.
+    #[inline]
+    fn is_audio(&self) -> bool {
+        !matches!(self, OggCodec::Theora | OggCodec::Unknown)
+    }
+}
+
+/// The logical stream of an Ogg file chosen for decoding.
+#[derive(Debug, Copy, Clone)]
+pub struct OggAudioStream {
+    pub codec: OggCodec,
+    pub stream_serial: u32,
+    /// Whether this is the file's first logical stream. Any other stream must be passed through a `StreamFilter`
+    /// to be decoded.
+    pub is_first: bool,
+}
+
+/// Reads the beginning-of-stream pages at the start of an Ogg file, each of which opens one multiplexed logical stream,
+/// and picks the first one that carries audio. If none does, the first stream is picked.
+///
+/// The stream is rewound to the start afterwards.
+pub fn sniff_audio_stream<R: Read + Seek>(reader: &mut R) -> Result<OggAudioStream, DecoderError> {
+    let mut first = None;
+    reader.seek(SeekFrom::Start(0))?;
+    loop {
+        let mut page_header = [0; 27];
+        match reader.read_exact(&mut page_header) {
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && first.is_some() => break,
+            result => result.map_err(|err| match err.kind() {
+                io::ErrorKind::UnexpectedEof => DecoderError::format_error(AudioFormat::Vorbis, "unexpected end of first page"),
+                _ => DecoderError::IOError(err),
+            })?,
+        }
+        // All of the beginning-of-stream pages come before any other page
+        if &page_header[0..4] != b"OggS" || (first.is_some() && page_header[5] & 0x02 == 0) {
+            break
+        }
+        let stream_serial = u32::from_le_bytes([page_header[14], page_header[15], page_header[16], page_header[17]]);
+        let mut segment_table = vec![0; page_header[26] as usize];
+        read_bytes(reader, &mut segment_table)?;
+        let payload_len: u64 = segment_table.iter().map(|&len| len as u64).sum();
+        let mut magic = vec![0; segment_table.first().map_or(0, |len| (*len as usize).min(8))];
+        read_bytes(reader, &mut magic)?;
+        reader.seek(SeekFrom::Current((payload_len - magic.len() as u64) as i64))?;
+
+        let stream = OggAudioStream {
+            codec: codec_from_magic(&magic),
+            stream_serial,
+            is_first: first.is_none(),
+        };
+        if stream.codec.is_audio() {
+            first = Some(stream);
+            break
+        }
+        first.get_or_insert(stream);
+    }
+    reader.seek(SeekFrom::Start(0))?;
+    match first {
+        Some(stream) => Ok(stream),
+        None => Err(DecoderError::format_error(AudioFormat::Vorbis, "no Ogg page found")),
+    }
+}
+
+/// The number of bytes a `StreamFilter` keeps behind its read position, so the reader on top of it can seek back
+/// over data it has read ahead. This covers the read-ahead of a default-sized `BufReader` with room to spare.
+const FILTER_HISTORY_LEN: usize = 64 * 1024;
+
+/// Passes through only the pages of one logical stream of a multiplexed Ogg file, so that a backend which expects the
+/// stream it decodes to come first (as both `lewton` and `OggFlacReader` do) can decode a later one.
+///
+/// Seeking is limited to rewinding to the start and to moving within the last `FILTER_HISTORY_LEN` bytes.
+pub struct StreamFilter<R: Read + Seek> {
+    inner: R,
+    stream_serial: u32,
+    /// The most recently passed-through bytes, which end with the page being read.
+    window: Vec<u8>,
+    cursor: usize,
+    /// The position of `cursor` in the filtered stream.
+    position: u64,
+}
+
+impl<R: Read + Seek> StreamFilter<R> {
+    #[inline]
+    pub fn new(inner: R, stream_serial: u32) -> Self {
+        Self {
+            inner,
+            stream_serial,
+            window: vec![],
+            cursor: 0,
+            position: 0,
+        }
+    }
+
+    /// Appends the next page of the chosen stream to the window. Returns `false` at the end of the file.
+    fn read_page(&mut self) -> io::Result<bool> {
+        loop {
+            let mut page_header = [0; 27];
+            let n = read_full(&mut self.inner, &mut page_header)?;
+            if n == 0 {
+                return Ok(false)
+            }
+            if n < page_header.len() || &page_header[0..4] != b"OggS" {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "lost sync between Ogg pages"))
+            }
+            let mut segment_table = vec![0; page_header[26] as usize];
+            self.inner.read_exact(&mut segment_table)?;
+            let payload_len: usize = segment_table.iter().map(|&len| len as usize).sum();
+            let stream_serial = u32::from_le_bytes([page_header[14], page_header[15], page_header[16], page_header[17]]);
+            if stream_serial != self.stream_serial {
+                self.inner.seek(SeekFrom::Current(payload_len as i64))?;
+                continue
+            }
+
+            if self.cursor > FILTER_HISTORY_LEN {
+                let drop = self.cursor - FILTER_HISTORY_LEN;
+                self.window.drain(..drop);
+                self.cursor -= drop;
+            }
+            self.window.extend_from_slice(&page_header);
+            self.window.extend_from_slice(&segment_table);
+            let payload_start = self.window.len();
+            self.window.resize(payload_start + payload_len, 0);
+            self.inner.read_exact(&mut self.window[payload_start..])?;
+            return Ok(true)
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for StreamFilter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.cursor >= self.window.len() && !self.read_page()? {
+            return Ok(0)
+        }
+        let n = (self.window.len() - self.cursor).min(buf.len());
+        buf[..n].copy_from_slice(&self.window[self.cursor..self.cursor + n]);
+        self.cursor += n;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for StreamFilter<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Start(0) => {
+                self.inner.seek(SeekFrom::Start(0))?;
+                self.window.clear();
+                self.cursor = 0;
+                self.position = 0;
+            },
+            SeekFrom::Current(offset) if (-(self.cursor as i64)..=(self.window.len() - self.cursor) as i64).contains(&offset) => {
+                self.cursor = (self.cursor as i64 + offset) as usize;
+                self.position = (self.position as i64 + offset) as u64;
+            },
+            _ => return Err(io::Error::new(io::ErrorKind::Unsupported, "seeking a multiplexed Ogg stream is not supported")),
+        }
+        Ok(self.position)
+    }
+}
+
+#[inline]
+fn codec_from_magic(magic: &[u8]) -> OggCodec {
+    if magic.starts_with(b"\x01vorbis") {
+        OggCodec::Vorbis
+    } else if magic.starts_with(b"\x7fFLAC") {
+        OggCodec::Flac
+    } else if magic.starts_with(b"OpusHead") {
+        OggCodec::Opus
+    } else if magic.starts_with(b"Speex   ") {
+        OggCodec::Speex
+    } else if magic.starts_with(b"\x80theora") {
+        OggCodec::Theora
+    } else {
+        OggCodec::Unknown
+    }
+}
+
+#[inline]
+fn read_bytes<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), DecoderError> {
+    reader.read_exact(buf).map_err(|err| match err.kind() {
+        std::io::ErrorKind::UnexpectedEof => DecoderError::format_error(AudioFormat::Vorbis, "unexpected end of first page"),
+        _ => DecoderError::IOError(err),
+    })
+}
+
+/// Reassembles a FLAC-in-Ogg stream into a native FLAC byte stream that `claxon` can decode.
+#[cfg(feature = "flac")]
+pub struct OggFlacReader {
+    packets: ogg::PacketReader<SourceReader>,
+    stream_serial: u32,
+    buffer: Vec<u8>,
+    cursor: usize,
+}
+
+#[cfg(feature = "flac")]
+impl OggFlacReader {
+    pub fn new(source: SourceReader) -> Result<Self, DecoderError> {
+        let mut packets = ogg::PacketReader::new(source);
+
+        // The first packet holds the mapping header followed by the native FLAC signature and STREAMINFO block
+        let first = packets.read_packet_expected().map_err(ogg_err_to_decoder_err)?;
+        const MAPPING_HEADER_LEN: usize = 9;
+        if first.data.len() < MAPPING_HEADER_LEN + 4 || &first.data[..5] != b"\x7fFLAC" || &first.data[MAPPING_HEADER_LEN..MAPPING_HEADER_LEN + 4] != b"fLaC" {
+            return Err(DecoderError::format_error(AudioFormat::Flac, "invalid Ogg FLAC mapping header"))
+        }
+        if first.data[5] != 1 {
+            return Err(DecoderError::format_error(AudioFormat::Flac, format!("unsupported Ogg FLAC mapping version {}.{}", first.data[5], first.data[6])))
+        }
+        let stream_serial = first.stream_serial();
+
+        // Collect the remaining metadata blocks, which each occupy one packet before the first audio frame
+        let mut metadata_blocks = vec![first.data[MAPPING_HEADER_LEN + 4..].to_vec()];
+        let mut first_frame = None;
+        while let Some(packet) = packets.read_packet().map_err(ogg_err_to_decoder_err)? {
+            if packet.stream_serial() != stream_serial {
+                continue
+            }
+            if packet.data.first() == Some(&0xff) {
+                first_frame = Some(packet.data);
+                break
+            }
+            metadata_blocks.push(packet.data);
+        }
+
+        // Only the final metadata block may carry the "last block" flag, or claxon would stop reading metadata early
+        let mut buffer = b"fLaC".to_vec();
+        let last_index = metadata_blocks.len() - 1;
+        for (i, mut block) in metadata_blocks.into_iter().enumerate() {
+            if let Some(flags) = block.first_mut() {
+                *flags = if i == last_index { *flags | 0x80 } else { *flags & 0x7f };
+            }
+            buffer.extend_from_slice(&block);
+        }
+        if let Some(frame) = first_frame {
+            buffer.extend_from_slice(&frame);
+        }
+
+        Ok(Self {
+            packets,
+            stream_serial,
+            buffer,
+            cursor: 0,
+        })
+    }
+}
+
+#[cfg(feature = "flac")]
+impl Read for OggFlacReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.cursor >= self.buffer.len() {
+            match self.packets.read_packet() {
+                Ok(Some(packet)) if packet.stream_serial() == self.stream_serial => {
+                    self.buffer = packet.data;
+                    self.cursor = 0;
+                },
+                Ok(Some(_)) => continue,
+                Ok(None) => return Ok(0),
+                Err(ogg::OggReadError::ReadError(err)) => return Err(err),
+                Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+            }
+        }
+
+        let n = (self.buffer.len() - self.cursor).min(buf.len());
+        buf[..n].copy_from_slice(&self.buffer[self.cursor..self.cursor + n]);
+        self.cursor += n;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "flac")]
+pub(super) fn ogg_err_to_decoder_err(error: ogg::OggReadError) -> DecoderError {
+    match error {
+        ogg::OggReadError::ReadError(ioerr) => DecoderError::IOError(ioerr),
+        other => DecoderError::format_error(AudioFormat::Flac, format!("Ogg container error: {}", describe_ogg_error(&other))),
+    }
+}
+
+/// Describes an error from the Ogg container, including the checksums of a page that failed its CRC check.
+#[cfg(any(feature = "flac", feature = "vorbis"))]
+pub(super) fn describe_ogg_error(error: &ogg::OggReadError) -> String {
+    match error {
+        ogg::OggReadError::HashMismatch(stored, calculated) => format!("page CRC mismatch (stored {:#010x}, calculated {:#010x})", stored, calculated),
+        other => other.to_string(),
+    }
+}
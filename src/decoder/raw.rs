@@ -1,16 +1,45 @@
 
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{self, Read, Seek, SeekFrom};
 
-use crate::{AudioFormat, AudioInfo, DecoderError, Endian, RawAudioSpec, RawSampleFormat};
+use crate::pool::{give_buffer, take_buffer};
+use crate::{AudioFormat, AudioInfo, ChannelLayout, DecodeBufferPool, DecoderError, Endian, RawAudioSpec, RawSampleFormat, Sample, SourceReader};
 
 pub struct RawDecoder<R: Read + Seek> {
     reader: R,
     spec: RawAudioSpec,
     info: AudioInfo,
+    /// The frame that decoding starts from.
+    start_frame: u64,
+    /// The number of frames in each channel's plane, for planar audio.
+    plane_frames: u64,
+    /// The pool that interleaved audio draws its buffers from, if any.
+    buffer_pool: Option<DecodeBufferPool>,
 }
 
 impl<R: Read + Seek> RawDecoder<R> {
     pub fn new(mut reader: R, spec: RawAudioSpec) -> Result<Self, DecoderError> {
+        if spec.channels == 0 || spec.sample_rate == 0 {
+            return Err(DecoderError::InvalidArgument("raw audio spec must have at least one channel and a nonzero sample rate".to_owned()))
+        }
+        // Measure the stream to find out how many whole frames it holds. Frames of packed formats can end partway
+        // through a byte, so count in bits
+        let stream_len = reader.seek(SeekFrom::End(0))?;
+        let data_bits = stream_len.saturating_sub(spec.start_offset as u64) * 8;
+        let bits = spec.sample_format.bits_per_sample() as u64;
+        let channels = spec.channels.max(1) as u64;
+        let (total_frames, plane_frames) = match spec.layout {
+            ChannelLayout::Interleaved => {
+                let total_frames = data_bits / (bits * channels);
+                (spec.max_frames.map_or(total_frames, |max_frames| total_frames.min(max_frames as u64)), 0)
+            },
+            ChannelLayout::Planar => {
+                // Each plane starts on a byte boundary, so work out where the last one starts to see how much of it is there
+                let plane_frames = spec.max_frames.map_or(data_bits / 8 / channels * 8 / bits, |max_frames| max_frames as u64);
+                let last_plane_start = (plane_frames * bits).div_ceil(8) * (channels - 1) * 8;
+                (plane_frames.min(data_bits.saturating_sub(last_plane_start) / bits), plane_frames)
+            },
+        };
+
         // Attempt to seek to requested starting position
         if let Err(err) = reader.seek(SeekFrom::Start(spec.start_offset as _)) {
             return Err(DecoderError::IOError(err))
@@ -20,16 +49,57 @@ impl<R: Read + Seek> RawDecoder<R> {
             channels: spec.channels,
             format: AudioFormat::Raw,
             sample_rate: spec.sample_rate,
+            total_frames: Some(total_frames),
         };
 
         Ok(Self {
             reader,
             spec,
-            info
+            info,
+            start_frame: 0,
+            plane_frames,
+            buffer_pool: None,
         })
     }
 }
 
+/// Narrows `spec` to the frames stored between the byte offsets `byte_start` and `byte_end`.
+///
+/// Both offsets must fall on frame boundaries that are also byte boundaries, counted from `spec.start_offset`.
+/// The resulting spec still respects `spec.max_frames`.
+pub fn range_spec(spec: &RawAudioSpec, byte_start: u64, byte_end: u64) -> Result<RawAudioSpec, DecoderError> {
+    if spec.layout == ChannelLayout::Planar {
+        return Err(DecoderError::InvalidArgument("byte ranges can't be decoded from planar audio".to_owned()))
+    }
+    let start_offset = spec.start_offset as u64;
+    if byte_start < start_offset || byte_end < byte_start {
+        return Err(DecoderError::InvalidArgument(format!(
+            "byte range {}..{} must start at or after the start offset {} and can't end before it starts", byte_start, byte_end, start_offset
+        )))
+    }
+
+    // Packed frames can end partway through a byte, so only every few frames lines up with a byte boundary
+    let frame_bits = (spec.sample_format.bits_per_sample() * spec.channels.max(1)) as u64;
+    let unit_frames = (1..=8).find(|frames| (frames * frame_bits).is_multiple_of(8)).unwrap_or(8);
+    let unit_bytes = unit_frames * frame_bits / 8;
+    if !(byte_start - start_offset).is_multiple_of(unit_bytes) || !(byte_end - start_offset).is_multiple_of(unit_bytes) {
+        return Err(DecoderError::InvalidArgument(format!(
+            "byte range {}..{} isn't aligned to frame boundaries, which fall every {} bytes from offset {}", byte_start, byte_end, unit_bytes, start_offset
+        )))
+    }
+
+    let skipped_frames = (byte_start - start_offset) / unit_bytes * unit_frames;
+    let mut frames = (byte_end - byte_start) / unit_bytes * unit_frames;
+    if let Some(max_frames) = spec.max_frames {
+        frames = frames.min((max_frames as u64).saturating_sub(skipped_frames));
+    }
+    Ok(RawAudioSpec {
+        start_offset: byte_start as usize,
+        max_frames: Some(frames as usize),
+        ..spec.clone()
+    })
+}
+
 impl<R: Read + Seek> RawDecoder<R> {
 
     #[inline]
@@ -38,143 +108,477 @@ impl<R: Read + Seek> RawDecoder<R> {
     }
 
     #[inline]
-    pub fn into_samples<'a>(self) -> Result<Box<dyn 'a + Iterator<Item = Result<crate::Sample, DecoderError>>>, DecoderError>
-    where R: 'a
+    pub fn spec(&self) -> &RawAudioSpec {
+        &self.spec
+    }
+
+    /// Gets the frame that decoding starts from.
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.start_frame
+    }
+
+    /// Sets the pool to draw buffers from when decoding interleaved audio.
+    #[inline]
+    pub fn set_buffer_pool(&mut self, buffer_pool: Option<DecodeBufferPool>) {
+        self.buffer_pool = buffer_pool;
+    }
+
+    /// Moves the start of decoding to the specified frame, clamped to the end of the stream.
+    ///
+    /// Packed samples can start partway through a byte, so this seeks to the start of the group of samples containing
+    /// the frame, and `into_samples()` skips the samples before it.
+    pub fn seek(&mut self, frame: u64) -> Result<(), DecoderError> {
+        let frame = frame.min(self.info.total_frames.unwrap_or(0));
+        // Planar audio is read from each plane in turn, so the iterator does its own seeking
+        if self.spec.layout == ChannelLayout::Planar {
+            self.start_frame = frame;
+            return Ok(())
+        }
+        let (group_samples, group_bytes) = self.spec.sample_format.pack_group();
+        let group = frame * self.spec.channels.max(1) as u64 / group_samples as u64;
+        self.reader.seek(SeekFrom::Start(self.spec.start_offset as u64 + group * group_bytes as u64))?;
+        self.start_frame = frame;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn into_samples<'a>(self) -> Result<Box<dyn 'a + Iterator<Item = Result<crate::Sample, DecoderError>> + Send>, DecoderError>
+    where R: 'a + Send
     {
-        let endian = self.spec.endianness;
-
-        macro_rules! sample_iterator {
-            ($func:expr) => {
-                Box::new(RawSampleIterator {
-                    reader: self.reader,
-                    read_func: $func,
-                })
-            };
-            (unsigned $sample_type:ty) => {
-                Box::new(RawSampleIterator {
-                    reader: self.reader,
-                    read_func: move |reader: &mut R| {
-                        const SIZE_BYTES: usize = std::mem::size_of::<$sample_type>();
-                        const MAX_VAL: f32 = <$sample_type>::MAX as f32; //(1 << $sample_bits) + ((1 << $sample_bits) - 1);
-                        let mut buf = [0; SIZE_BYTES];
-                        match reader.read(&mut buf) {
-                            Ok(0) => None,
-                            Ok(SIZE_BYTES) => Some(Ok(match endian {
-                                Endian::Big => <$sample_type>::from_be_bytes(buf) as f32 / MAX_VAL * 2.0 - 1.0,
-                                Endian::Little => <$sample_type>::from_le_bytes(buf) as f32 / MAX_VAL * 2.0 - 1.0,
-                            })),
-                            Ok(_) => Some(Err(DecoderError::IncompleteData)),
-                            Err(err) => Some(Err(DecoderError::IOError(err))),
-                        }
-                    }
-                })
-            };
-            (signed $sample_type:ty) => {
-                Box::new(RawSampleIterator {
-                    reader: self.reader,
-                    read_func: move |reader: &mut R| {
-                        const SIZE_BYTES: usize = std::mem::size_of::<$sample_type>();
-                        const MAX_VAL: f32 = <$sample_type>::MAX as f32; //(1 << $sample_bits) + ((1 << $sample_bits) - 1);
-                        let mut buf = [0; SIZE_BYTES];
-                        match reader.read(&mut buf) {
-                            Ok(0) => None,
-                            Ok(SIZE_BYTES) => Some(Ok(match endian {
-                                Endian::Big => <$sample_type>::from_be_bytes(buf) as f32 / MAX_VAL,
-                                Endian::Little => <$sample_type>::from_le_bytes(buf) as f32 / MAX_VAL,
-                            })),
-                            Ok(_) => Some(Err(DecoderError::IncompleteData)),
-                            Err(err) => Some(Err(DecoderError::IOError(err))),
-                        }
-                    }
-                })
-            };
-        }
-        
-        Ok(match self.spec.sample_format {
-            RawSampleFormat::Float32 => sample_iterator!(move |reader: &mut R| {
-                const SIZEOF_FLOAT: usize = 4;
-                let mut buf = [0; SIZEOF_FLOAT];
-                match reader.read(&mut buf) {
-                    Ok(0) => None,
-                    Ok(SIZEOF_FLOAT) => Some(Ok(match endian {
-                        Endian::Big => f32::from_be_bytes(buf),
-                        Endian::Little => f32::from_le_bytes(buf),
-                    })),
-                    Ok(_) => Some(Err(DecoderError::IncompleteData)),
-                    Err(err) => Some(Err(DecoderError::IOError(err))),
-                }
-            }),
-            RawSampleFormat::Float64 => sample_iterator!(move |reader: &mut R| {
-                const SIZEOF_DOUBLE: usize = 8;
-                let mut buf = [0; SIZEOF_DOUBLE];
-                match reader.read(&mut buf) {
-                    Ok(0) => None,
-                    Ok(SIZEOF_DOUBLE) => Some(Ok(match endian {
-                        Endian::Big => f64::from_be_bytes(buf) as f32,
-                        Endian::Little => f64::from_le_bytes(buf) as f32,
-                    })),
-                    Ok(_) => Some(Err(DecoderError::IncompleteData)),
-                    Err(err) => Some(Err(DecoderError::IOError(err))),
-                }
-            }),
-            RawSampleFormat::Unsigned24 => sample_iterator!(move |reader: &mut R| {
-                const MAX_U24: usize = (1 << 24) + ((1 << 24) - 1);
-                const SIZE: usize = 3;
-                const BUFFER_SIZE: usize = 5;
-                let mut buf = [0; BUFFER_SIZE];
-                match reader.read(&mut buf[1..SIZE]) {
-                    Ok(0) => None,
-                    Ok(SIZE) => Some(Ok(match endian {
-                        // I am so sorry.
-                        Endian::Big => u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as f32 / MAX_U24 as f32 * 2.0 - 1.0,
-                        Endian::Little => u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as f32 / MAX_U24 as f32 * 2.0 - 1.0,
-                    })),
-                    Ok(_) => Some(Err(DecoderError::IncompleteData)),
-                    Err(err) => Some(Err(DecoderError::IOError(err))),
-                }
-            }),
-            RawSampleFormat::Signed24 => sample_iterator!(move |reader: &mut R| {
-                const MAX_I24: usize = (1 << 23) + ((1 << 23) - 1);
-                const SIZE: usize = 3;
-                const BUFFER_SIZE: usize = 5;
-                let mut buf = [0; BUFFER_SIZE];
-                match reader.read(&mut buf[1..SIZE]) {
-                    Ok(0) => None,
-                    Ok(SIZE) => Some(Ok(match endian {                        
-                        Endian::Big => i32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as f32 / MAX_I24 as f32,
-                        Endian::Little => i32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as f32 / MAX_I24 as f32,
-                    })),
-                    Ok(_) => Some(Err(DecoderError::IncompleteData)),
-                    Err(err) => Some(Err(DecoderError::IOError(err))),
-                }
-            }),
-            RawSampleFormat::Unsigned8 => sample_iterator!(unsigned u8),
-            RawSampleFormat::Signed8 => sample_iterator!(signed i8),
-            RawSampleFormat::Unsigned16 => sample_iterator!(unsigned u16),
-            RawSampleFormat::Signed16 => sample_iterator!(signed i16),
-            RawSampleFormat::Unsigned32 => sample_iterator!(unsigned u32),
-            RawSampleFormat::Signed32 => sample_iterator!(signed i32),
-            RawSampleFormat::Unsigned64 => sample_iterator!(unsigned u64),
-            RawSampleFormat::Signed64 => sample_iterator!(signed i64),
-        })
+        Ok(Box::new(self.into_layout_samples()))
+    }
+
+    /// Returns an iterator over the samples, which reads them in whichever way the layout calls for.
+    pub fn into_layout_samples(self) -> LayoutSamples<R> {
+        if self.spec.layout == ChannelLayout::Planar {
+            let end_frame = self.info.total_frames.unwrap_or(0);
+            return LayoutSamples::Planar(PlanarSampleIterator::new(self.reader, &self.spec, self.plane_frames, self.start_frame, end_frame))
+        }
+        let start_frame = self.start_frame as usize;
+        let skip = start_frame * self.spec.channels % self.spec.sample_format.pack_group().0;
+        let remaining = self.spec.max_frames.map(|frames| frames.saturating_sub(start_frame) * self.spec.channels + skip);
+        let mut samples = RawSampleIterator::new(self.reader, self.spec.sample_format, self.spec.endianness, remaining, self.buffer_pool);
+        samples.skip = skip;
+        LayoutSamples::Interleaved(samples)
+    }
+}
+
+/// The samples of raw audio, read according to its channel layout.
+pub enum LayoutSamples<R: Read + Seek> {
+    Interleaved(RawSampleIterator<R>),
+    Planar(PlanarSampleIterator<R>),
+}
+
+impl<R: Read + Seek> Iterator for LayoutSamples<R> {
+    type Item = Result<Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            LayoutSamples::Interleaved(samples) => samples.next(),
+            LayoutSamples::Planar(samples) => samples.next(),
+        }
+    }
+}
+
+/// The samples of a file opened with `Decoder::open_raw()`, as an iterator of a concrete type.
+///
+/// See `Decoder::into_raw_samples()`.
+pub struct RawSamples {
+    samples: LayoutSamples<SourceReader>,
+}
+
+impl RawSamples {
+    #[inline]
+    pub(crate) fn new(samples: LayoutSamples<SourceReader>) -> Self {
+        Self { samples }
     }
 }
 
-struct RawSampleIterator<R: Read + Seek, F: Fn(&mut R) -> Option<Result<crate::Sample, DecoderError>>> {
+impl Iterator for RawSamples {
+    type Item = Result<Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.samples.next()
+    }
+}
+
+/// Converts a single raw sample to a `Sample`.
+///
+/// `bytes` must hold at least `format.size_bytes()` bytes; any bytes after that are ignored.
+/// Integer formats are normalized to the range [-1.0, 1.0], and floating-point formats are passed through as-is.
+/// For packed formats, this decodes the sample that starts at the first bit of `bytes`.
+///
+/// This function only depends on `core`, so it can be used to convert samples from in-memory buffers
+/// on targets without file system support.
+///
+/// # Panics
+///
+/// Panics if `bytes` is shorter than `format.size_bytes()`.
+#[inline]
+pub fn decode_raw_sample(format: RawSampleFormat, endian: Endian, bytes: &[u8]) -> Sample {
+    macro_rules! read_bytes {
+        ($size:literal) => {{
+            let mut buf = [0; $size];
+            buf.copy_from_slice(&bytes[..$size]);
+            buf
+        }}
+    }
+
+    macro_rules! from_bytes {
+        ($sample_type:ty, $size:literal) => {
+            match endian {
+                Endian::Big => <$sample_type>::from_be_bytes(read_bytes!($size)),
+                Endian::Little => <$sample_type>::from_le_bytes(read_bytes!($size)),
+            }
+        }
+    }
+
+    macro_rules! unsigned {
+        ($sample_type:ty, $size:literal) => {
+            (from_bytes!($sample_type, $size) as f64 / <$sample_type>::MAX as f64 * 2.0 - 1.0) as Sample
+        }
+    }
+
+    macro_rules! signed {
+        ($sample_type:ty, $size:literal) => {
+            (from_bytes!($sample_type, $size) as f64 / <$sample_type>::MAX as f64) as Sample
+        }
+    }
+
+    // 24-bit samples are widened to 32 bits, with the sample in the upper three bytes
+    let u24_bits = || {
+        let buf = read_bytes!(3);
+        match endian {
+            Endian::Big => u32::from_be_bytes([buf[0], buf[1], buf[2], 0]),
+            Endian::Little => u32::from_le_bytes([0, buf[0], buf[1], buf[2]]),
+        }
+    };
+
+    match format {
+        RawSampleFormat::Float32 => from_bytes!(f32, 4),
+        RawSampleFormat::Float64 => from_bytes!(f64, 8) as Sample,
+        RawSampleFormat::Unsigned8 => unsigned!(u8, 1),
+        RawSampleFormat::Signed8 => signed!(i8, 1),
+        RawSampleFormat::Unsigned16 => unsigned!(u16, 2),
+        RawSampleFormat::Signed16 => signed!(i16, 2),
+        RawSampleFormat::Unsigned12 | RawSampleFormat::Signed12 | RawSampleFormat::Unsigned20 | RawSampleFormat::Signed20 => {
+            decode_packed_sample(format, endian, &bytes[..format.size_bytes()], 0)
+        },
+        RawSampleFormat::Unsigned24 => {
+            const MAX_U24: f64 = 0xffffff as f64;
+            ((u24_bits() >> 8) as f64 / MAX_U24 * 2.0 - 1.0) as Sample
+        },
+        RawSampleFormat::Signed24 => {
+            const MAX_I24: f64 = 0x7fffff as f64;
+            // The arithmetic shift sign-extends the sample
+            (((u24_bits() as i32) >> 8) as f64 / MAX_I24) as Sample
+        },
+        RawSampleFormat::Unsigned32 => unsigned!(u32, 4),
+        RawSampleFormat::Signed32 => signed!(i32, 4),
+        RawSampleFormat::Unsigned64 => unsigned!(u64, 8),
+        RawSampleFormat::Signed64 => signed!(i64, 8),
+        RawSampleFormat::ALaw => expand_alaw(bytes[0]) as Sample / i16::MAX as Sample,
+        RawSampleFormat::MuLaw => expand_mulaw(bytes[0]) as Sample / i16::MAX as Sample,
+    }
+}
+
+/// Decodes sample `index` of a group of packed samples.
+///
+/// `group` may be shorter than a full group at the end of a stream, as long as it holds the sample.
+#[inline]
+fn decode_packed_sample(format: RawSampleFormat, endian: Endian, group: &[u8], index: usize) -> Sample {
+    let bits = format.bits_per_sample();
+    let (packed, shift) = match endian {
+        Endian::Little => (group.iter().rev().fold(0u64, |acc, &byte| acc << 8 | byte as u64), index * bits),
+        Endian::Big => (group.iter().fold(0u64, |acc, &byte| acc << 8 | byte as u64), group.len() * 8 - (index + 1) * bits),
+    };
+    let value = (packed >> shift) as u32 & ((1 << bits) - 1);
+    match format {
+        RawSampleFormat::Unsigned12 | RawSampleFormat::Unsigned20 => (value as f64 / ((1u32 << bits) - 1) as f64 * 2.0 - 1.0) as Sample,
+        // Shift the sign bit to the top so the arithmetic shift back down sign-extends the sample
+        _ => ((((value << (32 - bits)) as i32) >> (32 - bits)) as f64 / ((1u32 << (bits - 1)) - 1) as f64) as Sample,
+    }
+}
+
+/// Expands a G.711 A-law byte to a 16-bit linear sample.
+#[inline]
+fn expand_alaw(byte: u8) -> i16 {
+    let byte = byte ^ 0x55;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = (byte & 0x0f) as i16;
+    let magnitude = match exponent {
+        0 => (mantissa << 4) + 0x08,
+        _ => ((mantissa << 4) + 0x108) << (exponent - 1),
+    };
+    // Unlike most encodings, a set sign bit means the sample is positive
+    if byte & 0x80 != 0 { magnitude } else { -magnitude }
+}
+
+/// Expands a G.711 mu-law byte to a 16-bit linear sample.
+#[inline]
+fn expand_mulaw(byte: u8) -> i16 {
+    let byte = !byte;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = (byte & 0x0f) as i16;
+    let magnitude = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+    if byte & 0x80 != 0 { -magnitude } else { magnitude }
+}
+
+/// Number of samples read from the stream at once.
+const READ_BLOCK_SAMPLES: usize = 4096;
+
+pub struct RawSampleIterator<R: Read> {
     reader: R,
-    read_func: F,
+    format: RawSampleFormat,
+    endian: Endian,
+    /// Number of samples left to read, if limited by `max_frames`.
+    remaining: Option<usize>,
+    bytes: Vec<u8>,
+    /// Samples converted from the last block read.
+    samples: Vec<Sample>,
+    cursor: usize,
+    /// Number of samples to discard from the start of the stream, when decoding begins partway through a packed group.
+    skip: usize,
+    /// Whether the stream ended partway through a sample.
+    incomplete: bool,
+    finished: bool,
+    /// The pool the buffers came from, which they're returned to when the iterator is dropped.
+    buffer_pool: Option<DecodeBufferPool>,
 }
 
-impl<R: Read + Seek, F: Fn(&mut R) -> Option<Result<crate::Sample, DecoderError>>> Iterator for RawSampleIterator<R, F> {
+impl<R: Read> RawSampleIterator<R> {
+    pub fn new(reader: R, format: RawSampleFormat, endian: Endian, remaining: Option<usize>, buffer_pool: Option<DecodeBufferPool>) -> Self {
+        let block_len = READ_BLOCK_SAMPLES * format.bits_per_sample() / 8;
+        let mut bytes = take_buffer(buffer_pool.as_ref(), block_len);
+        bytes.resize(block_len, 0);
+        Self {
+            reader,
+            format,
+            endian,
+            remaining,
+            bytes,
+            samples: take_buffer(buffer_pool.as_ref(), READ_BLOCK_SAMPLES),
+            cursor: 0,
+            skip: 0,
+            incomplete: false,
+            finished: false,
+            buffer_pool,
+        }
+    }
+
+    /// Reads and converts the next block of samples.
+    ///
+    /// Every block except the last holds a whole number of packed groups, so blocks always start on a byte boundary.
+    fn read_block(&mut self) -> Result<(), DecoderError> {
+        let bits = self.format.bits_per_sample();
+        let block_samples = self.remaining.map_or(READ_BLOCK_SAMPLES, |remaining| remaining.min(READ_BLOCK_SAMPLES));
+        let n = read_full(&mut self.reader, &mut self.bytes[..(block_samples * bits).div_ceil(8)])?;
+        let count = (n * 8 / bits).min(block_samples);
+        // Fewer than 8 leftover bits is just the padding that fills out the last byte of a packed stream
+        self.incomplete = n * 8 - count * bits >= 8;
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= count;
+        }
+
+        self.samples.clear();
+        self.cursor = 0;
+        decode_block(self.format, self.endian, &self.bytes[..n], count, &mut self.samples);
+
+        let skipped = self.skip.min(self.samples.len());
+        self.cursor = skipped;
+        self.skip -= skipped;
+        Ok(())
+    }
+}
+
+/// Decodes the first `count` samples in `bytes`, appending them to `samples`.
+///
+/// `bytes` must start on a packed group boundary.
+#[inline]
+fn decode_block(format: RawSampleFormat, endian: Endian, bytes: &[u8], count: usize, samples: &mut Vec<Sample>) {
+    match (format, endian) {
+        // 16-bit little-endian is by far the most common format, so give it a dedicated loop the compiler can vectorize
+        (RawSampleFormat::Signed16, Endian::Little) => {
+            samples.extend(bytes[..count * 2].chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as Sample / i16::MAX as Sample));
+        },
+        (format, endian) if !format.bits_per_sample().is_multiple_of(8) => {
+            let (group_samples, group_bytes) = format.pack_group();
+            let decoded = bytes.chunks(group_bytes)
+                .flat_map(|group| (0..group_samples).map(move |index| (group, index)))
+                .take(count)
+                .map(|(group, index)| decode_packed_sample(format, endian, group, index));
+            samples.extend(decoded);
+        },
+        (format, endian) => {
+            let size = format.size_bytes();
+            samples.extend(bytes[..count * size].chunks_exact(size).map(|b| decode_raw_sample(format, endian, b)));
+        },
+    }
+}
+
+impl<R: Read> Drop for RawSampleIterator<R> {
+    fn drop(&mut self) {
+        give_buffer(self.buffer_pool.as_ref(), std::mem::take(&mut self.bytes));
+        give_buffer(self.buffer_pool.as_ref(), std::mem::take(&mut self.samples));
+    }
+}
+
+impl<R: Read> Iterator for RawSampleIterator<R> {
     type Item = Result<crate::Sample, DecoderError>;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        (self.read_func)(&mut self.reader)
+        if let Some(sample) = self.samples.get(self.cursor) {
+            self.cursor += 1;
+            return Some(Ok(*sample))
+        }
+
+        if self.finished {
+            return None
+        }
+        if self.incomplete {
+            self.finished = true;
+            return Some(Err(DecoderError::IncompleteData))
+        }
+        if self.remaining == Some(0) {
+            self.finished = true;
+            return None
+        }
+        if let Err(err) = self.read_block() {
+            self.finished = true;
+            return Some(Err(err))
+        }
+        if self.samples.is_empty() && !self.incomplete {
+            self.finished = true;
+        }
+        self.next()
+    }
+}
+
+/// Number of frames read from each plane at once when decoding planar audio.
+const PLANAR_BLOCK_FRAMES: usize = 1024;
+
+/// Decodes planar audio by reading a block from each channel's plane in turn, then interleaving them.
+pub struct PlanarSampleIterator<R: Read + Seek> {
+    reader: R,
+    format: RawSampleFormat,
+    endian: Endian,
+    channels: usize,
+    /// Byte offset of the start of each channel's plane.
+    plane_starts: Vec<u64>,
+    /// The next frame to read from the planes, which is always the start of a packed group.
+    frame: u64,
+    /// The frame after the last one to read.
+    end_frame: u64,
+    /// Number of frames to discard from the first block, when decoding begins partway through a packed group.
+    skip_frames: usize,
+    bytes: Vec<u8>,
+    /// The samples of each plane in the current block.
+    planes: Vec<Vec<Sample>>,
+    /// The current block, interleaved.
+    samples: Vec<Sample>,
+    cursor: usize,
+    /// Whether a plane ended before the end of the audio.
+    incomplete: bool,
+    finished: bool,
+}
+
+impl<R: Read + Seek> PlanarSampleIterator<R> {
+    fn new(reader: R, spec: &RawAudioSpec, plane_frames: u64, start_frame: u64, end_frame: u64) -> Self {
+        let format = spec.sample_format;
+        let plane_len = (plane_frames * format.bits_per_sample() as u64).div_ceil(8);
+        let group_samples = format.pack_group().0 as u64;
+        Self {
+            reader,
+            format,
+            endian: spec.endianness,
+            channels: spec.channels,
+            plane_starts: (0..spec.channels as u64).map(|channel| spec.start_offset as u64 + channel * plane_len).collect(),
+            frame: start_frame - start_frame % group_samples,
+            end_frame,
+            skip_frames: (start_frame % group_samples) as usize,
+            bytes: vec![0; PLANAR_BLOCK_FRAMES * format.bits_per_sample() / 8],
+            planes: vec![Vec::with_capacity(PLANAR_BLOCK_FRAMES); spec.channels],
+            samples: Vec::with_capacity(PLANAR_BLOCK_FRAMES * spec.channels),
+            cursor: 0,
+            incomplete: false,
+            finished: false,
+        }
+    }
+
+    /// Reads the next block of frames from every plane and interleaves them.
+    ///
+    /// If a plane ends early, only the frames that every plane could provide are kept.
+    fn read_block(&mut self) -> Result<(), DecoderError> {
+        let bits = self.format.bits_per_sample();
+        let frames = ((self.end_frame - self.frame) as usize).min(PLANAR_BLOCK_FRAMES);
+        let offset = self.frame * bits as u64 / 8;
+        let mut complete_frames = frames;
+        for (plane, start) in self.planes.iter_mut().zip(&self.plane_starts) {
+            self.reader.seek(SeekFrom::Start(start + offset))?;
+            let n = read_full(&mut self.reader, &mut self.bytes[..(frames * bits).div_ceil(8)])?;
+            let count = (n * 8 / bits).min(frames);
+            complete_frames = complete_frames.min(count);
+            plane.clear();
+            decode_block(self.format, self.endian, &self.bytes[..n], count, plane);
+        }
+        self.frame += frames as u64;
+
+        self.samples.clear();
+        for frame in 0..complete_frames {
+            self.samples.extend(self.planes.iter().map(|plane| plane[frame]));
+        }
+        let skipped = self.skip_frames.min(complete_frames);
+        self.cursor = skipped * self.channels;
+        self.skip_frames -= skipped;
+        self.incomplete = complete_frames < frames;
+        Ok(())
     }
 }
 
+impl<R: Read + Seek> Iterator for PlanarSampleIterator<R> {
+    type Item = Result<crate::Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(sample) = self.samples.get(self.cursor) {
+            self.cursor += 1;
+            return Some(Ok(*sample))
+        }
+
+        if self.finished {
+            return None
+        }
+        if self.incomplete {
+            self.finished = true;
+            return Some(Err(DecoderError::IncompleteData))
+        }
+        if self.frame >= self.end_frame {
+            self.finished = true;
+            return None
+        }
+        if let Err(err) = self.read_block() {
+            self.finished = true;
+            return Some(Err(err))
+        }
+        self.next()
+    }
+}
+
+/// Reads until `buf` is full or the end of the stream is reached, and returns the number of bytes read.
 #[inline]
-fn io_err_to_decoder_err(error: std::io::Error) -> DecoderError {
-    DecoderError::IOError(error)
-}
\ No newline at end of file
+pub(super) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
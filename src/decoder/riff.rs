@@ -0,0 +1,615 @@
+use std::convert::TryInto;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::{AudioFormat, DecodeEvent, DecoderError, EventHandler, WavRateCheck};
+
+pub const WAVE_FORMAT_PCM: u16 = 0x0001;
+pub const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+pub const WAVE_FORMAT_ALAW: u16 = 0x0006;
+pub const WAVE_FORMAT_MULAW: u16 = 0x0007;
+pub const WAVE_FORMAT_IMA_ADPCM: u16 = 0x0011;
+pub const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+/// The length of a `WAVE_FORMAT_EXTENSIBLE` fmt chunk, which holds every field the parser reads.
+const FMT_READ_LEN: u64 = 40;
+
+/// The parts of a WAV header needed to locate and interpret the sample data.
+#[derive(Debug, Clone)]
+pub struct WavHeader {
+    /// The format tag. For `WAVE_FORMAT_EXTENSIBLE` files, this is the tag taken from the sub-format GUID.
+    pub format_tag: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub byte_rate: u32,
+    pub block_align: u16,
+    pub bits_per_sample: u16,
+    /// Byte offset of the first sample in the `data` chunk.
+    pub data_offset: u64,
+    /// Length of the `data` chunk in bytes, as declared by its header. This never includes the pad byte that follows
+    /// an odd-length chunk, so it can be divided into frames directly.
+    pub data_len: u64,
+    /// Number of frames declared by the `fact` chunk, if present.
+    pub fact_frames: Option<u64>,
+    /// The contents of the `bext` chunk, if present. Boxed, since most files don't have one.
+    pub broadcast_info: Option<Box<BroadcastInfo>>,
+    /// The contents of the `inst` chunk, if present.
+    pub instrument_info: Option<InstrumentInfo>,
+    /// Whether the file is an RF64 or BW64 file, whose chunk sizes over 4 GiB are stored in its `ds64` chunk.
+    pub rf64: bool,
+}
+
+/// The 64-bit sizes from the `ds64` chunk of an RF64 or BW64 file, as defined by EBU Tech 3306.
+///
+/// Chunks too large for a 32-bit size give their size as `0xFFFFFFFF`, and the actual size is stored here.
+#[derive(Debug, Clone)]
+struct Ds64 {
+    data_len: u64,
+    sample_count: u64,
+    /// The sizes of any other chunks over 4 GiB, by chunk ID.
+    table: Vec<([u8; 4], u64)>,
+}
+
+impl Ds64 {
+    /// The chunk size that defers to the `ds64` chunk.
+    const DEFERRED_LEN: u64 = 0xffff_ffff;
+
+    fn parse(data: &[u8]) -> Option<Self> {
+        let u64_at = |offset: usize| data.get(offset..offset + 8).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()));
+        let table_len = data.get(24..28).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize);
+        // A table longer than the chunk is cut short rather than rejected, since only the data size is essential
+        let table = data.get(28..).unwrap_or_default()
+            .chunks_exact(12)
+            .take(table_len)
+            .map(|entry| ([entry[0], entry[1], entry[2], entry[3]], u64::from_le_bytes(entry[4..12].try_into().unwrap())))
+            .collect();
+        Some(Self {
+            data_len: u64_at(8)?,
+            sample_count: u64_at(16)?,
+            table,
+        })
+    }
+
+    /// Gets the actual size of a chunk whose header gives `len`, which is only replaced if it defers to this chunk.
+    #[inline]
+    fn chunk_len(&self, id: &[u8; 4], len: u64) -> u64 {
+        match (len, id) {
+            (Self::DEFERRED_LEN, b"data") => self.data_len,
+            (Self::DEFERRED_LEN, _) => self.table.iter().find(|(table_id, _)| table_id == id).map_or(len, |(_, len)| *len),
+            _ => len,
+        }
+    }
+}
+
+impl WavHeader {
+    /// Returns the number of frames in the file.
+    ///
+    /// Non-PCM files declare their length in the `fact` chunk, which is preferred when present;
+    /// otherwise the length is derived from the size of the `data` chunk.
+    #[inline]
+    pub fn total_frames(&self) -> u64 {
+        match self.fact_frames {
+            Some(frames) if self.format_tag != WAVE_FORMAT_PCM => frames,
+            _ if self.format_tag == WAVE_FORMAT_IMA_ADPCM => {
+                let block_align = self.block_align.max(1) as u64;
+                let full_blocks = self.data_len / block_align;
+                full_blocks * self.ima_block_frames(block_align) + self.ima_block_frames(self.data_len % block_align)
+            },
+            _ => self.data_len / self.block_align.max(1) as u64,
+        }
+    }
+
+    /// Cross-checks the sample rate against the byte rate, which should be the sample rate times the block alignment,
+    /// and handles a mismatch as `check` says.
+    ///
+    /// Only formats whose blocks each hold a single frame are checked, and a byte rate of 0 is taken to be unset.
+    pub fn check_sample_rate(&mut self, check: WavRateCheck) -> Result<(), DecoderError> {
+        let block_align = self.block_align as u64;
+        let one_frame_per_block = matches!(self.format_tag, WAVE_FORMAT_PCM | WAVE_FORMAT_IEEE_FLOAT | WAVE_FORMAT_ALAW | WAVE_FORMAT_MULAW);
+        if check == WavRateCheck::Ignore || !one_frame_per_block || block_align == 0 || self.byte_rate == 0
+            || self.sample_rate as u64 * block_align == self.byte_rate as u64 {
+            return Ok(())
+        }
+        let implied = self.byte_rate as u64 / block_align;
+        let exact = (self.byte_rate as u64).is_multiple_of(block_align);
+        match check {
+            WavRateCheck::Repair if exact && implied > 0 => {
+                self.sample_rate = implied as u32;
+                Ok(())
+            },
+            _ => Err(DecoderError::format_error(AudioFormat::Wav, format!(
+                "fmt chunk declares a sample rate of {}Hz, but its byte rate of {} bytes/s with a block alignment of {} bytes {}",
+                self.sample_rate, self.byte_rate, block_align,
+                if exact { format!("implies {}Hz", implied) } else { "isn't a whole number of frames per second".to_owned() }
+            ))),
+        }
+    }
+
+    /// Returns the number of frames in an IMA ADPCM block of `len` bytes.
+    ///
+    /// Each block starts with a 4-byte header per channel that holds the first sample,
+    /// followed by 4-byte chunks of eight samples for each channel in turn.
+    #[inline]
+    pub fn ima_block_frames(&self, len: u64) -> u64 {
+        let header_len = 4 * self.channels.max(1) as u64;
+        if len < header_len { 0 } else { 1 + (len - header_len) / header_len * 8 }
+    }
+}
+
+/// Metadata from the `bext` (Broadcast Extension) chunk of a Broadcast WAV file, as defined by EBU Tech 3285.
+///
+/// Text fields are decoded as UTF-8 (lossily) and have any null padding removed.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BroadcastInfo {
+    description: String,
+    originator: String,
+    originator_reference: String,
+    origination_date: String,
+    origination_time: String,
+    time_reference: u64,
+    version: u16,
+    umid: Option<Vec<u8>>,
+    loudness: [Option<f32>; 5],
+    coding_history: String,
+}
+
+impl BroadcastInfo {
+    /// The size of the fixed-length part of the chunk, which is followed by the coding history.
+    const FIXED_LEN: usize = 602;
+
+    /// Parses the chunk's contents, returning `None` if it's too short to hold the fixed-length fields.
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < Self::FIXED_LEN {
+            return None
+        }
+        let text = |range: std::ops::Range<usize>| {
+            let field = &data[range];
+            let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+            String::from_utf8_lossy(&field[..len]).into_owned()
+        };
+        let u32_at = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+        let version = u16::from_le_bytes([data[346], data[347]]);
+        // Loudness values are stored in hundredths of a LU or dB, with 0x7fff meaning the value wasn't set
+        let mut loudness = [None; 5];
+        if version >= 2 {
+            for (i, value) in loudness.iter_mut().enumerate() {
+                let raw = i16::from_le_bytes([data[412 + i * 2], data[413 + i * 2]]);
+                if raw != i16::MAX {
+                    *value = Some(raw as f32 / 100.0);
+                }
+            }
+        }
+        Some(Self {
+            description: text(0..256),
+            originator: text(256..288),
+            originator_reference: text(288..320),
+            origination_date: text(320..330),
+            origination_time: text(330..338),
+            time_reference: u32_at(338) as u64 | (u32_at(342) as u64) << 32,
+            version,
+            umid: if version >= 1 { Some(data[348..412].to_vec()) } else { None },
+            loudness,
+            coding_history: text(Self::FIXED_LEN..data.len()),
+        })
+    }
+
+    /// Gets the free-text description of the sound sequence.
+    #[inline]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Gets the name of the originator or producer of the audio.
+    #[inline]
+    pub fn originator(&self) -> &str {
+        &self.originator
+    }
+
+    /// Gets the originator's reference, which is meant to uniquely identify the audio.
+    #[inline]
+    pub fn originator_reference(&self) -> &str {
+        &self.originator_reference
+    }
+
+    /// Gets the date the audio was created, as `yyyy-mm-dd`. Some writers use other separators.
+    #[inline]
+    pub fn origination_date(&self) -> &str {
+        &self.origination_date
+    }
+
+    /// Gets the time the audio was created, as `hh:mm:ss`. Some writers use other separators.
+    #[inline]
+    pub fn origination_time(&self) -> &str {
+        &self.origination_time
+    }
+
+    /// Gets the time reference of the first sample, as the number of frames since midnight.
+    #[inline]
+    pub fn time_reference(&self) -> u64 {
+        self.time_reference
+    }
+
+    /// Gets the version of the `bext` chunk.
+    #[inline]
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Gets the 64-byte SMPTE UMID of the audio, if the chunk is version 1 or later.
+    #[inline]
+    pub fn umid(&self) -> Option<&[u8]> {
+        self.umid.as_deref()
+    }
+
+    /// Gets the integrated loudness of the audio in LUFS, if the chunk is version 2 or later and the value was set.
+    #[inline]
+    pub fn loudness_value(&self) -> Option<f32> {
+        self.loudness[0]
+    }
+
+    /// Gets the loudness range of the audio in LU, if the chunk is version 2 or later and the value was set.
+    #[inline]
+    pub fn loudness_range(&self) -> Option<f32> {
+        self.loudness[1]
+    }
+
+    /// Gets the maximum true peak level of the audio in dBTP, if the chunk is version 2 or later and the value was set.
+    #[inline]
+    pub fn max_true_peak_level(&self) -> Option<f32> {
+        self.loudness[2]
+    }
+
+    /// Gets the highest momentary loudness of the audio in LUFS, if the chunk is version 2 or later and the value was set.
+    #[inline]
+    pub fn max_momentary_loudness(&self) -> Option<f32> {
+        self.loudness[3]
+    }
+
+    /// Gets the highest short-term loudness of the audio in LUFS, if the chunk is version 2 or later and the value was set.
+    #[inline]
+    pub fn max_short_term_loudness(&self) -> Option<f32> {
+        self.loudness[4]
+    }
+
+    /// Gets the coding history, which describes each step of the audio's processing as lines of text.
+    #[inline]
+    pub fn coding_history(&self) -> &str {
+        &self.coding_history
+    }
+}
+
+/// Sampler metadata from the `inst` (instrument) chunk of a WAV file.
+///
+/// Notes and velocities are MIDI values, from 0 to 127.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstrumentInfo {
+    root_note: u8,
+    fine_tune: i8,
+    gain: i8,
+    low_note: u8,
+    high_note: u8,
+    low_velocity: u8,
+    high_velocity: u8,
+}
+
+impl InstrumentInfo {
+    /// The size of the chunk's contents. Some writers pad the chunk to 8 bytes.
+    const LEN: usize = 7;
+
+    /// Parses the chunk's contents, returning `None` if it's too short.
+    fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < Self::LEN {
+            return None
+        }
+        Some(Self {
+            root_note: data[0],
+            fine_tune: data[1] as i8,
+            gain: data[2] as i8,
+            low_note: data[3],
+            high_note: data[4],
+            low_velocity: data[5],
+            high_velocity: data[6],
+        })
+    }
+
+    /// Gets the MIDI note that plays the sample at its recorded pitch.
+    #[inline]
+    pub fn root_note(&self) -> u8 {
+        self.root_note
+    }
+
+    /// Gets the amount in cents, from -50 to +50, by which to detune the sample when it's played at its root note.
+    #[inline]
+    pub fn fine_tune(&self) -> i8 {
+        self.fine_tune
+    }
+
+    /// Gets the gain in decibels to apply when playing the sample.
+    #[inline]
+    pub fn gain_db(&self) -> i8 {
+        self.gain
+    }
+
+    /// Gets the lowest MIDI note the sample should be played for.
+    #[inline]
+    pub fn low_note(&self) -> u8 {
+        self.low_note
+    }
+
+    /// Gets the highest MIDI note the sample should be played for.
+    #[inline]
+    pub fn high_note(&self) -> u8 {
+        self.high_note
+    }
+
+    /// Gets the lowest MIDI velocity the sample should be played for.
+    #[inline]
+    pub fn low_velocity(&self) -> u8 {
+        self.low_velocity
+    }
+
+    /// Gets the highest MIDI velocity the sample should be played for.
+    #[inline]
+    pub fn high_velocity(&self) -> u8 {
+        self.high_velocity
+    }
+}
+
+/// Reads the RIFF/WAVE header and walks the chunk list up to the start of the `data` chunk.
+///
+/// The reader must be positioned at the start of the file, and is left positioned at the first byte of sample data.
+/// Chunks are skipped by reading past them, so the reader doesn't need to be seekable.
+pub fn read_wav_header<R: Read>(reader: &mut R, events: Option<&EventHandler>) -> Result<WavHeader, DecoderError> {
+    read_chunks(reader, events, false)
+}
+
+/// Reads the RIFF/WAVE header and walks the chunk list only as far as the end of the `fmt` chunk.
+///
+/// This is for files whose later chunks can't be trusted. The returned header's `data_offset` is the end of the
+/// `fmt` chunk and its `data_len` is 0, so the caller must fill in where the samples actually are.
+pub fn read_wav_format<R: Read>(reader: &mut R) -> Result<WavHeader, DecoderError> {
+    read_chunks(reader, None, true)
+}
+
+fn read_chunks<R: Read>(reader: &mut R, events: Option<&EventHandler>, until_fmt: bool) -> Result<WavHeader, DecoderError> {
+    let mut riff = [0; 12];
+    read_bytes(reader, &mut riff)?;
+    let rf64 = match &riff[0..4] {
+        _ if &riff[8..12] != b"WAVE" => None,
+        b"RIFF" => Some(false),
+        b"RF64" | b"BW64" => Some(true),
+        _ => None,
+    };
+    let rf64 = match rf64 {
+        Some(rf64) => rf64,
+        None => return Err(DecoderError::format_error(AudioFormat::Wav, "no RIFF/WAVE header found")),
+    };
+
+    let mut fmt: Option<[u16; 4]> = None;
+    let mut sample_rate = 0;
+    let mut byte_rate = 0;
+    let mut fact_frames = None;
+    let mut broadcast_info = None;
+    let mut instrument_info = None;
+    let mut ds64: Option<Ds64> = None;
+    let mut offset = riff.len() as u64;
+
+    loop {
+        let mut chunk_header = [0; 8];
+        read_bytes(reader, &mut chunk_header)?;
+        offset += chunk_header.len() as u64;
+        let chunk_id = [chunk_header[0], chunk_header[1], chunk_header[2], chunk_header[3]];
+        let mut chunk_len = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]) as u64;
+        if let Some(ds64) = &ds64 {
+            chunk_len = ds64.chunk_len(&chunk_id, chunk_len);
+        }
+        if let Some(handler) = events {
+            handler.emit(DecodeEvent::ChunkParsed { id: chunk_id, offset, len: chunk_len });
+        }
+
+        match &chunk_id {
+            b"ds64" if rf64 => {
+                let mut buf = vec![];
+                reader.take(chunk_len).read_to_end(&mut buf)?;
+                if (buf.len() as u64) < chunk_len {
+                    return Err(DecoderError::format_error(AudioFormat::Wav, "unexpected end of header"))
+                }
+                match Ds64::parse(&buf) {
+                    Some(parsed) => ds64 = Some(parsed),
+                    None => return Err(DecoderError::format_error(AudioFormat::Wav, "ds64 chunk is too short")),
+                }
+                skip_bytes(reader, chunk_len & 1)?;
+            },
+            b"fmt " => {
+                if chunk_len < 16 {
+                    return Err(DecoderError::format_error(AudioFormat::Wav, "fmt chunk is too short"))
+                }
+                // Nothing past the sub-format GUID's format tag is needed, and reading only that much means a corrupt
+                // length can't force a huge allocation
+                let read_len = chunk_len.min(FMT_READ_LEN);
+                let mut buf = vec![];
+                reader.take(read_len).read_to_end(&mut buf)?;
+                if (buf.len() as u64) < read_len {
+                    return Err(DecoderError::format_error(AudioFormat::Wav, "unexpected end of header"))
+                }
+                let mut format_tag = u16::from_le_bytes([buf[0], buf[1]]);
+                let channels = u16::from_le_bytes([buf[2], buf[3]]);
+                sample_rate = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+                byte_rate = u32::from_le_bytes([buf[8], buf[9], buf[10], buf[11]]);
+                let block_align = u16::from_le_bytes([buf[12], buf[13]]);
+                let bits_per_sample = u16::from_le_bytes([buf[14], buf[15]]);
+                // The first two bytes of the sub-format GUID hold the actual format tag
+                if format_tag == WAVE_FORMAT_EXTENSIBLE && buf.len() >= 26 {
+                    format_tag = u16::from_le_bytes([buf[24], buf[25]]);
+                }
+                fmt = Some([format_tag, channels, block_align, bits_per_sample]);
+                skip_bytes(reader, chunk_len - read_len + (chunk_len & 1))?;
+                if until_fmt {
+                    return Ok(WavHeader {
+                        format_tag,
+                        channels,
+                        sample_rate,
+                        byte_rate,
+                        block_align,
+                        bits_per_sample,
+                        data_offset: offset + chunk_len + (chunk_len & 1),
+                        data_len: 0,
+                        fact_frames,
+                        broadcast_info,
+                        instrument_info,
+                        rf64,
+                    })
+                }
+            },
+            b"fact" => {
+                if chunk_len < 4 {
+                    return Err(DecoderError::format_error(AudioFormat::Wav, "fact chunk is too short"))
+                }
+                // Only the frame count is read, so a corrupt length can't force a huge allocation
+                let mut buf = [0; 4];
+                read_bytes(reader, &mut buf)?;
+                fact_frames = match (u32::from_le_bytes(buf) as u64, &ds64) {
+                    (Ds64::DEFERRED_LEN, Some(ds64)) => Some(ds64.sample_count),
+                    (frames, _) => Some(frames),
+                };
+                skip_bytes(reader, chunk_len - buf.len() as u64 + (chunk_len & 1))?;
+            },
+            b"bext" => {
+                // Read through `take` so a corrupt length can't force a huge allocation up front
+                let mut buf = vec![];
+                reader.take(chunk_len).read_to_end(&mut buf)?;
+                if (buf.len() as u64) < chunk_len {
+                    return Err(DecoderError::format_error(AudioFormat::Wav, "unexpected end of header"))
+                }
+                // The metadata isn't needed for decoding, so a malformed chunk is ignored rather than rejected
+                broadcast_info = BroadcastInfo::parse(&buf).map(Box::new);
+                skip_bytes(reader, chunk_len & 1)?;
+            },
+            b"inst" => {
+                let mut buf = vec![];
+                reader.take(chunk_len).read_to_end(&mut buf)?;
+                if (buf.len() as u64) < chunk_len {
+                    return Err(DecoderError::format_error(AudioFormat::Wav, "unexpected end of header"))
+                }
+                instrument_info = InstrumentInfo::parse(&buf);
+                skip_bytes(reader, chunk_len & 1)?;
+            },
+            b"data" => {
+                let [format_tag, channels, block_align, bits_per_sample] = match fmt {
+                    Some(fmt) => fmt,
+                    None => return Err(DecoderError::format_error(AudioFormat::Wav, "data chunk appears before fmt chunk"))
+                };
+                if rf64 && ds64.is_none() && chunk_len == Ds64::DEFERRED_LEN {
+                    return Err(DecoderError::format_error(AudioFormat::Wav, "RF64 data chunk defers its size to a ds64 chunk, but there is none"))
+                }
+                // A 64-bit size from a ds64 chunk can claim more than any file could hold, which would overflow
+                // every offset computed from it later
+                if offset.checked_add(chunk_len).and_then(|end| end.checked_add(chunk_len & 1)).is_none() {
+                    return Err(DecoderError::format_error(AudioFormat::Wav, format!("data chunk size {} runs past the largest possible file", chunk_len)))
+                }
+                return Ok(WavHeader {
+                    format_tag,
+                    channels,
+                    sample_rate,
+                    byte_rate,
+                    block_align,
+                    bits_per_sample,
+                    data_offset: offset,
+                    data_len: chunk_len,
+                    fact_frames,
+                    broadcast_info,
+                    instrument_info,
+                    rf64,
+                })
+            },
+            // Skip unknown chunks, including the pad byte after odd-length chunks
+            _ => skip_bytes(reader, chunk_len + (chunk_len & 1))?,
+        }
+        offset += chunk_len + (chunk_len & 1);
+    }
+}
+
+/// Walks the chunks stored after the `data` chunk, picking up the metadata that some writers put there.
+///
+/// Only the `inst` chunk is read this way. The metadata isn't needed for decoding, so the walk just stops at the first
+/// chunk that doesn't fit in the file, and the reader is left at an unspecified position.
+pub fn read_trailing_chunks<R: Read + Seek>(reader: &mut R, header: &mut WavHeader, events: Option<&EventHandler>) -> Result<(), DecoderError> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    let mut offset = header.data_offset + header.data_len + (header.data_len & 1);
+    while offset.saturating_add(8) <= file_len {
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut chunk_header = [0; 8];
+        reader.read_exact(&mut chunk_header)?;
+        offset += chunk_header.len() as u64;
+        let chunk_id = [chunk_header[0], chunk_header[1], chunk_header[2], chunk_header[3]];
+        let chunk_len = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]) as u64;
+        if offset + chunk_len > file_len {
+            break
+        }
+        if let Some(handler) = events {
+            handler.emit(DecodeEvent::ChunkParsed { id: chunk_id, offset, len: chunk_len });
+        }
+        if &chunk_id == b"inst" && header.instrument_info.is_none() {
+            let mut buf = vec![0; chunk_len.min(InstrumentInfo::LEN as u64) as usize];
+            reader.read_exact(&mut buf)?;
+            header.instrument_info = InstrumentInfo::parse(&buf);
+        }
+        offset += chunk_len + (chunk_len & 1);
+    }
+    Ok(())
+}
+
+/// Finds the RIFF/WAVE files that some capture tools append after the one whose header is `first`, such as when
+/// they resume a recording after a crash without rewriting the original header.
+///
+/// The walk starts after the first file's `data` chunk and steps over any chunks stored after it, stopping at the end
+/// of the file or at the first chunk that doesn't fit in it. The offsets in the returned headers are from the start
+/// of the whole file, and a `data` chunk that runs past the end of the file is cut short at the end.
+pub fn read_appended_headers<R: Read + Seek>(reader: &mut R, first: &WavHeader) -> Result<Vec<WavHeader>, DecoderError> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    let mut headers = vec![];
+    let mut offset = first.data_offset + first.data_len + (first.data_len & 1);
+    while offset.saturating_add(12) <= file_len {
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut chunk_header = [0; 12];
+        reader.read_exact(&mut chunk_header)?;
+        let chunk_len = u32::from_le_bytes([chunk_header[4], chunk_header[5], chunk_header[6], chunk_header[7]]) as u64;
+        let is_wave = matches!(&chunk_header[0..4], b"RIFF" | b"RF64" | b"BW64") && &chunk_header[8..12] == b"WAVE";
+        if !is_wave {
+            if offset + 8 + chunk_len > file_len {
+                break
+            }
+            offset += 8 + chunk_len + (chunk_len & 1);
+            continue
+        }
+
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut header = read_wav_header(reader, None)?;
+        header.data_offset = match header.data_offset.checked_add(offset) {
+            Some(data_offset) if data_offset <= file_len => data_offset,
+            _ => return Err(DecoderError::format_error(AudioFormat::Wav, "appended file's data chunk starts past the end of the file")),
+        };
+        header.data_len = header.data_len.min(file_len - header.data_offset);
+        offset = header.data_offset + header.data_len + (header.data_len & 1);
+        headers.push(header);
+    }
+    Ok(headers)
+}
+
+#[inline]
+fn skip_bytes<R: Read>(reader: &mut R, len: u64) -> Result<(), DecoderError> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(DecoderError::format_error(AudioFormat::Wav, "unexpected end of header"))
+    }
+    Ok(())
+}
+
+#[inline]
+fn read_bytes<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), DecoderError> {
+    reader.read_exact(buf).map_err(|err| match err.kind() {
+        std::io::ErrorKind::UnexpectedEof => DecoderError::format_error(AudioFormat::Wav, "unexpected end of header"),
+        _ => DecoderError::IOError(err),
+    })
+}
@@ -0,0 +1,171 @@
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+use crate::DecoderError;
+
+/// Number of bytes read from the start of a file to identify its format.
+const SNIFF_LEN: usize = 12;
+
+/// Identifies the format of a stream from its first few bytes, then rewinds the stream to the start.
+///
+/// If the start of the stream isn't recognized, up to `max_probe_bytes` bytes in total are searched for MPEG audio frames,
+/// since MP3 files don't need to start with a frame. No more than that is ever read, so a slow or malicious stream can't
+/// hold up detection for long.
+///
+/// Returns the file extension that format is normally opened by, or `None` if the format wasn't recognized.
+pub fn sniff_extension<R: Read + Seek>(reader: &mut R, max_probe_bytes: u64) -> Result<Option<&'static str>, DecoderError> {
+    reader.seek(SeekFrom::Start(0))?;
+    let mut probe = vec![0; SNIFF_LEN.min(max_probe_bytes as usize)];
+    let len = read_full(reader, &mut probe)?;
+    probe.truncate(len);
+    let mut extension = extension_from_magic(&probe);
+    if extension.is_none() && len == SNIFF_LEN {
+        reader.take(max_probe_bytes - SNIFF_LEN as u64).read_to_end(&mut probe)?;
+        if find_mp3_frames(&probe) {
+            extension = Some("mp3");
+        }
+    }
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(extension)
+}
+
+/// Identifies a stream as a container of encrypted or DRM-protected audio, then rewinds the stream to the start.
+///
+/// None of these containers can be decoded, protected or not, but recognizing the protection lets the caller be told
+/// why a file won't open rather than that it wasn't recognized. No more than `max_probe_bytes` bytes are searched, so
+/// protection described past that point, such as in an MP4 file whose `moov` box comes after its media data, isn't found.
+///
+/// Returns the name of the container, or `None` if the stream isn't recognized as protected.
+pub fn find_protected_container<R: Read + Seek>(reader: &mut R, max_probe_bytes: u64) -> Result<Option<&'static str>, DecoderError> {
+    // The ASF header object, and the content encryption and extended content encryption objects within it
+    const ASF_HEADER: [u8; 16] = [0x30, 0x26, 0xb2, 0x75, 0x8e, 0x66, 0xcf, 0x11, 0xa6, 0xd9, 0x00, 0xaa, 0x00, 0x62, 0xce, 0x6c];
+    const ASF_ENCRYPTION: [[u8; 16]; 2] = [
+        [0xfb, 0xb3, 0x11, 0x22, 0x23, 0xbd, 0xd2, 0x11, 0xb4, 0xb7, 0x00, 0xa0, 0xc9, 0x55, 0xfc, 0x6e],
+        [0x14, 0xe6, 0x8a, 0x29, 0x22, 0x26, 0x17, 0x4c, 0xb9, 0x35, 0xda, 0xe0, 0x7e, 0xe9, 0x28, 0x9c],
+    ];
+    // FairPlay's sample entry, and the protection scheme box that wraps every other encrypted sample entry
+    const MP4_PROTECTION: [&[u8; 4]; 2] = [b"drms", b"sinf"];
+
+    reader.seek(SeekFrom::Start(0))?;
+    let mut data = vec![];
+    reader.take(max_probe_bytes).read_to_end(&mut data)?;
+    reader.seek(SeekFrom::Start(0))?;
+    let contains = |needle: &[u8]| data.windows(needle.len()).any(|window| window == needle);
+    let container = if data.get(4..8) == Some(b"ftyp") && MP4_PROTECTION.iter().any(|name| contains(&name[..])) {
+        Some("MP4")
+    } else if data.starts_with(&ASF_HEADER) && ASF_ENCRYPTION.iter().any(|guid| contains(guid)) {
+        Some("ASF")
+    } else {
+        None
+    };
+    Ok(container)
+}
+
+/// Reads until `buf` is full or the end of the stream is reached, and returns the number of bytes read.
+#[inline]
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, DecoderError> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(DecoderError::IOError(err)),
+        }
+    }
+    Ok(len)
+}
+
+#[inline]
+fn extension_from_magic(magic: &[u8]) -> Option<&'static str> {
+    if (magic.starts_with(b"RIFF") || magic.starts_with(b"RF64") || magic.starts_with(b"BW64")) && magic.get(8..12) == Some(b"WAVE") {
+        Some("wav")
+    } else if magic.starts_with(b"OggS") {
+        Some("ogg")
+    } else if magic.starts_with(b"fLaC") {
+        Some("flac")
+    } else if magic.starts_with(b"DSD ") {
+        Some("dsf")
+    } else if magic.starts_with(b"FRM8") {
+        Some("dff")
+    } else if magic.starts_with(b"ID3") || is_mp3_frame_header(magic) {
+        Some("mp3")
+    } else {
+        None
+    }
+}
+
+/// Checks for an MPEG audio frame sync followed by a Layer III layer description.
+#[inline]
+fn is_mp3_frame_header(magic: &[u8]) -> bool {
+    match magic {
+        [0xff, second, ..] => second & 0xe0 == 0xe0 && (second >> 1) & 0x03 == 0x01,
+        _ => false,
+    }
+}
+
+/// Searches `data` for an MPEG Layer III frame header that's immediately followed by another one.
+///
+/// Frame syncs turn up by chance in arbitrary data, so a lone header isn't enough to go on.
+fn find_mp3_frames(data: &[u8]) -> bool {
+    find_mp3_frame(data).is_some()
+}
+
+/// Gets the header of the first MPEG Layer III frame in `data` that's immediately followed by another one.
+pub fn find_mp3_frame(data: &[u8]) -> Option<Mp3FrameHeader> {
+    (0..data.len()).find_map(|pos| {
+        let header = parse_mp3_frame_header(&data[pos..])?;
+        data.get(pos + header.len..).and_then(parse_mp3_frame_header).map(|_| header)
+    })
+}
+
+/// The fields of an MPEG Layer III frame header that describe the stream.
+pub struct Mp3FrameHeader {
+    /// Length of the frame in bytes, including the header.
+    pub len: usize,
+    pub sample_rate: u32,
+    pub channels: usize,
+    /// Number of frames of audio the frame decodes to.
+    pub samples_per_frame: u32,
+    /// Offset of the frame's main data from the start of the header, past any CRC and the side information.
+    pub main_data_offset: usize,
+}
+
+/// Parses the MPEG Layer III frame header that starts `data`, if it's valid.
+pub fn parse_mp3_frame_header(data: &[u8]) -> Option<Mp3FrameHeader> {
+    const MPEG1_BITRATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const MPEG2_BITRATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+    const MPEG1_SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+    if data.len() < 4 || !is_mp3_frame_header(data) {
+        return None
+    }
+    let (version, bitrate_index, sample_rate_index) = ((data[1] >> 3) & 0x03, (data[2] >> 4) as usize, ((data[2] >> 2) & 0x03) as usize);
+    // Reject the reserved version, free-format and invalid bitrates, and the reserved sample rate
+    if version == 0x01 || bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+        return None
+    }
+    let padding = ((data[2] >> 1) & 0x01) as u32;
+    // MPEG-1 frames hold 1152 samples and the others hold 576, which is 144 or 72 bytes for every 1 kbit/s of bitrate
+    let (bitrate, sample_rate, bytes_per_kbps) = match version {
+        0x03 => (MPEG1_BITRATES[bitrate_index], MPEG1_SAMPLE_RATES[sample_rate_index], 144),
+        0x02 => (MPEG2_BITRATES[bitrate_index], MPEG1_SAMPLE_RATES[sample_rate_index] / 2, 72),
+        _ => (MPEG2_BITRATES[bitrate_index], MPEG1_SAMPLE_RATES[sample_rate_index] / 4, 72),
+    };
+    // Every channel mode except mono (0b11) carries two channels
+    let channels = if data[3] >> 6 == 0x03 { 1 } else { 2 };
+    // A clear protection bit means a 16-bit CRC follows the header
+    let crc_len = if data[1] & 0x01 == 0 { 2 } else { 0 };
+    let side_info_len = match (version, channels) {
+        (0x03, 1) => 17,
+        (0x03, _) => 32,
+        (_, 1) => 9,
+        _ => 17,
+    };
+    Some(Mp3FrameHeader {
+        len: (bytes_per_kbps * bitrate * 1000 / sample_rate + padding) as usize,
+        sample_rate,
+        channels,
+        samples_per_frame: bytes_per_kbps * 8,
+        main_data_offset: 4 + crc_len + side_info_len,
+    })
+}
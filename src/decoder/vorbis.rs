@@ -1,54 +1,144 @@
-use std::{fs::File, io::Read, path::Path, io::Seek};
+use std::io::{Read, Seek};
 
+use lewton::header::IdentHeader;
 use lewton::inside_ogg::OggStreamReader;
 
-use crate::{AudioFormat, AudioInfo, DecoderError};
+use crate::{AudioFormat, AudioInfo, DecoderError, DecoderOptions, SourceReader, Speaker};
+
+/// Vorbis-specific information about an opened Ogg Vorbis stream.
+#[derive(Debug, Clone)]
+pub struct VorbisInfo {
+    vendor: String,
+    stream_serial: u32,
+}
+
+impl VorbisInfo {
+    /// Gets the vendor string from the comment header, which identifies the encoder that produced the stream.
+    #[inline]
+    pub fn vendor(&self) -> &str {
+        &self.vendor
+    }
+
+    /// Gets the serial number of the logical Ogg bitstream being decoded.
+    #[inline]
+    pub fn stream_serial(&self) -> u32 {
+        self.stream_serial
+    }
+}
+
+/// Gets the speaker position of each channel of a Vorbis stream with `channels` channels, as defined by section 4.3.9
+/// of the Vorbis I specification. Streams with more than 8 channels have no defined order.
+pub fn speakers(channels: usize) -> Option<&'static [Speaker]> {
+    use Speaker::*;
+    Some(match channels {
+        1 => &[FrontCenter],
+        2 => &[FrontLeft, FrontRight],
+        3 => &[FrontLeft, FrontCenter, FrontRight],
+        4 => &[FrontLeft, FrontRight, BackLeft, BackRight],
+        5 => &[FrontLeft, FrontCenter, FrontRight, BackLeft, BackRight],
+        6 => &[FrontLeft, FrontCenter, FrontRight, BackLeft, BackRight, LowFrequency],
+        7 => &[FrontLeft, FrontCenter, FrontRight, SideLeft, SideRight, BackCenter, LowFrequency],
+        8 => &[FrontLeft, FrontCenter, FrontRight, SideLeft, SideRight, BackLeft, BackRight, LowFrequency],
+        _ => return None,
+    })
+}
 
 pub struct VorbisDecoder {
-    reader: OggStreamReader<File>,
+    reader: OggStreamReader<SourceReader>,
     channels: usize,
     sample_rate: u32,
+    /// The most frames a packet may decode to, from the decoder options.
+    max_packet_frames: u32,
 }
 
 impl VorbisDecoder {
     #[inline]
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DecoderError> {
-        let f = File::open(path).map_err(|err| DecoderError::IOError(err))?;
-        let reader = match OggStreamReader::new(f) {
+    pub fn new(source: SourceReader, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        let reader = match OggStreamReader::new(source) {
             Ok(reader) => reader,
             Err(err) => {
                 return Err(vorbis_err_to_decoder_err(err))
             }
         };
+        DecoderError::check_stream_params(AudioFormat::Vorbis, reader.ident_hdr.audio_sample_rate, reader.ident_hdr.audio_channels as usize)?;
+        check_packet_size(&reader.ident_hdr, options.max_vorbis_packet_frames)?;
 
         Ok(Self {
             channels: reader.ident_hdr.audio_channels as usize,
             sample_rate: reader.ident_hdr.audio_sample_rate,
+            max_packet_frames: options.max_vorbis_packet_frames,
             reader,
         })
     }
 
+    #[cfg(feature = "backend-access")]
+    #[inline]
+    pub fn lewton_reader(&self) -> &OggStreamReader<SourceReader> {
+        &self.reader
+    }
+
     #[inline]
     pub fn info(&self) -> AudioInfo {
         AudioInfo {
             format: AudioFormat::Vorbis,
             sample_rate: self.sample_rate,
             channels: self.channels,
+            total_frames: None,
+        }
+    }
+
+    #[inline]
+    pub fn vorbis_info(&self) -> VorbisInfo {
+        VorbisInfo {
+            vendor: self.reader.comment_hdr.vendor.clone(),
+            stream_serial: self.reader.stream_serial(),
+        }
+    }
+
+    #[inline]
+    pub fn into_samples(self) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>> + Send>, DecoderError> {
+        Ok(Box::new(self.into_vorbis_samples()?))
+    }
+
+    pub fn into_vorbis_samples(mut self) -> Result<VorbisSamples, DecoderError> {
+        let cur_packet = self.reader.read_dec_packet_itl().map_err(vorbis_err_to_decoder_err)?;
+        if let Some(packet) = &cur_packet {
+            check_packet_len(packet.len() / self.channels, self.max_packet_frames)?;
         }
+        Ok(VorbisSamples {
+            samples: OggSampleIterator {
+                cur_packet,
+                expected_channels: self.channels,
+                expected_sample_rate: self.sample_rate,
+                max_packet_frames: self.max_packet_frames,
+                reader: self.reader,
+                packet_cursor: 0,
+            },
+        })
     }
+}
+
+/// The samples of an Ogg Vorbis file, as an iterator of a concrete type.
+///
+/// See `Decoder::into_vorbis_samples()`.
+pub struct VorbisSamples {
+    samples: OggSampleIterator<SourceReader>,
+}
+
+impl Iterator for VorbisSamples {
+    type Item = Result<crate::Sample, DecoderError>;
 
     #[inline]
-    pub fn into_samples(mut self) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>>>, DecoderError> {
-        Ok(Box::new(OggSampleIterator {
-            cur_packet: self.reader.read_dec_packet_itl().map_err(vorbis_err_to_decoder_err)?,
-            reader: self.reader,
-            packet_cursor: 0,
-        }))
+    fn next(&mut self) -> Option<Self::Item> {
+        self.samples.next()
     }
 }
 
 struct OggSampleIterator<T: Read + Seek> {
     reader: OggStreamReader<T>,
+    expected_channels: usize,
+    expected_sample_rate: u32,
+    max_packet_frames: u32,
     cur_packet: Option<Vec<i16>>,
     packet_cursor: usize,
 }
@@ -59,8 +149,28 @@ impl<T: Read + Seek> OggSampleIterator<T> {
         self.packet_cursor = 0;
         self.cur_packet = match self.reader.read_dec_packet_itl() {
             Ok(packet) => packet,
-            Err(err) => return Err(vorbis_err_to_decoder_err(err))
+            Err(err) => {
+                // Stop at the first error, such as a page that fails its CRC check, rather than decode past it
+                self.cur_packet = None;
+                return Err(vorbis_err_to_decoder_err(err))
+            }
         };
+
+        // Chained streams are decoded back-to-back, but only if their format matches the first stream
+        let ident = &self.reader.ident_hdr;
+        if ident.audio_channels as usize != self.expected_channels || ident.audio_sample_rate != self.expected_sample_rate {
+            self.cur_packet = None;
+            return Err(DecoderError::format_error(AudioFormat::Vorbis, format!(
+                "chained stream changes format from {}-channel {}Hz to {}-channel {}Hz, which is not supported",
+                self.expected_channels, self.expected_sample_rate, ident.audio_channels, ident.audio_sample_rate
+            )))
+        }
+        if let Some(packet) = &self.cur_packet {
+            if let Err(err) = check_packet_len(packet.len() / self.expected_channels, self.max_packet_frames) {
+                self.cur_packet = None;
+                return Err(err)
+            }
+        }
         Ok(())
     }
 }
@@ -71,40 +181,50 @@ impl<T: Read + Seek> Iterator for OggSampleIterator<T> {
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(packet) = self.cur_packet.as_ref() {
-            match packet.get(self.packet_cursor) {
-                Some(sample) => {
-                    // Increment the cursor
-                    self.packet_cursor += 1;
-
-                    let sample = *sample;
-
-                    // Get the next packet if done reading this one
-                    if self.packet_cursor >= packet.len() {
-                        if let Err(err) = self.next_packet() {
-                            return Some(Err(err))
-                        }
-                    }
-
-                    // Convert the sample and return it
-                    let sample_float = sample as f32 / i16::MAX as f32;
-                    return Some(Ok(sample_float))
-                },
-                None => {                        
-                    if let Err(err) = self.next_packet() {
-                        return Some(Err(err))
-                    }
-                    continue
-                }
+            if let Some(sample) = packet.get(self.packet_cursor) {
+                self.packet_cursor += 1;
+                return Some(Ok(*sample as f32 / i16::MAX as f32))
+            }
+            // Only move on once every sample of this packet has been returned, so an error can't displace one
+            if let Err(err) = self.next_packet() {
+                return Some(Err(err))
             }
         }
         None
     }
 }
 
+/// Checks that the larger block size of a stream can't produce packets longer than `max_frames`.
+///
+/// A packet overlaps two blocks and decodes to a quarter of their combined size, so it's at most half a long block.
+fn check_packet_size(ident: &IdentHeader, max_frames: u32) -> Result<(), DecoderError> {
+    let max_packet_frames = (1u32 << ident.blocksize_1) / 2;
+    if max_packet_frames > max_frames {
+        return Err(DecoderError::format_error(AudioFormat::Vorbis, format!("block size {} allows packets of {} frames, which exceeds the limit of {}", 1u32 << ident.blocksize_1, max_packet_frames, max_frames)))
+    }
+    Ok(())
+}
+
+#[inline]
+fn check_packet_len(frames: usize, max_frames: u32) -> Result<(), DecoderError> {
+    if frames > max_frames as usize {
+        return Err(DecoderError::format_error(AudioFormat::Vorbis, format!("packet size {} exceeds the limit of {}", frames, max_frames)))
+    }
+    Ok(())
+}
+
 fn vorbis_err_to_decoder_err(error: lewton::VorbisError) -> DecoderError {
     match error {
-        lewton::VorbisError::BadAudio(err) => DecoderError::FormatError(format!("ogg: bad audio: {}", err)),
-        lewton::VorbisError::BadHeader(err) => DecoderError::FormatError(format!("ogg: bad header: {}", err)),
-        lewton::VorbisError::OggError(err) => DecoderError::FormatError(format!("ogg: {}", err)),
+        lewton::VorbisError::BadAudio(err) => DecoderError::format_error(AudioFormat::Vorbis, format!("bad audio: {}", err)),
+        lewton::VorbisError::BadHeader(err) => DecoderError::format_error(AudioFormat::Vorbis, format!("bad header: {}", err)),
+        lewton::VorbisError::OggError(ogg::OggReadError::ReadError(ioerr)) => DecoderError::IOError(ioerr),
+        lewton::VorbisError::OggError(err) => DecoderError::format_error(AudioFormat::Vorbis, super::ogg::describe_ogg_error(&err)),
+    }
+}
+
+impl From<lewton::VorbisError> for DecoderError {
+    #[inline]
+    fn from(error: lewton::VorbisError) -> Self {
+        vorbis_err_to_decoder_err(error)
     }
-}
\ No newline at end of file
+}
@@ -1,94 +1,635 @@
-use std::{io::BufReader, fs::File, path::Path};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
 
-use hound::{WavReader, WavSpec};
+use hound::{WavIntoSamples, WavReader, WavSpec};
 
-use crate::{AudioFormat, AudioInfo, DecoderError};
+use super::adpcm::{ImaAdpcmDecoder, ImaAdpcmSampleIterator};
+use super::raw::{LayoutSamples, RawDecoder, RawSampleIterator};
+use super::riff::{self, BroadcastInfo, InstrumentInfo, WavHeader};
+use crate::{AudioFormat, AudioInfo, ChannelLayout, DecodeBufferPool, DecoderError, DecoderOptions, Endian, ExpectedLength, RawAudioSpec, RawSampleFormat, SourceReader};
+
+/// Describes how the samples in a WAV file are encoded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WavEncoding {
+    /// Linear integer PCM.
+    Pcm,
+    /// IEEE floating-point.
+    Float,
+    /// 8-bit G.711 A-law companding.
+    ALaw,
+    /// 8-bit G.711 mu-law companding.
+    MuLaw,
+    /// 4-bit IMA ADPCM.
+    ImaAdpcm,
+    /// Any other encoding, identified by its format tag.
+    Other(u16),
+}
+
+/// WAV-specific information about an opened WAV file.
+#[derive(Debug, Clone)]
+pub struct WavInfo {
+    encoding: WavEncoding,
+    bits_per_sample: u16,
+}
+
+impl WavInfo {
+    /// Gets the encoding of the samples in the file.
+    #[inline]
+    pub fn encoding(&self) -> WavEncoding {
+        self.encoding
+    }
+
+    /// Gets the bit depth of the samples as stored in the file, before decoding.
+    ///
+    /// For companded encodings, this is the size of the companded samples (8 bits), not their expanded resolution.
+    #[inline]
+    pub fn bits_per_sample(&self) -> u16 {
+        self.bits_per_sample
+    }
+}
 
 pub struct WavDecoder {
-    reader: WavReader<BufReader<File>>,
-    spec: WavSpec
+    source: WavSource,
+}
+
+enum WavSource {
+    /// Sample formats supported by `hound`.
+    Hound {
+        reader: WavReader<SourceReader>,
+        spec: WavSpec,
+        /// Number of samples actually present in the file, which is less than declared if it's truncated.
+        available_samples: u64,
+        header: WavHeader,
+        /// The frame that decoding starts from.
+        start_frame: u64,
+    },
+    /// Sample formats that creak decodes itself, using its own header parser.
+    Extended {
+        decoder: RawDecoder<SourceReader>,
+        header: WavHeader,
+    },
+    /// IMA ADPCM, which creak decodes itself.
+    ImaAdpcm {
+        decoder: ImaAdpcmDecoder,
+        header: WavHeader,
+    },
+    /// A stream that can't seek, decoded by creak as it's read.
+    Stream {
+        samples: RawSampleIterator<BufReader<Box<dyn Read + Send>>>,
+        header: WavHeader,
+        /// Number of samples declared by the `data` chunk, if its length was filled in.
+        expected_samples: Option<u64>,
+    },
 }
 
 impl WavDecoder {
     #[inline]
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DecoderError> {
-        let reader = WavReader::open(path).map_err(hound_err_to_decoder_err)?;
+    pub fn new(mut reader: SourceReader, options: &DecoderOptions) -> Result<Self, DecoderError> {
+        let mut header = riff::read_wav_header(&mut reader, options.on_event.as_ref())?;
+        header.check_sample_rate(options.wav_rate_check)?;
+        DecoderError::check_stream_params(AudioFormat::Wav, header.sample_rate, header.channels as usize)?;
+        riff::read_trailing_chunks(&mut reader, &mut header, options.on_event.as_ref())?;
+        if options.concatenated_wav {
+            let appended = riff::read_appended_headers(&mut reader, &header)?;
+            if !appended.is_empty() {
+                return Self::open_concatenated(reader, header, appended, options.buffer_pool.clone())
+            }
+        }
+        if header.format_tag == riff::WAVE_FORMAT_IMA_ADPCM {
+            return Ok(Self {
+                source: WavSource::ImaAdpcm {
+                    decoder: ImaAdpcmDecoder::new(reader, &header, options.buffer_pool.clone())?,
+                    header,
+                }
+            })
+        }
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        if let Some(sample_format) = extended_format(&header) {
+            return Self::open_extended(reader, header, sample_format, options.buffer_pool.clone())
+        }
+
+        // hound is stricter about chunk layout than creak's own parser (it doesn't skip the pad byte
+        // after odd-length chunks, for example, and doesn't read RF64 at all), so fall back to the header that was
+        // already parsed. hound takes ownership of the reader, so try it on a borrowed reader first to keep it for the fallback.
+        let hound_result = match header.rf64 {
+            true => Err(DecoderError::format_error(AudioFormat::Wav, format!("RF64 files in format '{}-bit' with tag {:#06x} are not supported", header.bits_per_sample, header.format_tag))),
+            false => WavReader::new(reader.by_ref()).map(drop).map_err(hound_err_to_decoder_err),
+        };
+        if let Err(err) = hound_result {
+            return match fallback_format(&header) {
+                Some(sample_format) => {
+                    reader.seek(SeekFrom::Start(0))?;
+                    Self::open_extended(reader, header, sample_format, options.buffer_pool.clone())
+                },
+                None => Err(err),
+            }
+        }
+        reader.seek(SeekFrom::Start(0))?;
+        let reader = WavReader::new(reader).map_err(hound_err_to_decoder_err)?;
+        let spec = reader.spec();
+        let available_bytes = file_len.saturating_sub(header.data_offset).min(header.data_len);
+        let bytes_per_sample = (header.block_align / header.channels.max(1)).max(1) as u64;
         Ok(Self {
-            spec: reader.spec(),
-            reader
+            source: WavSource::Hound {
+                available_samples: available_bytes / bytes_per_sample,
+                spec,
+                reader,
+                header,
+                start_frame: 0,
+            }
+        })
+    }
+
+    /// Gets the info of a WAV file from its header, without opening it for decoding.
+    pub fn probe(mut reader: SourceReader, options: &DecoderOptions) -> Result<AudioInfo, DecoderError> {
+        let mut header = riff::read_wav_header(&mut reader, None)?;
+        header.check_sample_rate(options.wav_rate_check)?;
+        DecoderError::check_stream_params(AudioFormat::Wav, header.sample_rate, header.channels as usize)?;
+        Ok(AudioInfo {
+            format: AudioFormat::Wav,
+            sample_rate: header.sample_rate,
+            channels: header.channels as usize,
+            total_frames: Some(header.total_frames()),
+        })
+    }
+
+    /// Opens a WAV stream that can't seek, such as a pipe, and reads its samples in order as they arrive.
+    ///
+    /// Streaming writers often can't fill in the `data` chunk length up front, so a length of 0 or `u32::MAX`
+    /// is taken to mean the samples run to the end of the stream.
+    pub fn new_stream(source: Box<dyn Read + Send>) -> Result<Self, DecoderError> {
+        let mut reader = BufReader::new(source);
+        let header = riff::read_wav_header(&mut reader, None)?;
+        DecoderError::check_stream_params(AudioFormat::Wav, header.sample_rate, header.channels as usize)?;
+        let sample_format = match extended_format(&header).or_else(|| fallback_format(&header)) {
+            Some(sample_format) => sample_format,
+            None => return Err(DecoderError::format_error(AudioFormat::Wav, format!("format '{}-bit' with tag {:#06x} can't be streamed", header.bits_per_sample, header.format_tag))),
+        };
+        let expected_samples = match header.data_len {
+            0 | 0xffff_ffff => None,
+            len => Some(len / header.block_align.max(1) as u64 * header.channels as u64),
+        };
+        if is_offset_binary(&header) {
+            reader = BufReader::new(Box::new(SignedBytes(reader)));
+        }
+        Ok(Self {
+            source: WavSource::Stream {
+                samples: RawSampleIterator::new(reader, sample_format, Endian::Little, expected_samples.map(|samples| samples as usize), None),
+                header,
+                expected_samples,
+            }
+        })
+    }
+
+    /// Opens the frames of a WAV file stored between the byte offsets `byte_start` and `byte_end`.
+    ///
+    /// The range must lie within the `data` chunk and start and end on frame boundaries. As with `new_stream()`,
+    /// only the sample formats creak can decode itself are accepted.
+    pub fn new_range(mut reader: SourceReader, byte_start: u64, byte_end: u64) -> Result<Self, DecoderError> {
+        let mut header = riff::read_wav_header(&mut reader, None)?;
+        let sample_format = match extended_format(&header).or_else(|| fallback_format(&header)) {
+            Some(sample_format) => sample_format,
+            None => return Err(DecoderError::format_error(AudioFormat::Wav, format!("format '{}-bit' with tag {:#06x} can't be decoded by byte range", header.bits_per_sample, header.format_tag))),
+        };
+        let data_end = match header.data_offset.checked_add(header.data_len) {
+            Some(data_end) => data_end,
+            None => return Err(DecoderError::format_error(AudioFormat::Wav, "data chunk size overflows its offset")),
+        };
+        if byte_start < header.data_offset || byte_end > data_end || byte_end < byte_start {
+            return Err(DecoderError::InvalidArgument(format!(
+                "byte range {}..{} must lie within the data chunk at {}..{}", byte_start, byte_end, header.data_offset, data_end
+            )))
+        }
+        let frame_size = header.block_align.max(1) as u64;
+        if !(byte_start - header.data_offset).is_multiple_of(frame_size) || !(byte_end - header.data_offset).is_multiple_of(frame_size) {
+            return Err(DecoderError::InvalidArgument(format!(
+                "byte range {}..{} isn't aligned to frame boundaries, which fall every {} bytes from offset {}", byte_start, byte_end, frame_size, header.data_offset
+            )))
+        }
+
+        // Treat the range as if it were the whole data chunk. The fact chunk describes the whole file, so it no longer applies
+        header.data_offset = byte_start;
+        header.data_len = byte_end - byte_start;
+        header.fact_frames = None;
+        reader.seek(SeekFrom::Start(0))?;
+        Self::open_extended(reader, header, sample_format, None)
+    }
+
+    /// Opens a WAV file whose samples are known to start at the byte offset `data_offset`, ignoring its `data` chunk.
+    ///
+    /// Only the chunks up to the end of the `fmt` chunk are read, and the samples are taken to run to the end of the
+    /// file. As with `new_stream()`, only the sample formats creak can decode itself are accepted, as well as IMA ADPCM.
+    pub fn new_with_data_offset(mut reader: SourceReader, data_offset: u64) -> Result<Self, DecoderError> {
+        let mut header = riff::read_wav_format(&mut reader)?;
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        if data_offset > file_len {
+            return Err(DecoderError::InvalidArgument(format!("data offset {} is past the end of the file at {}", data_offset, file_len)))
+        }
+        // Nothing after the fmt chunk is trusted, so the fact chunk is ignored along with the data chunk
+        header.data_offset = data_offset;
+        header.data_len = file_len - data_offset;
+        header.fact_frames = None;
+        DecoderError::check_stream_params(AudioFormat::Wav, header.sample_rate, header.channels as usize)?;
+        if header.format_tag == riff::WAVE_FORMAT_IMA_ADPCM {
+            return Ok(Self {
+                source: WavSource::ImaAdpcm {
+                    decoder: ImaAdpcmDecoder::new(reader, &header, None)?,
+                    header,
+                }
+            })
+        }
+        match extended_format(&header).or_else(|| fallback_format(&header)) {
+            Some(sample_format) => Self::open_extended(reader, header, sample_format, None),
+            None => Err(DecoderError::format_error(AudioFormat::Wav, format!("format '{}-bit' with tag {:#06x} can't be decoded from a forced data offset", header.bits_per_sample, header.format_tag))),
+        }
+    }
+
+    /// Opens a WAV file followed by the appended files whose headers are `appended`, decoding their `data` chunks back
+    /// to back as if they were one.
+    fn open_concatenated(reader: SourceReader, mut header: WavHeader, appended: Vec<WavHeader>, buffer_pool: Option<DecodeBufferPool>) -> Result<Self, DecoderError> {
+        let format_of = |header: &WavHeader| (header.format_tag, header.channels, header.sample_rate, header.bits_per_sample, header.block_align);
+        if let Some(other) = appended.iter().find(|other| format_of(other) != format_of(&header)) {
+            return Err(DecoderError::format_error(AudioFormat::Wav, format!(
+                "appended file with its data at byte {} is {}-channel {}Hz {}-bit with tag {:#06x}, which doesn't match the {}-channel {}Hz {}-bit with tag {:#06x} before it",
+                other.data_offset, other.channels, other.sample_rate, other.bits_per_sample, other.format_tag,
+                header.channels, header.sample_rate, header.bits_per_sample, header.format_tag
+            )))
+        }
+        let sample_format = match extended_format(&header).or_else(|| fallback_format(&header)) {
+            Some(sample_format) => sample_format,
+            None => return Err(DecoderError::format_error(AudioFormat::Wav, format!("format '{}-bit' with tag {:#06x} can't be decoded across appended files", header.bits_per_sample, header.format_tag))),
+        };
+
+        // A file cut off mid-frame would throw every frame after it out of line, so each chunk is cut to whole frames
+        let frame_size = header.block_align.max(1) as u64;
+        let segments: Vec<(u64, u64)> = std::iter::once(&header).chain(appended.iter())
+            .map(|header| (header.data_offset, header.data_len / frame_size * frame_size))
+            .collect();
+        let data = ConcatenatedData::new(reader, segments);
+
+        // The fact chunk only describes the first file
+        header.data_offset = 0;
+        header.data_len = data.len;
+        header.fact_frames = None;
+        Self::open_extended(BufReader::new(Box::new(data)), header, sample_format, buffer_pool)
+    }
+
+    fn open_extended(mut reader: SourceReader, header: WavHeader, sample_format: RawSampleFormat, buffer_pool: Option<DecodeBufferPool>) -> Result<Self, DecoderError> {
+        DecoderError::check_stream_params(AudioFormat::Wav, header.sample_rate, header.channels as usize)?;
+        if is_offset_binary(&header) {
+            // The raw decoder seeks to the samples itself, so the bytes the buffer already holds can be dropped
+            reader = BufReader::new(Box::new(SignedBytes(reader.into_inner())));
+        }
+        let frame_size = header.block_align.max(1) as u64;
+        let mut decoder = RawDecoder::new(reader, RawAudioSpec {
+            sample_rate: header.sample_rate,
+            channels: header.channels as usize,
+            sample_format,
+            endianness: Endian::Little,
+            start_offset: header.data_offset as usize,
+            max_frames: Some((header.data_len / frame_size) as usize),
+            layout: ChannelLayout::Interleaved,
+        })?;
+        decoder.set_buffer_pool(buffer_pool);
+        Ok(Self {
+            source: WavSource::Extended {
+                decoder,
+                header,
+            }
         })
     }
 
     #[inline]
     pub fn info(&self) -> AudioInfo {
-        let spec = self.spec;
+        let header = match &self.source {
+            WavSource::Hound { header, .. } | WavSource::Extended { header, .. } | WavSource::ImaAdpcm { header, .. } | WavSource::Stream { header, .. } => header,
+        };
         AudioInfo {
             format: AudioFormat::Wav,
-            sample_rate: spec.sample_rate,
-            channels: spec.channels as usize,
-        }
-    }
-
-    pub fn into_samples(self) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>>>, DecoderError> {
-        let spec = self.spec;
-        Ok(match (spec.bits_per_sample, spec.sample_format) {
-            (8, hound::SampleFormat::Int) => {
-                let iter = self.reader.into_samples::<i8>()
-                    .map(|sample| 
-                        sample
-                        .map(|sample| sample as f32 / i8::MAX as f32)
-                        .map_err(hound_err_to_decoder_err)
-                    );
-                Box::new(iter)
+            sample_rate: header.sample_rate,
+            channels: header.channels as usize,
+            total_frames: match self.source {
+                WavSource::Stream { .. } => None,
+                _ => Some(header.total_frames()),
             },
-            (16, hound::SampleFormat::Int) => {
-                let iter = self.reader.into_samples::<i16>()
-                    .map(|sample| 
-                        sample
-                        .map(|sample| sample as f32 / i16::MAX as f32)
-                        .map_err(hound_err_to_decoder_err)
-                    );
-                Box::new(iter)
+        }
+    }
+
+    #[inline]
+    pub fn wav_info(&self) -> WavInfo {
+        let header = match &self.source {
+            WavSource::Hound { header, .. } | WavSource::Extended { header, .. } | WavSource::ImaAdpcm { header, .. } | WavSource::Stream { header, .. } => header,
+        };
+        let encoding = match header.format_tag {
+            riff::WAVE_FORMAT_PCM => WavEncoding::Pcm,
+            riff::WAVE_FORMAT_IEEE_FLOAT => WavEncoding::Float,
+            riff::WAVE_FORMAT_ALAW => WavEncoding::ALaw,
+            riff::WAVE_FORMAT_MULAW => WavEncoding::MuLaw,
+            riff::WAVE_FORMAT_IMA_ADPCM => WavEncoding::ImaAdpcm,
+            other => WavEncoding::Other(other),
+        };
+        WavInfo {
+            encoding,
+            bits_per_sample: header.bits_per_sample,
+        }
+    }
+
+    #[inline]
+    pub fn broadcast_info(&self) -> Option<BroadcastInfo> {
+        match &self.source {
+            WavSource::Hound { header, .. } | WavSource::Extended { header, .. } | WavSource::ImaAdpcm { header, .. } | WavSource::Stream { header, .. } => header.broadcast_info.as_deref().cloned(),
+        }
+    }
+
+    #[inline]
+    pub fn instrument_info(&self) -> Option<InstrumentInfo> {
+        match &self.source {
+            WavSource::Hound { header, .. } | WavSource::Extended { header, .. } | WavSource::ImaAdpcm { header, .. } | WavSource::Stream { header, .. } => header.instrument_info,
+        }
+    }
+
+    #[cfg(feature = "backend-access")]
+    #[inline]
+    pub fn hound_reader(&self) -> Option<&WavReader<SourceReader>> {
+        match &self.source {
+            WavSource::Hound { reader, .. } => Some(reader),
+            WavSource::Extended { .. } | WavSource::ImaAdpcm { .. } | WavSource::Stream { .. } => None,
+        }
+    }
+
+    /// Gets the frame that decoding starts from, or `None` if the stream isn't seekable.
+    #[inline]
+    pub fn position(&self) -> Option<u64> {
+        match &self.source {
+            WavSource::Hound { start_frame, .. } => Some(*start_frame),
+            WavSource::Extended { decoder, .. } => Some(decoder.position()),
+            WavSource::ImaAdpcm { decoder, .. } => Some(decoder.position()),
+            WavSource::Stream { .. } => None,
+        }
+    }
+
+    /// Returns `true` if the stream can seek to any frame in constant time, which it can unless it's read as it arrives.
+    #[inline]
+    pub fn random_access(&self) -> bool {
+        !matches!(self.source, WavSource::Stream { .. })
+    }
+
+    /// Moves the start of decoding to the specified frame, clamped to the end of the stream.
+    pub fn seek(&mut self, frame: u64) -> Result<(), DecoderError> {
+        match &mut self.source {
+            WavSource::Hound { reader, start_frame, .. } => {
+                let frame = frame.min(reader.duration() as u64);
+                reader.seek(frame as u32)?;
+                *start_frame = frame;
+                Ok(())
             },
-            (24, hound::SampleFormat::Int) => {
-                const MAX_I24: i32 = 0x7fffff;
-                let iter = self.reader
-                    .into_samples::<i32>()
-                    .map(|sample| 
-                        sample
-                        .map(|sample| sample as f32 / MAX_I24 as f32)
-                        .map_err(hound_err_to_decoder_err)
-                    );
-                Box::new(iter)
+            WavSource::Extended { decoder, .. } => decoder.seek(frame),
+            WavSource::ImaAdpcm { decoder, .. } => {
+                decoder.seek(frame);
+                Ok(())
             },
-            (32, hound::SampleFormat::Int) => {
-                let iter = self.reader
-                    .into_samples::<i32>()
-                    .map(|sample| 
-                        sample
-                        .map(|sample| sample as f32 / i32::MAX as f32)
-                        .map_err(hound_err_to_decoder_err)
-                    );
-                Box::new(iter)
+            WavSource::Stream { .. } => Err(DecoderError::SeekNotSupported(AudioFormat::Wav)),
+        }
+    }
+
+    #[inline]
+    pub fn into_samples(self) -> Result<Box<dyn Iterator<Item = Result<crate::Sample, DecoderError>> + Send>, DecoderError> {
+        Ok(Box::new(self.into_wav_samples()?))
+    }
+
+    pub fn into_wav_samples(self) -> Result<WavSamples, DecoderError> {
+        let (reader, spec, available_samples, skipped_samples) = match self.source {
+            WavSource::Hound { reader, spec, available_samples, header, start_frame } => (reader, spec, available_samples, start_frame * header.channels as u64),
+            WavSource::Extended { decoder, header } => {
+                let expected_frames = (header.data_len / header.block_align.max(1) as u64).saturating_sub(decoder.position());
+                let expected_samples = expected_frames * header.channels as u64;
+                return Ok(WavSamples::new(WavSampleSource::Extended(ExpectedLength::new(decoder.into_layout_samples(), expected_samples))))
             },
-            (32, hound::SampleFormat::Float) => {
-                let iter = self.reader
-                    .into_samples::<f32>()
-                    .map(|sample| sample.map_err(hound_err_to_decoder_err));
-                Box::new(iter)
+            WavSource::ImaAdpcm { decoder, .. } => return Ok(WavSamples::new(WavSampleSource::ImaAdpcm(decoder.into_samples()?))),
+            WavSource::Stream { samples, expected_samples: Some(expected_samples), .. } => return Ok(WavSamples::new(WavSampleSource::Stream(ExpectedLength::new(samples, expected_samples)))),
+            WavSource::Stream { samples, expected_samples: None, .. } => return Ok(WavSamples::new(WavSampleSource::UnboundedStream(samples))),
+        };
+        // hound reports a generic I/O error when a file is truncated, so stop at the end of the
+        // data that's actually there and let `ExpectedLength` report the truncation instead.
+        let expected_samples = (reader.len() as u64).saturating_sub(skipped_samples);
+        let available_samples = available_samples.saturating_sub(skipped_samples);
+        let samples = match (spec.bits_per_sample, spec.sample_format) {
+            (8, hound::SampleFormat::Int) => HoundSamples::I8(reader.into_samples()),
+            (16, hound::SampleFormat::Int) => HoundSamples::I16(reader.into_samples()),
+            (24, hound::SampleFormat::Int) => HoundSamples::I24(reader.into_samples()),
+            (32, hound::SampleFormat::Int) => HoundSamples::I32(reader.into_samples()),
+            (32, hound::SampleFormat::Float) => HoundSamples::F32(reader.into_samples()),
+            (other_bps, other_format) => return Err(DecoderError::format_error(AudioFormat::Wav, format!("format '{}-bit {:?}' is not supported", other_bps, other_format)))
+        };
+        Ok(WavSamples::new(WavSampleSource::Hound(ExpectedLength::new(samples.take(available_samples as usize), expected_samples))))
+    }
+}
+
+/// The samples of a WAV file, as an iterator of a concrete type.
+///
+/// See `Decoder::into_wav_samples()`.
+pub struct WavSamples {
+    source: WavSampleSource,
+}
+
+/// Where the samples of a WAV file are decoded, which depends on the sample format.
+enum WavSampleSource {
+    Hound(ExpectedLength<std::iter::Take<HoundSamples>>),
+    Extended(ExpectedLength<LayoutSamples<SourceReader>>),
+    ImaAdpcm(ExpectedLength<ImaAdpcmSampleIterator>),
+    Stream(ExpectedLength<RawSampleIterator<BufReader<Box<dyn Read + Send>>>>),
+    /// A stream whose `data` chunk length was left unset.
+    UnboundedStream(RawSampleIterator<BufReader<Box<dyn Read + Send>>>),
+}
+
+impl WavSamples {
+    #[inline]
+    fn new(source: WavSampleSource) -> Self {
+        Self { source }
+    }
+}
+
+impl Iterator for WavSamples {
+    type Item = Result<crate::Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.source {
+            WavSampleSource::Hound(samples) => samples.next(),
+            WavSampleSource::Extended(samples) => samples.next(),
+            WavSampleSource::ImaAdpcm(samples) => samples.next(),
+            WavSampleSource::Stream(samples) => samples.next(),
+            WavSampleSource::UnboundedStream(samples) => samples.next(),
+        }
+    }
+}
+
+/// The samples read by `hound`, by the integer type they're read as.
+enum HoundSamples {
+    I8(WavIntoSamples<SourceReader, i8>),
+    I16(WavIntoSamples<SourceReader, i16>),
+    I24(WavIntoSamples<SourceReader, i32>),
+    I32(WavIntoSamples<SourceReader, i32>),
+    F32(WavIntoSamples<SourceReader, f32>),
+}
+
+impl Iterator for HoundSamples {
+    type Item = Result<crate::Sample, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        const MAX_I24: i32 = 0x7fffff;
+        let sample = match self {
+            HoundSamples::I8(samples) => samples.next()?.map(|sample| sample as f32 / i8::MAX as f32),
+            HoundSamples::I16(samples) => samples.next()?.map(|sample| sample as f32 / i16::MAX as f32),
+            HoundSamples::I24(samples) => samples.next()?.map(|sample| sample as f32 / MAX_I24 as f32),
+            HoundSamples::I32(samples) => samples.next()?.map(|sample| sample as f32 / i32::MAX as f32),
+            HoundSamples::F32(samples) => samples.next()?,
+        };
+        Some(sample.map_err(hound_err_to_decoder_err))
+    }
+}
+
+/// Gets the raw sample format for sample formats that creak decodes itself, either because `hound` doesn't
+/// support them or because creak's bulk reader is faster.
+#[inline]
+fn extended_format(header: &WavHeader) -> Option<RawSampleFormat> {
+    match (header.format_tag, header.bits_per_sample) {
+        (riff::WAVE_FORMAT_PCM, 16) if header.block_align as u32 == header.channels as u32 * 2 => Some(RawSampleFormat::Signed16),
+        (riff::WAVE_FORMAT_IEEE_FLOAT, 64) => Some(RawSampleFormat::Float64),
+        (riff::WAVE_FORMAT_ALAW, 8) => Some(RawSampleFormat::ALaw),
+        (riff::WAVE_FORMAT_MULAW, 8) => Some(RawSampleFormat::MuLaw),
+        _ => None
+    }
+}
+
+/// The `data` chunks of concatenated WAV files, read back to back as one stream.
+struct ConcatenatedData {
+    reader: SourceReader,
+    /// The byte offset and length of each chunk's samples in the file.
+    segments: Vec<(u64, u64)>,
+    len: u64,
+    /// The position in the joined stream.
+    pos: u64,
+    /// The position of `reader` in the file, if it's known, to avoid seeking it between reads from the same chunk.
+    file_pos: Option<u64>,
+}
+
+impl ConcatenatedData {
+    #[inline]
+    fn new(reader: SourceReader, segments: Vec<(u64, u64)>) -> Self {
+        Self {
+            len: segments.iter().map(|(_, len)| len).sum(),
+            reader,
+            segments,
+            pos: 0,
+            file_pos: None,
+        }
+    }
+}
+
+impl Read for ConcatenatedData {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut segment_start = 0;
+        for &(offset, len) in &self.segments {
+            if self.pos < segment_start + len {
+                let within = self.pos - segment_start;
+                let file_pos = offset + within;
+                if self.file_pos != Some(file_pos) {
+                    self.reader.seek(SeekFrom::Start(file_pos))?;
+                }
+                let max = buf.len().min((len - within).min(usize::MAX as u64) as usize);
+                let read = self.reader.read(&mut buf[..max])?;
+                self.file_pos = Some(file_pos + read as u64);
+                self.pos += read as u64;
+                return Ok(read)
+            }
+            segment_start += len;
+        }
+        Ok(0)
+    }
+}
+
+impl Seek for ConcatenatedData {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+        };
+        match target {
+            Some(target) => {
+                self.pos = target;
+                Ok(target)
             },
-            (other_bps, other_format) => return Err(DecoderError::FormatError(format!("wav: format '{}-bit {:?}' is not supported", other_bps, other_format)))
-        })
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+        }
+    }
+}
+
+/// Gets the raw sample format for sample formats that `hound` supports, for files whose layout `hound` rejects.
+///
+/// 8-bit samples are read through `SignedBytes`, so they're given as signed here.
+#[inline]
+fn fallback_format(header: &WavHeader) -> Option<RawSampleFormat> {
+    let sample_format = match (header.format_tag, header.bits_per_sample) {
+        (riff::WAVE_FORMAT_PCM, 8) => RawSampleFormat::Signed8,
+        (riff::WAVE_FORMAT_PCM, 16) => RawSampleFormat::Signed16,
+        (riff::WAVE_FORMAT_PCM, 24) => RawSampleFormat::Signed24,
+        (riff::WAVE_FORMAT_PCM, 32) => RawSampleFormat::Signed32,
+        (riff::WAVE_FORMAT_IEEE_FLOAT, 32) => RawSampleFormat::Float32,
+        _ => return None
+    };
+    // Samples padded out to a larger container can't be read as raw samples
+    let bytes_per_sample = (header.block_align / header.channels.max(1)) as usize;
+    if bytes_per_sample == sample_format.size_bytes() { Some(sample_format) } else { None }
+}
+
+/// Checks whether a file's samples are 8-bit PCM, which WAV stores as unsigned bytes with 128 as zero.
+#[inline]
+fn is_offset_binary(header: &WavHeader) -> bool {
+    header.format_tag == riff::WAVE_FORMAT_PCM && header.bits_per_sample == 8
+}
+
+/// A reader that flips the top bit of every byte, which turns 8-bit WAV samples into signed bytes.
+///
+/// `hound` reads 8-bit samples by subtracting 128 from them, so reading them as signed bytes decodes them to the same
+/// values whether or not `hound` is used.
+struct SignedBytes<R>(R);
+
+impl<R: Read> Read for SignedBytes<R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.0.read(buf)?;
+        for byte in &mut buf[..read] {
+            *byte ^= 0x80;
+        }
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for SignedBytes<R> {
+    #[inline]
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
     }
 }
 
 fn hound_err_to_decoder_err(error: hound::Error) -> DecoderError {
     match error {
         hound::Error::IoError(ioerr) => DecoderError::IOError(ioerr),
-        hound::Error::FormatError(fmterr) => DecoderError::FormatError(format!("wav: {}", fmterr)),
-        hound::Error::Unsupported => DecoderError::FormatError("wav: unsupported format".to_owned()),
-        hound::Error::InvalidSampleFormat => DecoderError::FormatError("wav: invalid sample format".to_owned()),
-        hound::Error::TooWide => DecoderError::FormatError("wav: decoded samples are too wide for destination type".to_owned()),
-        _ => unreachable!()
+        hound::Error::FormatError(fmterr) => DecoderError::format_error(AudioFormat::Wav, fmterr),
+        hound::Error::Unsupported => DecoderError::format_error(AudioFormat::Wav, "unsupported format"),
+        hound::Error::InvalidSampleFormat => DecoderError::format_error(AudioFormat::Wav, "invalid sample format"),
+        hound::Error::TooWide => DecoderError::format_error(AudioFormat::Wav, "decoded samples are too wide for destination type"),
+        other => DecoderError::format_error(AudioFormat::Wav, other.to_string()),
+    }
+}
+
+impl From<hound::Error> for DecoderError {
+    #[inline]
+    fn from(error: hound::Error) -> Self {
+        hound_err_to_decoder_err(error)
     }
 }
\ No newline at end of file
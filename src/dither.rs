@@ -0,0 +1,106 @@
+use crate::{DecoderError, Sample, SampleIterator};
+
+/// Dithering applied when reducing decoded samples to an integer bit depth.
+#[derive(Debug, Copy, Clone, Default)]
+pub enum Dither {
+    /// No dithering. Samples are rounded to the nearest integer value, which is bit-exact but
+    /// can produce audible quantization distortion on quiet passages.
+    #[default]
+    None,
+    /// Triangular probability density function (TPDF) dither with a peak amplitude of 1 LSB.
+    Triangular,
+    /// TPDF dither combined with first-order error-feedback noise shaping,
+    /// which pushes the quantization noise towards higher frequencies.
+    NoiseShaped,
+}
+
+/// Converts float samples to integers of a given bit depth, applying dither if requested.
+pub(crate) struct Quantizer {
+    dither: Dither,
+    scale: f32,
+    min: i32,
+    max: i32,
+    channels: usize,
+    channel_cursor: usize,
+    errors: Vec<f32>,
+    rng_state: u32,
+}
+
+impl Quantizer {
+    pub fn new(bits: u32, channels: usize, dither: Dither) -> Self {
+        let max = ((1i64 << (bits - 1)) - 1) as i32;
+        Self {
+            dither,
+            scale: max as f32,
+            min: -max - 1,
+            max,
+            channels: channels.max(1),
+            channel_cursor: 0,
+            errors: vec![0.0; channels.max(1)],
+            rng_state: 0x9e37_79b9,
+        }
+    }
+
+    /// Quantizes the next interleaved sample.
+    #[inline]
+    pub fn quantize(&mut self, sample: Sample) -> i32 {
+        let channel = self.channel_cursor;
+        self.channel_cursor = (self.channel_cursor + 1) % self.channels;
+
+        let scaled = sample * self.scale;
+        let quantized = match self.dither {
+            Dither::None => scaled.round(),
+            Dither::Triangular => (scaled + self.tpdf()).round(),
+            Dither::NoiseShaped => {
+                let shaped = scaled - self.errors[channel];
+                let quantized = (shaped + self.tpdf()).round();
+                self.errors[channel] = quantized - shaped;
+                quantized
+            }
+        };
+
+        (quantized as i32).clamp(self.min, self.max)
+    }
+
+    /// Generates triangular noise in the range (-1, 1) LSB.
+    #[inline]
+    fn tpdf(&mut self) -> f32 {
+        self.uniform() + self.uniform() - 1.0
+    }
+
+    /// Generates uniform noise in the range [0, 1) using xorshift32.
+    #[inline]
+    fn uniform(&mut self) -> f32 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng_state = x;
+        (x >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
+/// Iterates over decoded audio samples converted to signed 16-bit integers. Channels are interleaved.
+pub struct I16SampleIterator {
+    samples: SampleIterator,
+    quantizer: Quantizer,
+}
+
+impl I16SampleIterator {
+    pub(crate) fn new(samples: SampleIterator, channels: usize, dither: Dither) -> Self {
+        Self {
+            samples,
+            quantizer: Quantizer::new(16, channels, dither),
+        }
+    }
+}
+
+impl Iterator for I16SampleIterator {
+    type Item = Result<i16, DecoderError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.samples.next()?;
+        Some(sample.map(|sample| self.quantizer.quantize(sample) as i16))
+    }
+}
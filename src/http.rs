@@ -0,0 +1,189 @@
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// How far ahead of the open response a read can land before a new range request is made instead of skipping bytes.
+const MAX_SKIP_BYTES: u64 = 64 * 1024;
+
+type Body = Box<dyn Read + Send + Sync>;
+
+/// A `Read + Seek` stream over a resource served over HTTP, for decoding audio with `Decoder::from_reader()`.
+///
+/// Seeking is done with range requests: the response body is read in order for as long as reads follow on from each
+/// other, and a seek to anywhere it can't cheaply skip ahead to drops the response and requests the bytes from the new
+/// position onwards. Servers that ignore range requests still work, but each backwards seek downloads the resource
+/// again from its start.
+///
+/// HTTP errors and status codes other than `2xx` are returned as I/O errors of kind `Other`.
+pub struct HttpReader {
+    agent: ureq::Agent,
+    url: String,
+    len: Option<u64>,
+    accepts_ranges: bool,
+    /// The position that the next read starts from.
+    pos: u64,
+    /// The open response body, and the position in the resource that it has been read up to.
+    body: Option<(Body, u64)>,
+}
+
+impl HttpReader {
+    /// Requests the resource at `url`, using a default `ureq::Agent`.
+    #[inline]
+    pub fn open(url: impl Into<String>) -> io::Result<Self> {
+        Self::open_with_agent(ureq::Agent::new(), url)
+    }
+
+    /// Requests the resource at `url` with `agent`, which sets the timeouts, proxy, and other configuration to use for
+    /// every request made by the reader.
+    pub fn open_with_agent(agent: ureq::Agent, url: impl Into<String>) -> io::Result<Self> {
+        let mut reader = Self {
+            agent,
+            url: url.into(),
+            len: None,
+            accepts_ranges: true,
+            pos: 0,
+            body: None,
+        };
+        reader.request_from(0)?;
+        Ok(reader)
+    }
+
+    /// Gets the URL of the resource.
+    #[inline]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Gets the length of the resource in bytes, if the server reported it.
+    #[inline]
+    pub fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Returns `true` if the server reported the resource as empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == Some(0)
+    }
+
+    /// Returns `true` unless the server has ignored a range request, after which seeking backwards downloads the
+    /// resource again from its start.
+    #[inline]
+    pub fn accepts_ranges(&self) -> bool {
+        self.accepts_ranges
+    }
+
+    /// Replaces the open response with one whose body starts at `pos`, or at the start of the resource if the server
+    /// doesn't serve ranges.
+    fn request_from(&mut self, pos: u64) -> io::Result<()> {
+        self.body = None;
+        let mut request = self.agent.get(&self.url);
+        if self.accepts_ranges {
+            request = request.set("Range", &format!("bytes={}-", pos));
+        }
+        let response = match request.call() {
+            Ok(response) => response,
+            // Asking for a range starting at or past the end of the resource
+            Err(ureq::Error::Status(416, response)) => {
+                if let Some(len) = response.header("Content-Range").and_then(parse_total_len) {
+                    self.len = Some(len);
+                }
+                self.body = Some((Box::new(io::empty()), pos));
+                return Ok(())
+            },
+            Err(err) => return Err(io::Error::other(err)),
+        };
+        let start = if response.status() == 206 {
+            let range = response.header("Content-Range").unwrap_or_default();
+            if let Some(len) = parse_total_len(range) {
+                self.len = Some(len);
+            }
+            match parse_range_start(range) {
+                Some(start) => start,
+                None => return Err(io::Error::other(format!("server sent an unreadable Content-Range header: '{}'", range))),
+            }
+        } else {
+            self.accepts_ranges = false;
+            if let Some(len) = response.header("Content-Length").and_then(|len| len.trim().parse().ok()) {
+                self.len = Some(len);
+            }
+            0
+        };
+        if start > pos {
+            return Err(io::Error::other(format!("server sent bytes from {} when asked for bytes from {}", start, pos)))
+        }
+        self.body = Some((response.into_reader(), start));
+        Ok(())
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.len.is_some_and(|len| self.pos >= len) {
+            return Ok(0)
+        }
+
+        let reusable = match &self.body {
+            Some((_, body_pos)) => *body_pos <= self.pos && (!self.accepts_ranges || self.pos - *body_pos <= MAX_SKIP_BYTES),
+            None => false,
+        };
+        if !reusable {
+            self.request_from(self.pos)?;
+        }
+
+        let pos = self.pos;
+        let (body, body_pos) = self.body.as_mut().unwrap();
+        if *body_pos < pos {
+            let skipped = io::copy(&mut body.by_ref().take(pos - *body_pos), &mut io::sink())?;
+            *body_pos += skipped;
+            if *body_pos < pos {
+                return Ok(0)
+            }
+        }
+        let read = body.read(buf)?;
+        *body_pos += read as u64;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for HttpReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => match self.len {
+                Some(len) => len.checked_add_signed(offset),
+                None => return Err(io::Error::new(io::ErrorKind::Unsupported, "can't seek from the end of an HTTP resource of unknown length")),
+            },
+        };
+        match target {
+            Some(target) => {
+                self.pos = target;
+                Ok(target)
+            },
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")),
+        }
+    }
+}
+
+impl fmt::Debug for HttpReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpReader")
+            .field("url", &self.url)
+            .field("len", &self.len)
+            .field("accepts_ranges", &self.accepts_ranges)
+            .field("pos", &self.pos)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Gets the first byte position from a `Content-Range` header such as `bytes 100-199/1000`.
+fn parse_range_start(header: &str) -> Option<u64> {
+    let range = header.trim().strip_prefix("bytes")?.trim_start();
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// Gets the total length from a `Content-Range` header such as `bytes 100-199/1000` or `bytes */1000`.
+fn parse_total_len(header: &str) -> Option<u64> {
+    header.rsplit('/').next()?.trim().parse().ok()
+}
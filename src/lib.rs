@@ -9,10 +9,33 @@
 //!
 //! The audio file is closed when the `Decoder` or `SampleIterator` is dropped.
 //! 
-//! Currently supported formats are WAV, Ogg Vorbis, MP3, and FLAC.
+//! Currently supported formats are WAV, Ogg Vorbis, MP3, FLAC (native or in an Ogg container), and DSD (DSF or
+//! DSDIFF, converted to PCM).
 
 #![allow(dead_code)]
 
+mod adapters;
+mod decoded;
 mod decoder;
+mod dither;
+#[cfg(feature = "http")] mod http;
+mod loudness;
+mod meters;
+mod pcm;
+mod planar;
+mod pool;
+mod spectrum;
+#[cfg(feature = "wav")] mod transcode;
 
-pub use decoder::*;
\ No newline at end of file
+pub use adapters::EnumeratedFrames;
+pub use decoded::DecodedAudio;
+pub use decoder::*;
+pub use dither::*;
+#[cfg(feature = "http")] pub use http::HttpReader;
+pub use loudness::LoudnessResult;
+pub use meters::Meters;
+pub use pcm::PcmReader;
+pub use planar::{PlanarBlocks, PlanarSamples};
+pub use pool::DecodeBufferPool;
+pub use spectrum::SpectrumFrames;
+#[cfg(feature = "wav")] pub use transcode::{transcode_to_wav, WavOutputSpec};
\ No newline at end of file
@@ -0,0 +1,253 @@
+use std::f64::consts::PI;
+
+use crate::{AudioInfo, DecoderError, SampleIterator};
+
+/// Loudness measurements of a stream, as specified by ITU-R BS.1770-4.
+#[derive(Debug, Clone)]
+pub struct LoudnessResult {
+    integrated_lufs: f64,
+    true_peak: f32,
+}
+
+impl LoudnessResult {
+    /// Gets the gated integrated loudness in LUFS.
+    ///
+    /// Returns negative infinity if the stream is silent or shorter than one 400ms gating block.
+    #[inline]
+    pub fn integrated_lufs(&self) -> f64 {
+        self.integrated_lufs
+    }
+
+    /// Gets the true-peak level as a linear amplitude, estimated by 4x oversampling.
+    #[inline]
+    pub fn true_peak(&self) -> f32 {
+        self.true_peak
+    }
+
+    /// Gets the true-peak level in dBTP.
+    #[inline]
+    pub fn true_peak_dbtp(&self) -> f32 {
+        20.0 * self.true_peak.log10()
+    }
+}
+
+/// Measures the loudness of the samples, consuming them in a single streaming pass.
+pub(crate) fn measure(info: &AudioInfo, samples: SampleIterator) -> Result<LoudnessResult, DecoderError> {
+    let channels = info.channels().max(1);
+    let sample_rate = info.sample_rate() as f64;
+    let weights = channel_weights(channels);
+
+    let mut filters: Vec<KWeightingFilter> = (0..channels).map(|_| KWeightingFilter::new(sample_rate)).collect();
+    let mut peak_meters: Vec<TruePeakMeter> = (0..channels).map(|_| TruePeakMeter::new()).collect();
+
+    // Mean-square energy is accumulated in 100ms steps; each gating block spans four steps (400ms, 75% overlap)
+    let step_len = ((sample_rate * 0.1).round() as usize).max(1);
+    let mut step_energy = vec![0.0f64; channels];
+    let mut step_frames = 0;
+    let mut steps: Vec<f64> = Vec::with_capacity(4);
+    let mut block_powers: Vec<f64> = vec![];
+
+    let mut channel = 0;
+    for sample in samples {
+        let sample = sample?;
+        peak_meters[channel].push(sample);
+        let filtered = filters[channel].process(sample as f64);
+        step_energy[channel] += filtered * filtered;
+
+        channel += 1;
+        if channel < channels {
+            continue
+        }
+        channel = 0;
+        step_frames += 1;
+
+        if step_frames == step_len {
+            let power: f64 = step_energy.iter()
+                .zip(weights.iter())
+                .map(|(energy, weight)| weight * energy / step_len as f64)
+                .sum();
+            steps.push(power);
+            if steps.len() > 4 {
+                steps.remove(0);
+            }
+            if steps.len() == 4 {
+                block_powers.push(steps.iter().sum::<f64>() / 4.0);
+            }
+            step_energy.iter_mut().for_each(|energy| *energy = 0.0);
+            step_frames = 0;
+        }
+    }
+
+    Ok(LoudnessResult {
+        integrated_lufs: gated_loudness(&block_powers),
+        true_peak: peak_meters.iter().map(|meter| meter.peak).fold(0.0, f32::max),
+    })
+}
+
+/// Applies the absolute (-70 LUFS) and relative (-10 LU) gates to the block powers.
+fn gated_loudness(block_powers: &[f64]) -> f64 {
+    const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+    const RELATIVE_GATE_LU: f64 = -10.0;
+
+    let above_absolute: Vec<f64> = block_powers.iter()
+        .copied()
+        .filter(|power| power_to_lufs(*power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if above_absolute.is_empty() {
+        return f64::NEG_INFINITY
+    }
+
+    let relative_gate = power_to_lufs(mean(&above_absolute)) + RELATIVE_GATE_LU;
+    let above_relative: Vec<f64> = above_absolute.into_iter()
+        .filter(|power| power_to_lufs(*power) > relative_gate)
+        .collect();
+    if above_relative.is_empty() {
+        return f64::NEG_INFINITY
+    }
+
+    power_to_lufs(mean(&above_relative))
+}
+
+#[inline]
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+#[inline]
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Gets the BS.1770 channel weights, assuming the standard WAV channel order for 5.0 and 5.1 layouts.
+fn channel_weights(channels: usize) -> Vec<f64> {
+    const SURROUND: f64 = 1.41;
+    match channels {
+        // L, R, C, Ls, Rs
+        5 => vec![1.0, 1.0, 1.0, SURROUND, SURROUND],
+        // L, R, C, LFE, Ls, Rs
+        6 => vec![1.0, 1.0, 1.0, 0.0, SURROUND, SURROUND],
+        n => vec![1.0; n],
+    }
+}
+
+/// The two-stage K-weighting filter (high shelf followed by the RLB high-pass).
+struct KWeightingFilter {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeightingFilter {
+    fn new(sample_rate: f64) -> Self {
+        // Coefficients are derived for the actual sample rate so that rates other than 48kHz are weighted correctly
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / sample_rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / sample_rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        Self {
+            shelf,
+            high_pass,
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, x: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(x))
+    }
+}
+
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            z: [0.0; 2],
+        }
+    }
+
+    /// Processes one sample (transposed direct form II).
+    #[inline]
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// Estimates the inter-sample peak of one channel by 4x polyphase oversampling.
+struct TruePeakMeter {
+    history: [f32; TAPS_PER_PHASE],
+    coefficients: [[f32; TAPS_PER_PHASE]; OVERSAMPLING - 1],
+    cursor: usize,
+    peak: f32,
+}
+
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+impl TruePeakMeter {
+    fn new() -> Self {
+        let mut coefficients = [[0.0; TAPS_PER_PHASE]; OVERSAMPLING - 1];
+        for (phase, taps) in coefficients.iter_mut().enumerate() {
+            for (tap, coefficient) in taps.iter_mut().enumerate() {
+                *coefficient = interpolation_tap(phase + 1, tap);
+            }
+        }
+
+        Self {
+            history: [0.0; TAPS_PER_PHASE],
+            coefficients,
+            cursor: 0,
+            peak: 0.0,
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, sample: f32) {
+        self.history[self.cursor] = sample;
+        self.cursor = (self.cursor + 1) % TAPS_PER_PHASE;
+        self.peak = self.peak.max(sample.abs());
+
+        for taps in self.coefficients.iter() {
+            let mut acc = 0.0;
+            for (tap, coefficient) in taps.iter().enumerate() {
+                // Walk the history from newest to oldest
+                let index = (self.cursor + TAPS_PER_PHASE - 1 - tap) % TAPS_PER_PHASE;
+                acc += self.history[index] * coefficient;
+            }
+            self.peak = self.peak.max(acc.abs());
+        }
+    }
+}
+
+/// Computes a tap of the Hann-windowed sinc interpolation filter for the given phase.
+#[inline]
+fn interpolation_tap(phase: usize, tap: usize) -> f32 {
+    let len = (TAPS_PER_PHASE * OVERSAMPLING) as f64;
+    // Position of this tap relative to the filter's center, in input samples
+    let n = (tap * OVERSAMPLING + OVERSAMPLING - phase) as f64;
+    let t = (n - len / 2.0) / OVERSAMPLING as f64;
+    let sinc = if t == 0.0 { 1.0 } else { (PI * t).sin() / (PI * t) };
+    let window = 0.5 - 0.5 * (2.0 * PI * n / len).cos();
+    (sinc * window) as f32
+}
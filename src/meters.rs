@@ -0,0 +1,83 @@
+use crate::Sample;
+
+/// Running per-channel level meters, updated by a `SampleIterator` as samples are returned.
+///
+/// See `SampleIterator::with_meters()`.
+#[derive(Debug, Clone)]
+pub struct Meters {
+    peaks: Vec<Sample>,
+    sums_of_squares: Vec<f64>,
+    /// Number of samples measured across all channels, which also tells which channel the next sample belongs to.
+    samples: u64,
+}
+
+impl Meters {
+    #[inline]
+    pub(crate) fn new(channels: usize) -> Self {
+        Self {
+            peaks: vec![0.0; channels],
+            sums_of_squares: vec![0.0; channels],
+            samples: 0,
+        }
+    }
+
+    /// Adds the next interleaved sample to the meter of its channel.
+    #[inline(always)]
+    pub(crate) fn measure(&mut self, sample: Sample) {
+        let channel = (self.samples % self.peaks.len().max(1) as u64) as usize;
+        if let (Some(peak), Some(sum)) = (self.peaks.get_mut(channel), self.sums_of_squares.get_mut(channel)) {
+            *peak = peak.max(sample.abs());
+            *sum += sample as f64 * sample as f64;
+        }
+        self.samples += 1;
+    }
+
+    /// Clears the measurements, so that later readings cover only the samples returned from now on.
+    ///
+    /// The channel of the next sample is still tracked, so this can be called partway through a frame.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.peaks.iter_mut().for_each(|peak| *peak = 0.0);
+        self.sums_of_squares.iter_mut().for_each(|sum| *sum = 0.0);
+        self.samples %= self.peaks.len().max(1) as u64;
+    }
+
+    /// Gets the number of channels being measured.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.peaks.len()
+    }
+
+    /// Gets the highest absolute sample value measured on `channel` as a linear amplitude, or 0.0 if `channel`
+    /// is out of range or nothing has been measured on it.
+    #[inline]
+    pub fn peak(&self, channel: usize) -> Sample {
+        self.peaks.get(channel).copied().unwrap_or(0.0)
+    }
+
+    /// Gets the peak level of `channel` in dBFS, which is negative infinity for a silent channel.
+    #[inline]
+    pub fn peak_dbfs(&self, channel: usize) -> Sample {
+        20.0 * self.peak(channel).log10()
+    }
+
+    /// Gets the root-mean-square level of the samples measured on `channel` as a linear amplitude, or 0.0 if
+    /// `channel` is out of range or nothing has been measured on it.
+    #[inline]
+    pub fn rms(&self, channel: usize) -> Sample {
+        let channels = self.peaks.len() as u64;
+        let sum = match self.sums_of_squares.get(channel) {
+            Some(sum) => *sum,
+            None => return 0.0,
+        };
+        // Samples are spread evenly over the channels, except for the first few of a partial frame
+        let count = self.samples / channels + if (channel as u64) < self.samples % channels { 1 } else { 0 };
+        if count == 0 { 0.0 } else { (sum / count as f64).sqrt() as Sample }
+    }
+
+    /// Gets the RMS level of `channel` in dBFS, which is negative infinity for a silent channel.
+    #[inline]
+    pub fn rms_dbfs(&self, channel: usize) -> Sample {
+        20.0 * self.rms(channel).log10()
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Read};
+
+use crate::{DecoderError, Endian, SampleIterator};
+
+/// Serializes decoded samples as interleaved 32-bit float PCM bytes in a chosen byte order.
+///
+/// Decoding errors are reported as I/O errors from `read()`. I/O errors from the underlying file are passed through
+/// as-is; any other error is wrapped in an `io::Error` of kind `InvalidData`.
+pub struct PcmReader {
+    samples: SampleIterator,
+    endian: Endian,
+    /// Bytes of the current sample that haven't been read yet.
+    pending: [u8; 4],
+    pending_cursor: usize,
+    /// An error to return from the next read, held back so that the bytes before it could be returned first.
+    error: Option<DecoderError>,
+}
+
+impl PcmReader {
+    #[inline]
+    pub(crate) fn new(samples: SampleIterator, endian: Endian) -> Self {
+        Self {
+            samples,
+            endian,
+            pending: [0; 4],
+            pending_cursor: 4,
+            error: None,
+        }
+    }
+}
+
+impl Read for PcmReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(err) = self.error.take() {
+            return Err(decoder_err_to_io_err(err))
+        }
+
+        let mut written = 0;
+        while written < buf.len() {
+            if self.pending_cursor == self.pending.len() {
+                let sample = match self.samples.next() {
+                    Some(Ok(sample)) => sample,
+                    // Return the bytes already written first, and the error on the next read
+                    Some(Err(err)) if written > 0 => {
+                        self.error = Some(err);
+                        break
+                    },
+                    Some(Err(err)) => return Err(decoder_err_to_io_err(err)),
+                    None => break,
+                };
+                self.pending = match self.endian {
+                    Endian::Big => sample.to_be_bytes(),
+                    Endian::Little => sample.to_le_bytes(),
+                };
+                self.pending_cursor = 0;
+            }
+
+            let n = (self.pending.len() - self.pending_cursor).min(buf.len() - written);
+            buf[written..written + n].copy_from_slice(&self.pending[self.pending_cursor..self.pending_cursor + n]);
+            self.pending_cursor += n;
+            written += n;
+        }
+        Ok(written)
+    }
+}
+
+#[inline]
+fn decoder_err_to_io_err(error: DecoderError) -> io::Error {
+    match error {
+        DecoderError::IOError(err) => err,
+        other => io::Error::new(io::ErrorKind::InvalidData, other),
+    }
+}
@@ -0,0 +1,166 @@
+use std::ops::Index;
+
+use crate::{DecoderError, Sample, SampleIterator};
+
+/// Decoded audio stored one channel after another, rather than interleaved.
+///
+/// All of channel 0's samples come first, followed by all of channel 1's, and so on, so each channel is a contiguous
+/// slice that SIMD code can process directly. Indexing by channel gives that channel's samples, so
+/// `planar[channel][frame]` reads a single sample.
+#[derive(Debug, Clone)]
+pub struct PlanarSamples {
+    channels: usize,
+    sample_rate: u32,
+    frames: usize,
+    samples: Vec<Sample>,
+}
+
+impl PlanarSamples {
+    /// Rearranges interleaved samples into planes. A partial frame at the end is dropped.
+    pub(crate) fn from_interleaved(interleaved: &[Sample], channels: usize, sample_rate: u32) -> Self {
+        let frames = interleaved.len().checked_div(channels).unwrap_or(0);
+        let mut samples = vec![0.0; frames * channels];
+        for (frame, samples_in_frame) in interleaved.chunks_exact(channels.max(1)).take(frames).enumerate() {
+            for (channel, sample) in samples_in_frame.iter().enumerate() {
+                samples[channel * frames + frame] = *sample;
+            }
+        }
+        Self { channels, sample_rate, frames, samples }
+    }
+
+    /// Gets the number of channels in the audio.
+    #[inline]
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Gets the sample rate of the audio.
+    #[inline]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Gets the number of frames, which is also the length of each channel.
+    #[inline]
+    pub fn len_frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Returns `true` if there are no frames.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    /// Gets the samples of the specified channel, or `None` if it's out of range.
+    #[inline]
+    pub fn channel(&self, channel: usize) -> Option<&[Sample]> {
+        if channel >= self.channels {
+            return None
+        }
+        Some(&self.samples[channel * self.frames..(channel + 1) * self.frames])
+    }
+
+    /// Iterates over the channels, each a slice of `len_frames()` samples.
+    #[inline]
+    pub fn planes(&self) -> impl Iterator<Item = &[Sample]> + '_ {
+        (0..self.channels).map(move |channel| &self.samples[channel * self.frames..(channel + 1) * self.frames])
+    }
+
+    /// Gets all of the samples, one channel after another.
+    #[inline]
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Consumes the audio and returns its samples, one channel after another.
+    #[inline]
+    pub fn into_vec(self) -> Vec<Sample> {
+        self.samples
+    }
+}
+
+impl Index<usize> for PlanarSamples {
+    type Output = [Sample];
+
+    /// Gets the samples of the specified channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channel` is out of range.
+    #[inline]
+    fn index(&self, channel: usize) -> &Self::Output {
+        match self.channel(channel) {
+            Some(samples) => samples,
+            None => panic!("channel {} is out of range for audio with {} channels", channel, self.channels),
+        }
+    }
+}
+
+/// Iterates over decoded audio in fixed-size blocks, each stored as `PlanarSamples`.
+///
+/// Every block holds the requested number of frames except the last, which may be shorter. Only one block is held
+/// in memory at a time. If decoding fails, the frames decoded before the error are returned as a block first, and
+/// iteration stops after the error.
+pub struct PlanarBlocks {
+    samples: SampleIterator,
+    block_frames: usize,
+    /// Reused between blocks to gather the interleaved samples before they're split into planes.
+    interleaved: Vec<Sample>,
+    error: Option<DecoderError>,
+    finished: bool,
+}
+
+impl PlanarBlocks {
+    #[inline]
+    pub(crate) fn new(samples: SampleIterator, block_frames: usize) -> Self {
+        Self {
+            interleaved: Vec::with_capacity(block_frames * samples.channels()),
+            samples,
+            block_frames,
+            error: None,
+            finished: false,
+        }
+    }
+
+    /// Gets the number of frames in each full block.
+    #[inline]
+    pub fn block_frames(&self) -> usize {
+        self.block_frames
+    }
+}
+
+impl Iterator for PlanarBlocks {
+    type Item = Result<PlanarSamples, DecoderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err))
+        }
+        if self.finished {
+            return None
+        }
+
+        let (channels, sample_rate) = (self.samples.channels(), self.samples.sample_rate());
+        let block_len = self.block_frames * channels.max(1);
+        self.interleaved.clear();
+        for sample in self.samples.by_ref().take(block_len) {
+            match sample {
+                Ok(sample) => self.interleaved.push(sample),
+                Err(err) => {
+                    self.error = Some(err);
+                    break
+                },
+            }
+        }
+        if self.interleaved.len() < block_len {
+            self.finished = true;
+        }
+
+        let block = PlanarSamples::from_interleaved(&self.interleaved, channels, sample_rate);
+        if block.is_empty() {
+            return self.error.take().map(Err)
+        }
+        Some(Ok(block))
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::{Arc, Mutex};
+
+use crate::Sample;
+
+/// The most buffers of each type that a pool keeps. Buffers returned beyond this are freed.
+const MAX_POOLED_BUFFERS: usize = 64;
+
+/// A pool of decoding buffers that can be shared between decoders, so that decoding many short files in a row
+/// reuses the same allocations instead of making new ones for every file.
+///
+/// Set `DecoderOptions::buffer_pool` to a pool to have decoders draw their block buffers from it; the buffers are
+/// returned when the `SampleIterator` is dropped. Cloning a pool gives another handle to the same buffers, and the pool
+/// can be used from several threads at once.
+///
+/// FLAC files, and WAV files whose samples creak decodes itself, use pooled buffers. The MP3 and Vorbis backends
+/// allocate their packet buffers internally, so they don't benefit from a pool.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeBufferPool {
+    shelves: Arc<Mutex<Shelves>>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Shelves {
+    bytes: Vec<Vec<u8>>,
+    ints: Vec<Vec<i32>>,
+    samples: Vec<Vec<Sample>>,
+}
+
+/// An element type of the buffers a pool holds.
+pub(crate) trait Pooled: Sized {
+    fn shelf(shelves: &mut Shelves) -> &mut Vec<Vec<Self>>;
+}
+
+impl Pooled for u8 {
+    #[inline]
+    fn shelf(shelves: &mut Shelves) -> &mut Vec<Vec<Self>> {
+        &mut shelves.bytes
+    }
+}
+
+impl Pooled for i32 {
+    #[inline]
+    fn shelf(shelves: &mut Shelves) -> &mut Vec<Vec<Self>> {
+        &mut shelves.ints
+    }
+}
+
+impl Pooled for Sample {
+    #[inline]
+    fn shelf(shelves: &mut Shelves) -> &mut Vec<Vec<Self>> {
+        &mut shelves.samples
+    }
+}
+
+impl DecodeBufferPool {
+    /// Creates an empty pool.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the number of buffers currently held by the pool and not in use by a decoder.
+    #[inline]
+    pub fn len(&self) -> usize {
+        let shelves = self.lock();
+        shelves.bytes.len() + shelves.ints.len() + shelves.samples.len()
+    }
+
+    /// Returns `true` if the pool holds no buffers.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes an empty buffer with room for at least `capacity` elements, reusing a pooled one if there are any.
+    pub(crate) fn take<T: Pooled>(&self, capacity: usize) -> Vec<T> {
+        let mut buffer = T::shelf(&mut self.lock()).pop().unwrap_or_default();
+        buffer.clear();
+        buffer.reserve(capacity);
+        buffer
+    }
+
+    /// Returns a buffer to the pool, unless it never allocated or the pool is full.
+    pub(crate) fn give<T: Pooled>(&self, buffer: Vec<T>) {
+        if buffer.capacity() == 0 {
+            return
+        }
+        let mut shelves = self.lock();
+        let shelf = T::shelf(&mut shelves);
+        if shelf.len() < MAX_POOLED_BUFFERS {
+            shelf.push(buffer);
+        }
+    }
+
+    #[inline]
+    fn lock(&self) -> std::sync::MutexGuard<'_, Shelves> {
+        // The shelves are never left half-updated, so a panic on another thread doesn't invalidate them
+        self.shelves.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Takes a buffer from `pool`, or allocates a new one if there's no pool.
+#[inline]
+pub(crate) fn take_buffer<T: Pooled>(pool: Option<&DecodeBufferPool>, capacity: usize) -> Vec<T> {
+    match pool {
+        Some(pool) => pool.take(capacity),
+        None => Vec::with_capacity(capacity),
+    }
+}
+
+/// Returns a buffer to `pool`, if there is one.
+#[inline]
+pub(crate) fn give_buffer<T: Pooled>(pool: Option<&DecodeBufferPool>, buffer: Vec<T>) {
+    if let Some(pool) = pool {
+        pool.give(buffer);
+    }
+}
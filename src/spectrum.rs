@@ -0,0 +1,130 @@
+use std::{collections::VecDeque, f64::consts::PI};
+
+use crate::{DecoderError, Sample, SampleIterator};
+
+/// Iterates over the magnitude spectra of overlapping windows of a mono stream.
+///
+/// Each item holds `fft_size / 2 + 1` magnitudes, from DC up to the Nyquist frequency. Bin `k` is centered on
+/// `k * sample_rate / fft_size` Hz. Magnitudes are scaled so that a full-scale sine wave centered on a bin reads 1.0.
+///
+/// Only whole windows are analyzed, so any samples after the last full window are ignored.
+pub struct SpectrumFrames {
+    samples: SampleIterator,
+    fft: Fft,
+    hop: usize,
+    window: Vec<f64>,
+    /// Scale factor that undoes the energy lost to the window.
+    scale: f64,
+    /// The samples of the current window.
+    buffer: VecDeque<Sample>,
+    /// Samples to skip before the next window starts, when the hop is longer than the window.
+    skip: usize,
+    finished: bool,
+}
+
+impl SpectrumFrames {
+    pub(crate) fn new(samples: SampleIterator, fft_size: usize, hop: usize) -> Self {
+        // Periodic Hann window, which overlaps cleanly at hops of half the window size
+        let window: Vec<f64> = (0..fft_size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / fft_size as f64).cos())
+            .collect();
+        let scale = 2.0 / window.iter().sum::<f64>();
+        Self {
+            samples,
+            fft: Fft::new(fft_size),
+            hop,
+            window,
+            scale,
+            buffer: VecDeque::with_capacity(fft_size),
+            skip: 0,
+            finished: false,
+        }
+    }
+}
+
+impl Iterator for SpectrumFrames {
+    type Item = Result<Vec<f32>, DecoderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None
+        }
+
+        while self.skip > 0 || self.buffer.len() < self.window.len() {
+            match self.samples.next() {
+                Some(Ok(_)) if self.skip > 0 => self.skip -= 1,
+                Some(Ok(sample)) => self.buffer.push_back(sample),
+                Some(Err(err)) => {
+                    self.finished = true;
+                    return Some(Err(err))
+                },
+                None => {
+                    self.finished = true;
+                    return None
+                },
+            }
+        }
+
+        let (scale, bins) = (self.scale, self.window.len() / 2 + 1);
+        let windowed = self.buffer.iter().zip(&self.window).map(|(sample, gain)| (*sample as f64 * gain, 0.0));
+        let magnitudes = self.fft.transform(windowed)[..bins]
+            .iter()
+            .map(|(re, im)| ((re * re + im * im).sqrt() * scale) as f32)
+            .collect();
+
+        let advance = self.hop.min(self.buffer.len());
+        self.buffer.drain(..advance);
+        self.skip = self.hop - advance;
+        Some(Ok(magnitudes))
+    }
+}
+
+/// An iterative radix-2 FFT of a fixed power-of-two size.
+struct Fft {
+    /// `exp(-2πik/n)` for each `k` below `n / 2`.
+    twiddles: Vec<(f64, f64)>,
+    /// Bit-reversed index of each input position.
+    reversed: Vec<usize>,
+    data: Vec<(f64, f64)>,
+}
+
+impl Fft {
+    fn new(size: usize) -> Self {
+        let bits = size.trailing_zeros();
+        Self {
+            twiddles: (0..size / 2)
+                .map(|k| {
+                    let angle = -2.0 * PI * k as f64 / size as f64;
+                    (angle.cos(), angle.sin())
+                })
+                .collect(),
+            reversed: (0..size).map(|i| if bits == 0 { 0 } else { i.reverse_bits() >> (usize::BITS - bits) }).collect(),
+            data: vec![(0.0, 0.0); size],
+        }
+    }
+
+    /// Transforms `size` complex input values, returning the spectrum.
+    fn transform<I: Iterator<Item = (f64, f64)>>(&mut self, input: I) -> &[(f64, f64)] {
+        for (i, value) in input.enumerate() {
+            self.data[self.reversed[i]] = value;
+        }
+
+        let size = self.data.len();
+        let mut len = 2;
+        while len <= size {
+            let stride = size / len;
+            for start in (0..size).step_by(len) {
+                for k in 0..len / 2 {
+                    let (wr, wi) = self.twiddles[k * stride];
+                    let (ar, ai) = self.data[start + k];
+                    let (br, bi) = self.data[start + k + len / 2];
+                    let (tr, ti) = (br * wr - bi * wi, br * wi + bi * wr);
+                    self.data[start + k] = (ar + tr, ai + ti);
+                    self.data[start + k + len / 2] = (ar - tr, ai - ti);
+                }
+            }
+            len *= 2;
+        }
+        &self.data
+    }
+}
@@ -1,20 +1,76 @@
 // Simple program that reads an audio file and dumps its samples in 32-bit float to stdout
+//
+// Samples are written in little-endian byte order, unless `--big-endian` is passed after the file name.
+// Pass `-` as the file name to read a WAV stream from stdin.
+//
+// Exits with status 2 if the format is unsupported or unrecognized, 3 on an I/O error, 4 if the audio is corrupt
+// or truncated, 64 if the arguments are missing or invalid, and 1 on any other error.
 
-use std::{env, io, io::Write};
-use creak;
+use std::{env, error::Error, fmt, io, process::ExitCode};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+use creak::DecoderError;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(exit_status(err.as_ref()))
+        }
+    }
+}
+
+/// An error in the command-line arguments.
+#[derive(Debug)]
+struct UsageError(String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\nUsage: creak-raw <file | -> [--little-endian | --big-endian]", self.0)
+    }
+}
+
+impl Error for UsageError {}
+
+/// Picks the exit status for an error, so scripts can tell the kinds of failure apart.
+fn exit_status(err: &(dyn Error + 'static)) -> u8 {
+    if err.is::<UsageError>() {
+        // EX_USAGE from sysexits.h
+        return 64
+    }
+    // Decoding errors raised while copying samples to stdout arrive wrapped in an `io::Error`
+    let err = match err.downcast_ref::<io::Error>().and_then(|err| err.get_ref()) {
+        Some(inner) if inner.is::<DecoderError>() => inner,
+        _ => err,
+    };
+    match err.downcast_ref::<DecoderError>() {
+        Some(DecoderError::NoExtension | DecoderError::UnsupportedExtension(_) | DecoderError::DisabledExtension { .. } | DecoderError::UnrecognizedFormat | DecoderError::Protected(_)) => 2,
+        Some(DecoderError::IOError(_)) => 3,
+        Some(DecoderError::FormatError { .. } | DecoderError::IncompleteData | DecoderError::EmptyStream(_)) => 4,
+        Some(_) => 1,
+        None if err.is::<io::Error>() => 3,
+        None => 1,
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
     // Get a file name from the cmdline args
     let file_name = match env::args().nth(1) {
         Some(arg) => arg,
-        None => {
-            eprintln!("No audio file specified!");
-            return Ok(())
-        }
+        None => return Err(UsageError("No audio file specified!".to_owned()).into()),
+    };
+
+    let endian = match env::args().nth(2).as_deref() {
+        None | Some("--little-endian") => creak::Endian::Little,
+        Some("--big-endian") => creak::Endian::Big,
+        Some(other) => return Err(UsageError(format!("Unknown option '{}'; expected --little-endian or --big-endian", other)).into()),
     };
 
     // Open an audio file of any supported format with one function call
-    let decoder = creak::Decoder::open(&file_name)?;
+    let decoder = match file_name.as_str() {
+        "-" => creak::Decoder::from_wav_stream(io::stdin())?,
+        path => creak::Decoder::open(path)?,
+    };
 
     // Print basic audio info to stderr
     let info = decoder.info();
@@ -24,15 +80,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         info.sample_rate()
     );
 
-    let mut stdout = io::stdout();
-    let mut num_samples: usize = 0;
-
     // Dump all samples to stdout
-    for sample in decoder.into_samples()? {
-        stdout.write(&sample?.to_le_bytes())?;
-        num_samples += 1;
-    }
+    let mut pcm = decoder.into_samples()?.into_pcm_reader(endian);
+    let num_bytes = io::copy(&mut pcm, &mut io::stdout())?;
 
-    eprintln!("{} samples(s) read.", num_samples);
+    eprintln!("{} samples(s) read.", num_bytes / 4);
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use crate::dither::Quantizer;
+use crate::{Decoder, DecoderError, Dither};
+
+/// Describes the WAV file written by `transcode_to_wav()`.
+#[derive(Debug, Copy, Clone)]
+pub struct WavOutputSpec {
+    /// The bit depth of the integer samples to write. Must be 8, 16, 24, or 32.
+    pub bits_per_sample: u16,
+    /// The sample rate to write, or `None` to keep the sample rate of the source.
+    ///
+    /// Resampling isn't supported yet, so this must match the source if it's set.
+    pub sample_rate: Option<u32>,
+    /// The dithering applied when reducing samples to `bits_per_sample`.
+    pub dither: Dither,
+}
+
+impl Default for WavOutputSpec {
+    /// Creates a spec for 16-bit output at the source sample rate without dithering.
+    #[inline]
+    fn default() -> Self {
+        Self {
+            bits_per_sample: 16,
+            sample_rate: None,
+            dither: Dither::None,
+        }
+    }
+}
+
+/// Decodes the audio file at `src` and writes it to `dst` as an integer PCM WAV file.
+///
+/// If decoding fails partway through, the samples decoded so far are left in `dst` and the error is returned.
+pub fn transcode_to_wav<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q, spec: WavOutputSpec) -> Result<(), DecoderError> {
+    if !matches!(spec.bits_per_sample, 8 | 16 | 24 | 32) {
+        return Err(DecoderError::InvalidArgument(format!(
+            "WAV output bit depth must be 8, 16, 24, or 32, but {} was requested", spec.bits_per_sample
+        )))
+    }
+
+    let decoder = Decoder::open(src)?;
+    let info = decoder.info();
+    if let Some(sample_rate) = spec.sample_rate.filter(|&rate| rate != info.sample_rate()) {
+        return Err(DecoderError::InvalidArgument(format!(
+            "cannot write {}Hz audio at {}Hz because resampling is not supported", info.sample_rate(), sample_rate
+        )))
+    }
+
+    let wav_spec = hound::WavSpec {
+        channels: info.channels() as u16,
+        sample_rate: info.sample_rate(),
+        bits_per_sample: spec.bits_per_sample,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(dst, wav_spec)?;
+    let mut quantizer = Quantizer::new(spec.bits_per_sample as u32, info.channels(), spec.dither);
+    for sample in decoder.into_samples()? {
+        writer.write_sample(quantizer.quantize(sample?))?;
+    }
+    Ok(writer.finalize()?)
+}
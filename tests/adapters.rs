@@ -0,0 +1,69 @@
+#![cfg(feature = "wav")]
+
+mod common;
+
+use std::time::Duration;
+
+use creak::{AudioFormat, Decoder, DecoderError};
+
+fn open() -> Decoder {
+    Decoder::open(common::sample("sine_1k_i16_44100.wav")).unwrap()
+}
+
+#[test]
+fn remapped_info_survives_gain_and_other_adapters() {
+    let samples = open().remap_channels(2).unwrap()
+        .with_gain(0.5)
+        .with_gain_db(0.0)
+        .with_clamping()
+        .with_meters();
+    let info = samples.info();
+    assert_eq!(info.channels(), 2);
+    assert_eq!(samples.channels(), 2);
+    assert_eq!(info.sample_rate(), 44100);
+    assert_eq!(info.format(), AudioFormat::Wav);
+    assert_eq!(info.total_frames(), Some(44100));
+
+    let original = common::decode(open());
+    let remapped: Vec<f32> = samples.collect::<Result<_, _>>().unwrap();
+    assert_eq!(remapped.len(), original.len() * 2);
+    for (frame, sample) in remapped.chunks_exact(2).zip(&original) {
+        assert_eq!(frame, [sample * 0.5, sample * 0.5]);
+    }
+}
+
+#[test]
+fn remapped_streams_mix_with_each_other_but_not_with_the_source_layout() {
+    let mixed = open().remap_channels(2).unwrap().mix(open().remap_channels(2).unwrap().with_gain(0.5)).unwrap();
+    assert_eq!(mixed.info().channels(), 2);
+    assert_eq!(mixed.info().total_frames(), Some(44100));
+
+    let err = open().remap_channels(2).unwrap().mix(open().into_samples().unwrap()).unwrap_err();
+    assert!(matches!(err, DecoderError::InvalidArgument(_)));
+}
+
+#[test]
+fn mixed_length_follows_the_adapters_of_both_streams() {
+    let mixed = open().fit_frames(1000, 0.0).unwrap().mix(open().into_samples().unwrap().with_gain(0.5)).unwrap();
+    assert_eq!(mixed.info().total_frames(), Some(1000));
+    assert_eq!(mixed.count(), 1000);
+
+    let padded = open().fit_frames(1000, 0.0).unwrap().mix_padded(open().into_samples().unwrap()).unwrap();
+    assert_eq!(padded.info().total_frames(), Some(44100));
+
+    let trimmed = open().trim_silence(-60.0).unwrap().with_gain(0.5);
+    assert_eq!(trimmed.info().total_frames(), None);
+    let unknown = trimmed.mix_padded(open().into_samples().unwrap()).unwrap();
+    assert_eq!(unknown.info().total_frames(), None);
+}
+
+#[test]
+fn fade_out_after_seek_ends_at_the_end_of_the_stream() {
+    let mut decoder = open();
+    let total_frames = decoder.info().total_frames().unwrap();
+    decoder.seek(total_frames - 100).unwrap();
+    let samples: Vec<_> = decoder.with_fades(Duration::ZERO, Duration::from_millis(1)).unwrap().map(Result::unwrap).collect();
+    assert_eq!(samples.len(), 100);
+    assert_eq!(samples[0], common::decode(open())[total_frames as usize - 100]);
+    assert!(samples[99].abs() < 1e-6, "last sample is {}", samples[99]);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::io::Cursor;
+
+use creak::{AudioFormat, AudioInfo, CustomBackend, Decoder, DecoderError, DecoderOptions, FormatBackend, Sample};
+
+/// A backend for a made-up format of silence, which can seek if `seekable` is set.
+struct Silence {
+    seekable: bool,
+    position: u64,
+}
+
+impl FormatBackend for Silence {
+    fn info(&self) -> AudioInfo {
+        AudioInfo::new(AudioFormat::Custom, 44100, 1, Some(100))
+    }
+
+    fn into_samples(self: Box<Self>) -> Result<Box<dyn Iterator<Item = Result<Sample, DecoderError>> + Send>, DecoderError> {
+        Ok(Box::new((self.position..100).map(|_| Ok(0.0))))
+    }
+
+    fn position(&self) -> Option<u64> {
+        if self.seekable { Some(self.position) } else { None }
+    }
+
+    fn seek(&mut self, frame: u64) -> Result<(), DecoderError> {
+        self.position = frame.min(100);
+        Ok(())
+    }
+
+    fn random_access(&self) -> bool {
+        self.seekable
+    }
+}
+
+fn open_silence(seekable: bool) -> Decoder {
+    let backend = CustomBackend::new("silence", move |_| Ok(Box::new(Silence { seekable, position: 0 }) as Box<dyn FormatBackend>))
+        .with_magic(&b"SILENCE"[..]);
+    let options = DecoderOptions {
+        custom_backends: vec![backend],
+        ..DecoderOptions::default()
+    };
+    Decoder::from_reader_with_options(Cursor::new(b"SILENCE".to_vec()), options).unwrap()
+}
+
+#[test]
+fn custom_backends_report_their_own_random_access() {
+    let capabilities = open_silence(true).capabilities();
+    assert!(capabilities.seekable());
+    assert!(capabilities.random_access());
+
+    let capabilities = open_silence(false).capabilities();
+    assert!(!capabilities.seekable());
+    assert!(!capabilities.random_access());
+}
+
+#[test]
+#[cfg(feature = "wav")]
+fn wav_files_have_random_access_but_wav_streams_do_not() {
+    let capabilities = Decoder::open(common::sample("sine_1k_i16_44100.wav")).unwrap().capabilities();
+    assert!(capabilities.seekable());
+    assert!(capabilities.random_access());
+
+    let file = std::fs::File::open(common::sample("sine_1k_i16_44100.wav")).unwrap();
+    let capabilities = Decoder::from_wav_stream(file).unwrap().capabilities();
+    assert!(!capabilities.seekable());
+    assert!(!capabilities.random_access());
+}
+
+#[test]
+#[cfg(feature = "mp3")]
+fn mp3_has_no_random_access() {
+    let capabilities = Decoder::open(common::sample("sine_1k_44100.mp3")).unwrap().capabilities();
+    assert!(!capabilities.seekable());
+    assert!(!capabilities.random_access());
+}
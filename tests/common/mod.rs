@@ -0,0 +1,25 @@
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+/// Gets the path of a file in the `samples` directory.
+pub fn sample(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("samples").join(name)
+}
+
+/// Writes `data` to a file named `name` in a scratch directory, and returns its path.
+pub fn write_temp(name: &str, data: &[u8]) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+/// Decodes every sample, panicking on the first error.
+pub fn decode(decoder: creak::Decoder) -> Vec<f32> {
+    decoder.into_samples().unwrap().collect::<Result<_, _>>().unwrap()
+}
+
+/// The value of sample `frame` of a sine wave at 1 kHz sampled at 44.1 kHz.
+pub fn sine_1k(frame: usize, amplitude: f64) -> f32 {
+    (amplitude * (2.0 * std::f64::consts::PI * 1000.0 * frame as f64 / 44100.0).sin()) as f32
+}
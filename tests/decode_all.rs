@@ -0,0 +1,28 @@
+#![cfg(feature = "flac")]
+
+mod common;
+
+use creak::Decoder;
+
+#[test]
+fn forged_flac_length_does_not_reserve_it() {
+    // Claim 2^36 - 1 frames in STREAMINFO, far more than the file holds. The 36-bit count starts in the low nibble of
+    // the 14th byte of the block
+    let mut file = std::fs::read(common::sample("sine_1k_44100.flac")).unwrap();
+    file[21] |= 0x0f;
+    file[22..26].copy_from_slice(&[0xff; 4]);
+    let path = common::write_temp("forged_length.flac", &file);
+    let decoder = Decoder::open(path).unwrap();
+    assert_eq!(decoder.info().total_frames(), Some((1 << 36) - 1));
+    // The file itself decodes fine until it runs out
+    let _ = decoder.decode_all();
+}
+
+#[test]
+fn forged_flac_length_does_not_reserve_peaks_for_it() {
+    let mut file = std::fs::read(common::sample("sine_1k_44100.flac")).unwrap();
+    file[21] |= 0x0f;
+    file[22..26].copy_from_slice(&[0xff; 4]);
+    let path = common::write_temp("forged_length_peaks.flac", &file);
+    let _ = Decoder::open(path).unwrap().compute_peaks(1);
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "dsd")]
+
+mod common;
+
+use creak::{Decoder, DecoderError};
+
+/// Builds a DSDIFF file from chunks, each given as its ID, declared length, and contents.
+fn dff(chunks: &[(&[u8; 4], u64, &[u8])]) -> Vec<u8> {
+    let mut body = b"DSD ".to_vec();
+    for (id, len, contents) in chunks {
+        body.extend_from_slice(&id[..]);
+        body.extend_from_slice(&len.to_be_bytes());
+        body.extend_from_slice(contents);
+    }
+    let mut file = b"FRM8".to_vec();
+    file.extend_from_slice(&(body.len() as u64).to_be_bytes());
+    file.extend_from_slice(&body);
+    file
+}
+
+#[test]
+fn dff_chunk_shorter_than_its_fields_is_rejected() {
+    for id in [b"PROP", b"FS  ", b"CHNL", b"CMPR"] {
+        let path = common::write_temp("short_chunk.dff", &dff(&[(id, 1, b"SND \0\0\0\0")]));
+        match Decoder::open(path) {
+            Err(DecoderError::FormatError { message, .. }) => assert!(message.contains("must be at least"), "{}", message),
+            other => panic!("expected a FormatError for a short {:?} chunk, but got {:?}", id, other.map(|_| ())),
+        }
+    }
+}
+
+/// Builds a stereo DSF file with the given `fmt` chunk length and block size.
+fn dsf(fmt_len: u64, block_size: u32) -> Vec<u8> {
+    let mut file = b"DSD \x1c\0\0\0\0\0\0\0".to_vec();
+    file.extend_from_slice(&[0; 16]);
+    file.extend_from_slice(b"fmt ");
+    file.extend_from_slice(&fmt_len.to_le_bytes());
+    for field in [1u32, 0, 2, 2, 2_822_400, 1] {
+        file.extend_from_slice(&field.to_le_bytes());
+    }
+    file.extend_from_slice(&0u64.to_le_bytes());
+    file.extend_from_slice(&block_size.to_le_bytes());
+    file.extend_from_slice(&[0; 4]);
+    file.extend_from_slice(b"data\x0c\0\0\0\0\0\0\0");
+    file
+}
+
+#[test]
+fn dsf_block_size_other_than_4096_is_rejected() {
+    let path = common::write_temp("huge_block.dsf", &dsf(0x34, 0xffff_fff0));
+    match Decoder::open(path) {
+        Err(DecoderError::FormatError { message, .. }) => assert!(message.contains("block size"), "{}", message),
+        other => panic!("expected a FormatError, but got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn dsf_fmt_length_overflowing_its_offset_is_rejected() {
+    let path = common::write_temp("huge_fmt.dsf", &dsf(u64::MAX - 10, 4096));
+    match Decoder::open(path) {
+        Err(DecoderError::FormatError { message, .. }) => assert!(message.contains("fmt chunk length"), "{}", message),
+        other => panic!("expected a FormatError, but got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn dff_sound_data_length_overflowing_the_bit_count_is_rejected() {
+    let file = dff(&[(b"FS  ", 4, &2_822_400u32.to_be_bytes()), (b"CHNL", 2, &1u16.to_be_bytes()), (b"DSD ", u64::MAX, &[0; 16])]);
+    let path = common::write_temp("huge_sound_data.dff", &file);
+    match Decoder::open(path) {
+        Err(DecoderError::FormatError { message, .. }) => assert!(message.contains("too large"), "{}", message),
+        other => panic!("expected a FormatError, but got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use creak::{AudioFormat, Decoder, DecoderError, Endian, RawAudioSpec, RawSampleFormat};
+
+#[test]
+fn empty_file_fails_for_every_extension() {
+    let formats = [
+        ("wav", AudioFormat::Wav, "WAV"),
+        ("ogg", AudioFormat::Vorbis, "Vorbis"),
+        ("oga", AudioFormat::Vorbis, "Vorbis"),
+        ("ogv", AudioFormat::Vorbis, "Vorbis"),
+        ("mp3", AudioFormat::Mp3, "MP3"),
+        ("flac", AudioFormat::Flac, "FLAC"),
+        ("dsf", AudioFormat::Dsd, "DSD"),
+        ("dff", AudioFormat::Dsd, "DSD"),
+    ];
+    let supported = creak::all_supported_extensions();
+    for (ext, expected_format, name) in formats.iter().filter(|(ext, ..)| supported.contains(ext)) {
+        let path = common::write_temp(&format!("empty.{}", ext), &[]);
+        match Decoder::open(path) {
+            Err(err @ DecoderError::EmptyStream(format)) => {
+                assert_eq!(format, *expected_format, "wrong format for .{}", ext);
+                assert!(err.to_string().contains(name), "message for .{} doesn't name {}: {}", ext, name, err);
+            },
+            Err(err) => panic!("opening an empty .{} file failed with {:?}", ext, err),
+            Ok(_) => panic!("opening an empty .{} file succeeded", ext),
+        }
+    }
+}
+
+#[test]
+fn empty_raw_file_fails() {
+    let path = common::write_temp("empty.raw", &[]);
+    match Decoder::open_raw(path, RawAudioSpec::pcm(44100, 2, RawSampleFormat::Signed16, Endian::Little)) {
+        Err(err @ DecoderError::EmptyStream(AudioFormat::Raw)) => assert!(err.to_string().contains("Raw")),
+        Err(err) => panic!("opening an empty raw file failed with {:?}", err),
+        Ok(_) => panic!("opening an empty raw file succeeded"),
+    }
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "flac")]
+
+mod common;
+
+use creak::Decoder;
+
+#[test]
+fn truncated_stream_ends_with_incomplete_data() {
+    let file = std::fs::read(common::sample("sine_1k_44100.flac")).unwrap();
+    let path = common::write_temp("truncated.flac", &file[..file.len() / 2]);
+    let results: Vec<_> = Decoder::open(path).unwrap().into_samples().unwrap().collect();
+    assert!(matches!(results.last(), Some(Err(creak::DecoderError::IncompleteData))));
+    assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+}
+
+#[test]
+fn stream_cut_between_frames_ends_with_incomplete_data() {
+    // Cut the file just before its last frame, so every frame that's left decodes cleanly and only STREAMINFO's sample
+    // count shows that anything is missing
+    let file = std::fs::read(common::sample("sine_1k_44100.flac")).unwrap();
+    let last_frame = file.windows(2).rposition(|sync| sync == [0xff, 0xf8]).unwrap();
+    let path = common::write_temp("cut_between_frames.flac", &file[..last_frame]);
+    let results: Vec<_> = Decoder::open(path).unwrap().into_samples().unwrap().collect();
+    assert!(matches!(results.last(), Some(Err(creak::DecoderError::IncompleteData))));
+    assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+}
+
+#[test]
+fn zero_sample_rate_is_rejected() {
+    // The 20-bit sample rate starts at the 11th byte of STREAMINFO. claxon rejects it before creak's own check does.
+    // FLAC stores the channel count minus one, so a stream can't declare zero channels
+    let mut file = std::fs::read(common::sample("sine_1k_44100.flac")).unwrap();
+    file[18] = 0;
+    file[19] = 0;
+    file[20] &= 0x0f;
+    let path = common::write_temp("zero_sample_rate.flac", &file);
+    match Decoder::open(path) {
+        Err(err @ creak::DecoderError::FormatError { .. }) => assert!(err.to_string().contains("sample rate"), "{}", err),
+        Err(err) => panic!("failed with {:?}", err),
+        Ok(_) => panic!("opened a file with a sample rate of 0 Hz"),
+    }
+}
+
+/// Gets sample `frame` of a 1 kHz sine at 44.1 kHz, quantized as the fixtures are: rounded to the nearest multiple of
+/// `quantum` at the given bit depth.
+fn quantized_sine(frame: usize, amplitude: f64, bits: u32, quantum: f64) -> f32 {
+    let peak = ((1u32 << (bits - 1)) - 1) as f64;
+    let sine = amplitude * (2.0 * std::f64::consts::PI * 1000.0 * frame as f64 / 44100.0).sin();
+    ((sine * peak / quantum).round() * quantum / peak) as f32
+}
+
+/// Decodes a fixture and checks it against the sine waves it was written from, one per channel.
+fn assert_decodes_to_sine(name: &str, bits: u32, amplitudes: &[f64], quantum: f64) {
+    let decoder = Decoder::open(common::sample(name)).unwrap();
+    assert_eq!(decoder.channels(), amplitudes.len());
+    assert_eq!(decoder.info().total_frames(), Some(4410));
+    let samples = common::decode(decoder);
+    assert_eq!(samples.len(), 4410 * amplitudes.len());
+    for (i, sample) in samples.iter().enumerate() {
+        let (frame, channel) = (i / amplitudes.len(), i % amplitudes.len());
+        let expected = quantized_sine(frame, amplitudes[channel], bits, quantum);
+        assert!((sample - expected).abs() < 1e-6, "frame {} of channel {} decoded to {}, not {}", frame, channel, sample, expected);
+    }
+    assert!(Decoder::open(common::sample(name)).unwrap().verify_md5().unwrap());
+}
+
+#[test]
+fn decodes_8_bit() {
+    assert_decodes_to_sine("sine_1k_i8_44100.flac", 8, &[0.5], 1.0);
+}
+
+#[test]
+fn decodes_16_bit_stereo() {
+    assert_decodes_to_sine("sine_1k_i16_stereo_44100.flac", 16, &[0.5, 0.25], 1.0);
+}
+
+#[test]
+fn decodes_24_bit() {
+    assert_decodes_to_sine("sine_1k_i24_44100.flac", 24, &[0.5], 1.0);
+}
+
+#[test]
+fn decodes_wasted_bits() {
+    // Every sample is a multiple of 8, and each subframe stores them with its 3 wasted low bits removed
+    assert_decodes_to_sine("sine_1k_i16_wasted_44100.flac", 16, &[0.5], 8.0);
+}
+
+#[test]
+fn rejects_32_bit() {
+    // The 5-bit depth field, which holds the depth minus one, spans the 13th and 14th bytes of STREAMINFO
+    let mut file = std::fs::read(common::sample("sine_1k_44100.flac")).unwrap();
+    file[20] |= 0x01;
+    file[21] |= 0xf0;
+    let path = common::write_temp("32_bit.flac", &file);
+    match Decoder::open(path) {
+        Err(err @ creak::DecoderError::FormatError { .. }) => assert!(err.to_string().contains("32-bit"), "{}", err),
+        Err(err) => panic!("failed with {:?}", err),
+        Ok(_) => panic!("opened a 32-bit file"),
+    }
+}
@@ -0,0 +1,139 @@
+#![cfg(feature = "http")]
+
+mod common;
+
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+use creak::{Decoder, HttpReader};
+
+/// A test server on localhost that serves `body` at every path, and records the start of the range asked for by each
+/// request (`None` for a request without a `Range` header).
+struct Server {
+    url: String,
+    requests: Arc<Mutex<Vec<Option<u64>>>>,
+}
+
+impl Server {
+    fn start(body: Vec<u8>, honor_ranges: bool) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/audio", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let log = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut range_start = None;
+                let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+                for line in lines.by_ref() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break
+                    }
+                    if let Some(range) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        range_start = range.trim_end_matches('-').parse::<u64>().ok();
+                    }
+                }
+                log.lock().unwrap().push(range_start);
+
+                let len = body.len() as u64;
+                let response = match range_start.filter(|_| honor_ranges) {
+                    Some(start) if start >= len => {
+                        format!("HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", len).into_bytes()
+                    },
+                    Some(start) => {
+                        let mut response = format!(
+                            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                            start, len - 1, len, len - start
+                        ).into_bytes();
+                        response.extend_from_slice(&body[start as usize..]);
+                        response
+                    },
+                    None => {
+                        let mut response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", len).into_bytes();
+                        response.extend_from_slice(&body);
+                        response
+                    },
+                };
+                // The client hangs up early whenever it drops a response to seek, so write errors are expected
+                let _ = stream.write_all(&response);
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn requests(&self) -> Vec<Option<u64>> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// 200 KiB of bytes that don't repeat within 251 bytes, so a read from the wrong offset is noticed.
+fn pattern() -> Vec<u8> {
+    (0..200 * 1024).map(|i| (i % 251) as u8).collect()
+}
+
+fn read_at(reader: &mut HttpReader, pos: SeekFrom, len: usize) -> Vec<u8> {
+    reader.seek(pos).unwrap();
+    let mut buf = vec![0; len];
+    reader.read_exact(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn reads_the_whole_resource() {
+    let body = pattern();
+    let server = Server::start(body.clone(), true);
+    let mut reader = HttpReader::open(&server.url).unwrap();
+    assert_eq!(reader.len(), Some(body.len() as u64));
+    let mut read = vec![];
+    reader.read_to_end(&mut read).unwrap();
+    assert_eq!(read, body);
+    assert!(reader.accepts_ranges());
+    assert_eq!(server.requests(), [Some(0)]);
+}
+
+#[test]
+fn seeks_with_range_requests() {
+    let body = pattern();
+    let server = Server::start(body.clone(), true);
+    let mut reader = HttpReader::open(&server.url).unwrap();
+
+    // A short skip ahead is read through, while a long one or a seek backwards makes a new request
+    assert_eq!(read_at(&mut reader, SeekFrom::Start(100), 10), body[100..110]);
+    assert_eq!(read_at(&mut reader, SeekFrom::Current(1000), 10), body[1110..1120]);
+    assert_eq!(server.requests(), [Some(0)]);
+    assert_eq!(read_at(&mut reader, SeekFrom::Start(150_000), 10), body[150_000..150_010]);
+    assert_eq!(read_at(&mut reader, SeekFrom::Start(50), 10), body[50..60]);
+    assert_eq!(read_at(&mut reader, SeekFrom::End(-10), 10), body[body.len() - 10..]);
+    assert_eq!(server.requests(), [Some(0), Some(150_000), Some(50), Some(body.len() as u64 - 10)]);
+
+    // Past the end, reads come back empty
+    reader.seek(SeekFrom::End(5)).unwrap();
+    assert_eq!(reader.read(&mut [0; 10]).unwrap(), 0);
+}
+
+#[test]
+fn server_that_ignores_ranges_still_seeks() {
+    let body = pattern();
+    let server = Server::start(body.clone(), false);
+    let mut reader = HttpReader::open(&server.url).unwrap();
+    assert!(!reader.accepts_ranges());
+    assert_eq!(reader.len(), Some(body.len() as u64));
+
+    assert_eq!(read_at(&mut reader, SeekFrom::Start(150_000), 10), body[150_000..150_010]);
+    assert_eq!(read_at(&mut reader, SeekFrom::Start(50), 10), body[50..60]);
+    // Once the server has ignored a range, no more are asked for, and seeking backwards downloads it all again
+    assert_eq!(server.requests(), [Some(0), None]);
+}
+
+#[test]
+#[cfg(feature = "wav")]
+fn decodes_wav_over_http() {
+    let file = std::fs::read(common::sample("sine_1k_i16_44100.wav")).unwrap();
+    let server = Server::start(file, true);
+    let mut decoder = Decoder::from_reader(HttpReader::open(&server.url).unwrap()).unwrap();
+    decoder.seek(22050).unwrap();
+    let local = common::decode(Decoder::open(common::sample("sine_1k_i16_44100.wav")).unwrap());
+    assert_eq!(common::decode(decoder), local[22050..]);
+}
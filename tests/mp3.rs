@@ -0,0 +1,78 @@
+#![cfg(feature = "mp3")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use creak::{DecodeEvent, Decoder, DecoderOptions, EventHandler};
+
+/// Gets the byte offsets of the MPEG-1 Layer III frames in `data`, which must start with a frame.
+fn frame_offsets(data: &[u8]) -> Vec<usize> {
+    const BITRATES: [usize; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const SAMPLE_RATES: [usize; 3] = [44100, 48000, 32000];
+    let mut offsets = vec![];
+    let mut pos = 0;
+    while pos + 4 <= data.len() && data[pos] == 0xff {
+        offsets.push(pos);
+        let header = &data[pos..pos + 4];
+        let padding = ((header[2] >> 1) & 1) as usize;
+        pos += 144 * 1000 * BITRATES[(header[2] >> 4) as usize] / SAMPLE_RATES[((header[2] >> 2) & 3) as usize] + padding;
+    }
+    offsets
+}
+
+/// A silent MPEG-1 Layer III frame at 128 kbit/s and 44.1 kHz, in stereo.
+fn silent_stereo_frame() -> Vec<u8> {
+    let mut frame = vec![0xff, 0xfb, 0x90, 0x04];
+    frame.resize(417, 0);
+    frame
+}
+
+#[test]
+fn frame_with_wrong_channel_count_is_replaced_by_silence() {
+    let original = std::fs::read(common::sample("sine_1k_44100.mp3")).unwrap();
+    let original_len = common::decode(Decoder::open(common::sample("sine_1k_44100.mp3")).unwrap()).len() as u64;
+
+    // Insert a stereo frame into the mono stream, before a frame that doesn't reach back into the bit reservoir, so
+    // the frames around it still decode
+    let offsets = frame_offsets(&original);
+    let insert_at = *offsets.iter().skip(1).find(|&&offset| original[offset + 4] == 0 && original[offset + 5] & 0x80 == 0).unwrap();
+    let mut file = original[..insert_at].to_vec();
+    file.extend_from_slice(&silent_stereo_frame());
+    file.extend_from_slice(&original[insert_at..]);
+    let path = common::write_temp("wrong_channels.mp3", &file);
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let log = events.clone();
+    let options = DecoderOptions {
+        reset_mp3_on_error: true,
+        on_event: Some(EventHandler::new(move |event| log.lock().unwrap().push(event.clone()))),
+        ..DecoderOptions::default()
+    };
+    let decoder = Decoder::open_with_options(path, options).unwrap();
+    assert_eq!(decoder.channels(), 1);
+    let samples = common::decode(decoder);
+    assert_eq!(samples.len() as u64, original_len + 1152);
+
+    let events = events.lock().unwrap();
+    let skipped: Vec<_> = events.iter()
+        .filter_map(|event| match event {
+            DecodeEvent::FrameSkipped { frame, len } => Some((*frame, *len)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(skipped.len(), 1);
+    let (skipped_frame, skipped_len) = skipped[0];
+    assert_eq!(skipped_len, 1152);
+    assert!(samples[skipped_frame as usize..(skipped_frame + skipped_len) as usize].iter().all(|sample| *sample == 0.0));
+
+    // Every frame, including the replaced one, starts where the one before it ended
+    let mut next_frame = 0;
+    for event in events.iter() {
+        if let DecodeEvent::FrameStart { frame, len } = event {
+            assert_eq!((*frame, *len), (next_frame, 1152));
+            next_frame += len;
+        }
+    }
+    assert_eq!(next_frame, samples.len() as u64);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use creak::{ChannelLayout, Decoder, Endian, RawAudioSpec, RawSampleFormat};
+
+/// Packs `values`, each `bits` wide, into a bit stream in the order the packed raw formats store them.
+fn pack(values: &[u32], bits: usize, endian: Endian) -> Vec<u8> {
+    let mut bytes = vec![0u8; (values.len() * bits).div_ceil(8)];
+    let mut pos = 0;
+    for value in values {
+        for bit in 0..bits {
+            let (value_bit, byte_bit) = match endian {
+                Endian::Little => (bit, pos % 8),
+                Endian::Big => (bits - 1 - bit, 7 - pos % 8),
+            };
+            if value >> value_bit & 1 != 0 {
+                bytes[pos / 8] |= 1 << byte_bit;
+            }
+            pos += 1;
+        }
+    }
+    bytes
+}
+
+/// Packs `values` in `format`, decodes them as mono raw audio, and checks that they come back as expected.
+fn assert_packed_round_trip(name: &str, format: RawSampleFormat, endian: Endian, values: &[u32]) {
+    let bits = format.bits_per_sample();
+    let signed = matches!(format, RawSampleFormat::Signed12 | RawSampleFormat::Signed20);
+    let expected: Vec<f32> = values.iter()
+        .map(|&value| if signed {
+            // Sign-extend from the sample's width
+            let value = ((value << (32 - bits)) as i32 >> (32 - bits)) as f64;
+            (value / ((1u32 << (bits - 1)) - 1) as f64) as f32
+        } else {
+            (value as f64 / ((1u32 << bits) - 1) as f64 * 2.0 - 1.0) as f32
+        })
+        .collect();
+    let path = common::write_temp(name, &pack(values, bits, endian));
+    let decoder = Decoder::open_raw(path, RawAudioSpec::pcm(44100, 1, format, endian)).unwrap();
+    assert_eq!(decoder.info().total_frames(), Some(values.len() as u64));
+    assert_eq!(common::decode(decoder), expected);
+}
+
+/// An odd number of 12-bit values, so that the last one doesn't fill its group.
+const VALUES_12: [u32; 7] = [0x000, 0xfff, 0x800, 0x7ff, 0x123, 0xabc, 0x001];
+
+/// An odd number of 20-bit values, so that the last one doesn't fill its group.
+const VALUES_20: [u32; 5] = [0x00000, 0xfffff, 0x80000, 0x7ffff, 0x12345];
+
+#[test]
+fn packed_12_bit_round_trips() {
+    assert_packed_round_trip("packed_s12_le.raw", RawSampleFormat::Signed12, Endian::Little, &VALUES_12);
+    assert_packed_round_trip("packed_s12_be.raw", RawSampleFormat::Signed12, Endian::Big, &VALUES_12);
+    assert_packed_round_trip("packed_u12_le.raw", RawSampleFormat::Unsigned12, Endian::Little, &VALUES_12);
+    assert_packed_round_trip("packed_u12_be.raw", RawSampleFormat::Unsigned12, Endian::Big, &VALUES_12);
+}
+
+#[test]
+fn packed_20_bit_round_trips() {
+    assert_packed_round_trip("packed_s20_le.raw", RawSampleFormat::Signed20, Endian::Little, &VALUES_20);
+    assert_packed_round_trip("packed_s20_be.raw", RawSampleFormat::Signed20, Endian::Big, &VALUES_20);
+    assert_packed_round_trip("packed_u20_le.raw", RawSampleFormat::Unsigned20, Endian::Little, &VALUES_20);
+    assert_packed_round_trip("packed_u20_be.raw", RawSampleFormat::Unsigned20, Endian::Big, &VALUES_20);
+}
+
+#[test]
+fn packed_12_bit_matches_byte_layout() {
+    // 0xabc then 0x123, laid out as the RawSampleFormat docs describe
+    let little = common::write_temp("layout_u12_le.raw", &[0xbc, 0x3a, 0x12]);
+    let big = common::write_temp("layout_u12_be.raw", &[0xab, 0xc1, 0x23]);
+    for (path, endian) in [(little, Endian::Little), (big, Endian::Big)] {
+        let decoder = Decoder::open_raw(path, RawAudioSpec::pcm(44100, 1, RawSampleFormat::Unsigned12, endian)).unwrap();
+        let expected = [0xabc, 0x123].map(|value: u32| (value as f64 / 4095.0 * 2.0 - 1.0) as f32);
+        assert_eq!(common::decode(decoder), expected);
+    }
+}
+
+#[test]
+fn planar_stereo_decodes_interleaved() {
+    // Left plane holds 1, 2, 3 and right plane holds -1, -2, -3
+    let samples: Vec<u8> = [1i16, 2, 3, -1, -2, -3].iter().flat_map(|sample| sample.to_le_bytes()).collect();
+    let path = common::write_temp("planar_stereo.raw", &samples);
+    let spec = RawAudioSpec {
+        layout: ChannelLayout::Planar,
+        ..RawAudioSpec::pcm(44100, 2, RawSampleFormat::Signed16, Endian::Little)
+    };
+    let decoder = Decoder::open_raw(path, spec).unwrap();
+    assert_eq!(decoder.info().total_frames(), Some(3));
+    let expected = [1i16, -1, 2, -2, 3, -3].map(|sample| sample as f32 / i16::MAX as f32);
+    assert_eq!(common::decode(decoder), expected);
+}
+
+#[test]
+fn planar_stereo_seeks_within_each_plane() {
+    let samples: Vec<u8> = [1i16, 2, 3, -1, -2, -3].iter().flat_map(|sample| sample.to_le_bytes()).collect();
+    let path = common::write_temp("planar_stereo_seek.raw", &samples);
+    let spec = RawAudioSpec {
+        layout: ChannelLayout::Planar,
+        ..RawAudioSpec::pcm(44100, 2, RawSampleFormat::Signed16, Endian::Little)
+    };
+    let mut decoder = Decoder::open_raw(path, spec).unwrap();
+    decoder.seek(1).unwrap();
+    let expected = [2i16, -2, 3, -3].map(|sample| sample as f32 / i16::MAX as f32);
+    assert_eq!(common::decode(decoder), expected);
+}
@@ -0,0 +1,266 @@
+#![cfg(feature = "wav")]
+
+mod common;
+
+use creak::Decoder;
+
+#[test]
+fn decodes_64_bit_float() {
+    let decoder = Decoder::open(common::sample("sine_1k_f64_44100.wav")).unwrap();
+    assert_eq!(decoder.info().total_frames(), Some(4410));
+    let samples = common::decode(decoder);
+    assert_eq!(samples.len(), 4410);
+    for (frame, sample) in samples.iter().enumerate() {
+        assert!((sample - common::sine_1k(frame, 0.5)).abs() < 1e-6, "frame {} decoded to {}", frame, sample);
+    }
+}
+
+#[test]
+fn huge_fmt_length_fails_without_allocating_it() {
+    let mut file = b"RIFF\0\0\0\0WAVEfmt \xf0\xff\xff\xff".to_vec();
+    file.extend_from_slice(&[1, 0, 1, 0, 0x44, 0xac, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0]);
+    let path = common::write_temp("huge_fmt.wav", &file);
+    assert!(matches!(Decoder::open(path), Err(creak::DecoderError::FormatError { .. })));
+}
+
+#[test]
+fn huge_fact_length_fails_without_allocating_it() {
+    let mut file = b"RIFF\0\0\0\0WAVEfmt \x10\0\0\0".to_vec();
+    file.extend_from_slice(&[3, 0, 1, 0, 0x44, 0xac, 0, 0, 0x10, 0xb1, 2, 0, 4, 0, 32, 0]);
+    file.extend_from_slice(b"fact\xf0\xff\xff\xff\x10\0\0\0");
+    let path = common::write_temp("huge_fact.wav", &file);
+    assert!(matches!(Decoder::open(path), Err(creak::DecoderError::FormatError { .. })));
+}
+
+#[test]
+fn odd_length_data_chunk_streams_every_frame() {
+    let decoder = Decoder::open(common::sample("sine_1k_u8_44100_odd.wav")).unwrap();
+    assert_eq!(decoder.info().total_frames(), Some(44101));
+    let samples = common::decode(decoder);
+    assert_eq!(samples.len(), 44101);
+    for (frame, sample) in samples.iter().enumerate() {
+        assert!((sample - common::sine_1k(frame, 1.0)).abs() < 1.0 / 64.0, "frame {} decoded to {}", frame, sample);
+    }
+}
+
+#[test]
+fn odd_length_data_chunk_seeks_to_last_frame() {
+    let mut decoder = Decoder::open(common::sample("sine_1k_u8_44100_odd.wav")).unwrap();
+    decoder.seek(44100).unwrap();
+    let samples = common::decode(decoder);
+    assert_eq!(samples.len(), 1);
+    assert!((samples[0] - common::sine_1k(44100, 1.0)).abs() < 1.0 / 64.0);
+}
+
+#[test]
+fn truncated_data_chunk_ends_with_incomplete_data() {
+    let file = std::fs::read(common::sample("sine_1k_i16_44100.wav")).unwrap();
+    let path = common::write_temp("truncated.wav", &file[..file.len() / 2]);
+    let results: Vec<_> = Decoder::open(path).unwrap().into_samples().unwrap().collect();
+    assert!(matches!(results.last(), Some(Err(creak::DecoderError::IncompleteData))));
+    assert!(results[..results.len() - 1].iter().all(Result::is_ok));
+}
+
+/// Builds a mono 8-bit WAV file at 44.1 kHz with the given format tag and data chunk.
+fn companded_wav(format_tag: u16, data: &[u8]) -> Vec<u8> {
+    let mut file = b"RIFF\0\0\0\0WAVEfmt \x12\0\0\0".to_vec();
+    file.extend_from_slice(&format_tag.to_le_bytes());
+    file.extend_from_slice(&[1, 0, 0x44, 0xac, 0, 0, 0x44, 0xac, 0, 0, 1, 0, 8, 0, 0, 0]);
+    file.extend_from_slice(b"fact\x04\0\0\0");
+    file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    file.extend_from_slice(data);
+    let riff_len = file.len() as u32 - 8;
+    file[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    file
+}
+
+#[test]
+fn decodes_a_law() {
+    let decoder = Decoder::open(common::sample("sine_1k_alaw_44100.wav")).unwrap();
+    assert_eq!(decoder.info().total_frames(), Some(4410));
+    let samples = common::decode(decoder);
+    assert_eq!(samples.len(), 4410);
+    for (frame, sample) in samples.iter().enumerate() {
+        assert!((sample - common::sine_1k(frame, 0.5)).abs() < 0.02, "frame {} decoded to {}", frame, sample);
+    }
+}
+
+#[test]
+fn decodes_mu_law() {
+    let decoder = Decoder::open(common::sample("sine_1k_mulaw_44100.wav")).unwrap();
+    assert_eq!(decoder.info().total_frames(), Some(4410));
+    let samples = common::decode(decoder);
+    assert_eq!(samples.len(), 4410);
+    for (frame, sample) in samples.iter().enumerate() {
+        assert!((sample - common::sine_1k(frame, 0.5)).abs() < 0.02, "frame {} decoded to {}", frame, sample);
+    }
+}
+
+#[test]
+fn a_law_code_points_expand_to_g711_values() {
+    let path = common::write_temp("code_points_alaw.wav", &companded_wav(6, &[0xd5, 0x55, 0xaa, 0x2a]));
+    let expected = [8, -8, 32256, -32256].map(|value: i16| value as f32 / i16::MAX as f32);
+    assert_eq!(common::decode(Decoder::open(path).unwrap()), expected);
+}
+
+#[test]
+fn mu_law_code_points_expand_to_g711_values() {
+    let path = common::write_temp("code_points_mulaw.wav", &companded_wav(7, &[0xff, 0x7f, 0x80, 0x00]));
+    let expected = [0, 0, 32124, -32124].map(|value: i16| value as f32 / i16::MAX as f32);
+    assert_eq!(common::decode(Decoder::open(path).unwrap()), expected);
+}
+
+/// Reads the reference decode of `sine_1k_ima_44100.wav`, which holds interleaved 16-bit little-endian samples.
+fn ima_reference() -> Vec<f32> {
+    std::fs::read(common::sample("sine_1k_ima_44100_ref.raw")).unwrap()
+        .chunks_exact(2)
+        .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / i16::MAX as f32)
+        .collect()
+}
+
+#[test]
+fn decodes_ima_adpcm() {
+    let decoder = Decoder::open(common::sample("sine_1k_ima_44100.wav")).unwrap();
+    assert_eq!(decoder.channels(), 2);
+    assert_eq!(decoder.info().total_frames(), Some(4410));
+    assert_eq!(common::decode(decoder), ima_reference());
+}
+
+#[test]
+fn ima_adpcm_seeks_into_the_middle_of_a_block() {
+    // Blocks hold 505 frames each, so this lands partway through the third one
+    let mut decoder = Decoder::open(common::sample("sine_1k_ima_44100.wav")).unwrap();
+    decoder.seek(1234).unwrap();
+    assert_eq!(common::decode(decoder), ima_reference()[1234 * 2..]);
+}
+
+#[test]
+fn zero_channels_are_rejected() {
+    let mut file = std::fs::read(common::sample("sine_1k_i16_44100.wav")).unwrap();
+    file[22..24].copy_from_slice(&[0, 0]);
+    let path = common::write_temp("zero_channels.wav", &file);
+    match Decoder::open(path) {
+        Err(err @ creak::DecoderError::FormatError { .. }) => assert!(err.to_string().contains("zero channels"), "{}", err),
+        Err(err) => panic!("failed with {:?}", err),
+        Ok(_) => panic!("opened a file with zero channels"),
+    }
+}
+
+#[test]
+fn zero_sample_rate_is_rejected() {
+    let mut file = std::fs::read(common::sample("sine_1k_i16_44100.wav")).unwrap();
+    file[24..28].copy_from_slice(&[0; 4]);
+    let path = common::write_temp("zero_sample_rate.wav", &file);
+    match Decoder::open(path) {
+        Err(err @ creak::DecoderError::FormatError { .. }) => assert!(err.to_string().contains("0 Hz"), "{}", err),
+        Err(err) => panic!("failed with {:?}", err),
+        Ok(_) => panic!("opened a file with a sample rate of 0 Hz"),
+    }
+}
+
+#[test]
+fn broadcast_wav_with_bext_before_data() {
+    // An odd-length coding history leaves the chunk odd-length too, so a pad byte follows it
+    let mut bext = vec![0u8; 602];
+    bext[..10].copy_from_slice(b"1 kHz sine");
+    bext[256..261].copy_from_slice(b"creak");
+    bext[320..330].copy_from_slice(b"2026-10-14");
+    bext[330..338].copy_from_slice(b"12:34:56");
+    bext[338..342].copy_from_slice(&44100u32.to_le_bytes());
+    bext.extend_from_slice(b"A=PCM\r\n");
+
+    let original = std::fs::read(common::sample("sine_1k_i16_44100.wav")).unwrap();
+    let mut file = original[..36].to_vec();
+    file.extend_from_slice(b"bext");
+    file.extend_from_slice(&(bext.len() as u32).to_le_bytes());
+    file.extend_from_slice(&bext);
+    file.push(0);
+    file.extend_from_slice(&original[36..]);
+    let riff_len = file.len() as u32 - 8;
+    file[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    let path = common::write_temp("bext_before_data.wav", &file);
+
+    let decoder = Decoder::open(&path).unwrap();
+    let info = decoder.broadcast_info().unwrap();
+    assert_eq!(info.description(), "1 kHz sine");
+    assert_eq!(info.originator(), "creak");
+    assert_eq!(info.origination_date(), "2026-10-14");
+    assert_eq!(info.origination_time(), "12:34:56");
+    assert_eq!(info.time_reference(), 44100);
+    assert_eq!(info.version(), 0);
+    assert_eq!(info.coding_history(), "A=PCM\r\n");
+    assert_eq!(common::decode(decoder), common::decode(Decoder::open(common::sample("sine_1k_i16_44100.wav")).unwrap()));
+}
+
+/// Builds a 16-bit mono RF64 file whose `ds64` chunk gives the data size as `data_len`, followed by `frames` frames.
+fn rf64_wav(data_len: u64, frames: usize) -> Vec<u8> {
+    let mut file = b"RF64\xff\xff\xff\xffWAVEds64\x1c\0\0\0".to_vec();
+    file.extend_from_slice(&0u64.to_le_bytes());
+    file.extend_from_slice(&data_len.to_le_bytes());
+    file.extend_from_slice(&(frames as u64).to_le_bytes());
+    file.extend_from_slice(&0u32.to_le_bytes());
+    file.extend_from_slice(b"fmt \x10\0\0\0");
+    file.extend_from_slice(&[1, 0, 1, 0, 0x44, 0xac, 0, 0, 0x88, 0x58, 1, 0, 2, 0, 16, 0]);
+    file.extend_from_slice(b"data\xff\xff\xff\xff");
+    file.extend((0..frames * 2).map(|_| 0));
+    file
+}
+
+#[test]
+fn rf64_data_size_is_read_from_ds64() {
+    let path = common::write_temp("rf64.wav", &rf64_wav(20, 10));
+    let decoder = Decoder::open(&path).unwrap();
+    assert_eq!(decoder.info().total_frames(), Some(10));
+    assert_eq!(common::decode(decoder).len(), 10);
+}
+
+#[test]
+fn rf64_data_size_overflowing_its_offset_is_rejected() {
+    for data_len in [u64::MAX, u64::MAX - 10] {
+        let path = common::write_temp("rf64_huge_data.wav", &rf64_wav(data_len, 10));
+        assert!(matches!(Decoder::open(&path), Err(creak::DecoderError::FormatError { .. })), "data size {:#x}", data_len);
+        assert!(matches!(Decoder::open_range(&path, 80, 100), Err(creak::DecoderError::FormatError { .. })), "data size {:#x}", data_len);
+    }
+}
+
+#[test]
+fn rf64_data_size_near_the_largest_offset_does_not_overflow() {
+    let path = common::write_temp("rf64_near_max_data.wav", &rf64_wav(u64::MAX - 200, 10));
+    // The data chunk runs far past the end of the file, so decoding stops there as it would for any truncated file
+    let results: Vec<_> = Decoder::open(&path).unwrap().into_samples().unwrap().collect();
+    assert_eq!(results.len(), 11);
+    assert!(matches!(results.last(), Some(Err(creak::DecoderError::IncompleteData))));
+    let decoder = Decoder::open_range(&path, 80, 100).unwrap();
+    assert_eq!(common::decode(decoder).len(), 10);
+}
+
+#[test]
+fn eight_bit_pcm_decodes_the_same_on_every_path() {
+    let data = [0, 128, 255, 64];
+    let expected = [-128.0 / 127.0, 0.0, 1.0, -64.0 / 127.0];
+    let file = companded_wav(1, &data);
+    let path = common::write_temp("8_bit.wav", &file);
+    assert_eq!(common::decode(Decoder::open(&path).unwrap()), expected, "hound");
+
+    // hound doesn't skip the pad byte after an odd-length chunk, so this file falls back to creak's own reader
+    let mut padded = file[..50].to_vec();
+    padded.extend_from_slice(b"junk\x01\0\0\0\0\0");
+    padded.extend_from_slice(&file[50..]);
+    let riff_len = padded.len() as u32 - 8;
+    padded[4..8].copy_from_slice(&riff_len.to_le_bytes());
+    let padded_path = common::write_temp("8_bit_padded.wav", &padded);
+    assert_eq!(common::decode(Decoder::open(&padded_path).unwrap()), expected, "fallback");
+
+    assert_eq!(common::decode(Decoder::open_range(&path, 58, 62).unwrap()), expected, "byte range");
+    assert_eq!(common::decode(Decoder::open_with_data_offset(&path, 58).unwrap()), expected, "data offset");
+    assert_eq!(common::decode(Decoder::from_wav_stream(std::io::Cursor::new(file.clone())).unwrap()), expected, "stream");
+
+    let mut appended = file.clone();
+    appended.extend_from_slice(&file);
+    let appended_path = common::write_temp("8_bit_appended.wav", &appended);
+    let options = creak::DecoderOptions { concatenated_wav: true, ..creak::DecoderOptions::default() };
+    let samples = common::decode(Decoder::open_with_options(&appended_path, options).unwrap());
+    assert_eq!(samples, [expected, expected].concat(), "concatenated");
+}